@@ -0,0 +1,180 @@
+use ty::{self, Type, TypeContext, Mutability};
+
+// functions that the compiler provides itself. A user-defined function with
+// the same name always takes precedence. Every builtin is generic over one
+// type `T`, which can be given explicitly, as in `alloc::<u8>(0)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Builtin {
+    WrappingAdd,
+    WrappingSub,
+    WrappingMul,
+    // checked_*(a: T, b: T) -> (T, bool); the wrapped result, and whether
+    // it overflowed
+    CheckedAdd,
+    CheckedSub,
+    CheckedMul,
+    SaturatingAdd,
+    SaturatingSub,
+    SaturatingMul,
+    // alloc(value: T) -> &mut T
+    Alloc,
+    // alloc_array(count: usize, value: T) -> &mut T; every element is set to
+    // `value`. Panics if `count` is 0, since there's no element to point to
+    AllocArray,
+    // free(ptr: &mut T); unsafe, since `ptr` may still be used afterwards
+    Free,
+    // null() -> *const T
+    Null,
+    // null_mut() -> *mut T
+    NullMut,
+    // is_null(ptr: *T) -> bool, for either kind of raw pointer
+    IsNull,
+    // offset(ptr: *T, count: isize) -> *T; `count` is in elements, not bytes
+    Offset,
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wrapping_add" => Some(Builtin::WrappingAdd),
+            "wrapping_sub" => Some(Builtin::WrappingSub),
+            "wrapping_mul" => Some(Builtin::WrappingMul),
+            "checked_add" => Some(Builtin::CheckedAdd),
+            "checked_sub" => Some(Builtin::CheckedSub),
+            "checked_mul" => Some(Builtin::CheckedMul),
+            "saturating_add" => Some(Builtin::SaturatingAdd),
+            "saturating_sub" => Some(Builtin::SaturatingSub),
+            "saturating_mul" => Some(Builtin::SaturatingMul),
+            "alloc" => Some(Builtin::Alloc),
+            "alloc_array" => Some(Builtin::AllocArray),
+            "free" => Some(Builtin::Free),
+            "null" => Some(Builtin::Null),
+            "null_mut" => Some(Builtin::NullMut),
+            "is_null" => Some(Builtin::IsNull),
+            "offset" => Some(Builtin::Offset),
+            _ => None,
+        }
+    }
+
+    pub fn arity(self) -> usize {
+        match self {
+            Builtin::Null | Builtin::NullMut => 0,
+            Builtin::Alloc | Builtin::Free | Builtin::IsNull => 1,
+            _ => 2,
+        }
+    }
+
+    // whether `T` must be an integer
+    pub fn is_arithmetic(self) -> bool {
+        match self {
+            Builtin::WrappingAdd |
+            Builtin::WrappingSub |
+            Builtin::WrappingMul |
+            Builtin::CheckedAdd |
+            Builtin::CheckedSub |
+            Builtin::CheckedMul |
+            Builtin::SaturatingAdd |
+            Builtin::SaturatingSub |
+            Builtin::SaturatingMul => true,
+            _ => false,
+        }
+    }
+
+    // whether it may only be called inside an `unsafe` block
+    pub fn is_unsafe(self) -> bool {
+        self == Builtin::Free
+    }
+
+    // whether the first argument is a raw pointer of either mutability
+    pub fn takes_raw_pointer(self) -> bool {
+        match self {
+            Builtin::IsNull | Builtin::Offset => true,
+            _ => false,
+        }
+    }
+
+    // the argument and return types, given `T`. `mutability` is that of the
+    // raw pointer, for the builtins which take one
+    pub fn signature<'t>(self,
+                         param: Type<'t>,
+                         mutability: Mutability,
+                         ctxt: &'t TypeContext<'t>)
+                         -> (Vec<Type<'t>>, Type<'t>) {
+        let ptr = Type::reference(param, Mutability::Mutable, ctxt);
+        let raw_ptr = Type::raw_pointer(param, mutability, ctxt);
+        match self {
+            Builtin::Alloc => (vec![param], ptr),
+            Builtin::AllocArray => (vec![Type::uint(ty::Int::Pointer, ctxt), param], ptr),
+            Builtin::Free => (vec![ptr], Type::unit(ctxt)),
+            Builtin::Null => (vec![], Type::raw_pointer(param, Mutability::Immutable, ctxt)),
+            Builtin::NullMut => (vec![], Type::raw_pointer(param, Mutability::Mutable, ctxt)),
+            Builtin::IsNull => (vec![raw_ptr], Type::bool(ctxt)),
+            Builtin::Offset => {
+                (vec![raw_ptr, Type::sint(ty::Int::Pointer, ctxt)], raw_ptr)
+            }
+            Builtin::CheckedAdd | Builtin::CheckedSub | Builtin::CheckedMul => {
+                (vec![param, param], Type::tuple(vec![param, Type::bool(ctxt)], ctxt))
+            }
+            _ => (vec![param, param], param),
+        }
+    }
+}
+
+// `panic`, `unreachable`, `assert` and `assert_eq`, which take an optional
+// string literal as the last argument. Like the other builtins, a function
+// with the same name takes precedence; typeck turns the calls of the rest
+// into `Panic`s and `Assert`s
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Panicking {
+    Panic,
+    Unreachable,
+    Assert,
+    AssertEq,
+}
+
+impl Panicking {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "panic" => Some(Panicking::Panic),
+            "unreachable" => Some(Panicking::Unreachable),
+            "assert" => Some(Panicking::Assert),
+            "assert_eq" => Some(Panicking::AssertEq),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Panicking::Panic => "panic",
+            Panicking::Unreachable => "unreachable",
+            Panicking::Assert => "assert",
+            Panicking::AssertEq => "assert_eq",
+        }
+    }
+
+    // the number of arguments before the message
+    pub fn arity(self) -> usize {
+        match self {
+            Panicking::Panic | Panicking::Unreachable => 0,
+            Panicking::Assert => 1,
+            Panicking::AssertEq => 2,
+        }
+    }
+
+    // what's printed after the location, given the user's message
+    pub fn message(self, message: Option<&str>) -> String {
+        match (self, message) {
+            (Panicking::Panic, Some(message)) => message.to_owned(),
+            (Panicking::Panic, None) => "explicit panic".to_owned(),
+            (Panicking::Unreachable, Some(message)) => {
+                format!("internal error: entered unreachable code: {}", message)
+            }
+            (Panicking::Unreachable, None) => {
+                "internal error: entered unreachable code".to_owned()
+            }
+            (_, Some(message)) => format!("assertion failed: {}", message),
+            (Panicking::Assert, None) => "assertion failed".to_owned(),
+            (Panicking::AssertEq, None) => "assertion failed: left == right".to_owned(),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt::{Display, Formatter, Error};
+use ast::{Ast, Block};
+use ast::builtin::Panicking;
+use ast::expr::{Stmt, Expr, ExprKind, Pattern};
+use ast::fmt::operator;
+use ty::Mutability;
+
+const INDENT: &'static str = "  ";
+
+// the tree printed by --print-ast and --print-typed-ast, without the prelude's
+// items. Every expression is annotated with its type, which is `_` until it's
+// been inferred
+impl<'t> Display for Ast<'t> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        let mut names = self.consts.keys().filter(|name| !self.prelude.contains(*name))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let (ty, ref init) = self.consts[name];
+            try!(writeln!(f, "const {}: {}", name, ty));
+            try!(block(f, init, 1));
+            try!(writeln!(f, ""));
+        }
+        let mut names = self.statics.keys().filter(|name| !self.prelude.contains(*name))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let (ty, mutability, ref init) = self.statics[name];
+            try!(writeln!(f, "static {}{}: {}", mutable(mutability), name, ty));
+            try!(block(f, init, 1));
+            try!(writeln!(f, ""));
+        }
+        let mut names = self.externs.keys().filter(|name| !self.prelude.contains(*name))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let ty = &self.externs[name];
+            let inputs = ty.input().iter().map(|input| input.to_string()).collect::<Vec<_>>();
+            try!(writeln!(f, "extern fn {}({}) -> {}\n", name, inputs.join(", "), ty.output()));
+        }
+        let mut names = self.functions.keys().filter(|name| !self.prelude.contains(*name))
+            .collect::<Vec<_>>();
+        names.sort();
+        for name in names {
+            let (ref func, ref body) = self.functions[name];
+            let mut args = func.args.iter().collect::<Vec<_>>();
+            args.sort_by_key(|&(_, &(index, _, _))| index);
+            let args = args.into_iter()
+                .map(|(name, &(_, ty, mutability))| {
+                    format!("{}{}: {}", mutable(mutability), name, ty)
+                })
+                .collect::<Vec<_>>();
+            try!(writeln!(f, "fn {}({}) -> {}", name, args.join(", "), func.ret_ty));
+            try!(block(f, body, 1));
+            try!(writeln!(f, ""));
+        }
+        Ok(())
+    }
+}
+
+// the statements of `blk`, then its value, marked with `=>`
+fn block(f: &mut Formatter, blk: &Block, depth: usize) -> Result<(), Error> {
+    for stmt in &blk.stmts {
+        match *stmt {
+            Stmt::Let { ref pattern, ty, ref value } => {
+                try!(writeln!(f, "{}let {}: {}", indent(depth), self::pattern(pattern), ty));
+                if let Some(ref value) = *value {
+                    try!(expr(f, value, depth + 1, ""));
+                }
+            }
+            Stmt::Expr(ref e) => try!(expr(f, e, depth, "")),
+        }
+    }
+    if let Some(ref e) = blk.expr {
+        try!(expr(f, e, depth, "=> "));
+    }
+    Ok(())
+}
+
+fn expr(f: &mut Formatter, e: &Expr, depth: usize, prefix: &str) -> Result<(), Error> {
+    let label = match e.kind {
+        ExprKind::Call { ref callee, ref ty_args, ref message, method, .. } => {
+            let mut label = format!("Call {}", callee);
+            if !ty_args.is_empty() {
+                let ty_args = ty_args.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                label.push_str(&format!("::<{}>", ty_args.join(", ")));
+            }
+            if let Some(ref message) = *message {
+                label.push_str(&format!(" {:?}", message.text));
+            }
+            if method {
+                label.push_str(" (method)");
+            }
+            label
+        }
+        ExprKind::If { .. } => "If".to_owned(),
+        ExprKind::Block(_) => "Block".to_owned(),
+        ExprKind::Unsafe(_) => "Unsafe".to_owned(),
+        ExprKind::Binop { op, .. } => format!("Binop {}", operator(op)),
+        ExprKind::Pos(_) => "Pos".to_owned(),
+        ExprKind::Neg(_) => "Neg".to_owned(),
+        ExprKind::Not(_) => "Not".to_owned(),
+        ExprKind::Ref(_, Mutability::Immutable) => "Ref".to_owned(),
+        ExprKind::Ref(_, Mutability::Mutable) => "Ref mut".to_owned(),
+        ExprKind::Deref(_) => "Deref".to_owned(),
+        ExprKind::Cast(_) => "Cast".to_owned(),
+        ExprKind::Tuple(_) => "Tuple".to_owned(),
+        ExprKind::Field(_, index) => format!("Field {}", index),
+        ExprKind::Variable(ref name) => format!("Variable {}", name),
+        ExprKind::IntLiteral(value) => format!("IntLiteral {}", value),
+        ExprKind::FloatLiteral(value) => format!("FloatLiteral {:?}", value),
+        ExprKind::CharLiteral(value) => format!("CharLiteral {:?}", value),
+        ExprKind::BoolLiteral(value) => format!("BoolLiteral {}", value),
+        ExprKind::UnitLiteral => "UnitLiteral".to_owned(),
+        ExprKind::Return(_) => "Return".to_owned(),
+        ExprKind::Panic { builtin, ref message } => {
+            panicking("Panic", builtin, message.as_ref())
+        }
+        ExprKind::Assert { builtin, ref message, .. } => {
+            panicking("Assert", builtin, message.as_ref())
+        }
+        ExprKind::Assign { .. } => "Assign".to_owned(),
+        ExprKind::CompoundAssign { op, .. } => format!("CompoundAssign {}=", operator(op)),
+    };
+    try!(writeln!(f, "{}{}{}: {}", indent(depth), prefix, label, e.ty));
+
+    let depth = depth + 1;
+    match e.kind {
+        ExprKind::Call { ref args, .. } | ExprKind::Tuple(ref args) => {
+            for arg in args {
+                try!(expr(f, arg, depth, ""));
+            }
+        }
+        ExprKind::If { ref condition, ref then_value, ref else_value } => {
+            try!(expr(f, condition, depth, ""));
+            try!(writeln!(f, "{}then", indent(depth)));
+            try!(block(f, then_value, depth + 1));
+            try!(writeln!(f, "{}else", indent(depth)));
+            try!(block(f, else_value, depth + 1));
+        }
+        ExprKind::Block(ref blk) | ExprKind::Unsafe(ref blk) => {
+            try!(block(f, blk, depth));
+        }
+        ExprKind::Binop { ref lhs, ref rhs, .. } |
+        ExprKind::Assign { dst: ref lhs, src: ref rhs } |
+        ExprKind::CompoundAssign { dst: ref lhs, src: ref rhs, .. } => {
+            try!(expr(f, lhs, depth, ""));
+            try!(expr(f, rhs, depth, ""));
+        }
+        ExprKind::Pos(ref inner) |
+        ExprKind::Neg(ref inner) |
+        ExprKind::Not(ref inner) |
+        ExprKind::Ref(ref inner, _) |
+        ExprKind::Deref(ref inner) |
+        ExprKind::Cast(ref inner) |
+        ExprKind::Field(ref inner, _) |
+        ExprKind::Return(ref inner) |
+        ExprKind::Assert { condition: ref inner, .. } => {
+            try!(expr(f, inner, depth, ""));
+        }
+        ExprKind::Variable(_) |
+        ExprKind::IntLiteral(_) |
+        ExprKind::FloatLiteral(_) |
+        ExprKind::CharLiteral(_) |
+        ExprKind::BoolLiteral(_) |
+        ExprKind::UnitLiteral |
+        ExprKind::Panic { .. } => {}
+    }
+    Ok(())
+}
+
+fn panicking(kind: &str, builtin: Panicking, message: Option<&String>) -> String {
+    match message {
+        Some(message) => format!("{} {} {:?}", kind, builtin.name(), message),
+        None => format!("{} {}", kind, builtin.name()),
+    }
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    match *pattern {
+        Pattern::Binding { ref name, mutability } => format!("{}{}", mutable(mutability), name),
+        Pattern::Tuple(ref elements) => {
+            let elements = elements.iter().map(self::pattern).collect::<Vec<_>>();
+            format!("({})", elements.join(", "))
+        }
+    }
+}
+
+fn mutable(mutability: Mutability) -> &'static str {
+    match mutability {
+        Mutability::Immutable => "",
+        Mutability::Mutable => "mut ",
+    }
+}
+
+fn indent(depth: usize) -> String {
+    INDENT.repeat(depth)
+}
@@ -0,0 +1,484 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use parse::Operand;
+use ty::{self, Type, TypeVariant};
+use mir;
+use super::{AstError, Block};
+use super::expr::{Stmt, Expr, ExprKind, Pattern};
+
+// evaluates the initializers of consts and statics at compile time. Only
+// literals, other consts, the unary and binary operators, and blocks and
+// `if`s without statements are constant.
+pub struct ConstEval<'a, 't: 'a> {
+    // the typechecked initializers of the consts
+    consts: &'a HashMap<String, (Type<'t>, Block<'t>)>,
+    values: HashMap<String, Const>,
+    // the consts whose initializers are being evaluated, to catch cycles
+    evaluating: Vec<String>,
+}
+
+// chars are ints
+#[derive(Copy, Clone, Debug)]
+enum Const {
+    Int(u64),
+    Float(f64),
+    Bool(bool),
+    Unit,
+}
+
+impl<'a, 't> ConstEval<'a, 't> {
+    pub fn new(consts: &'a HashMap<String, (Type<'t>, Block<'t>)>) -> Self {
+        ConstEval {
+            consts: consts,
+            values: HashMap::new(),
+            evaluating: Vec::new(),
+        }
+    }
+
+    pub fn const_value(&mut self, name: &str) -> Result<mir::Value<'t>, AstError<'t>> {
+        let (ty, _) = self.consts[name];
+        let value = try!(self.get_const(name, name, 0));
+        Ok(to_mir(value, ty))
+    }
+
+    // the value of the initializer of the static `name`
+    pub fn static_value(&mut self,
+                        name: &str,
+                        ty: Type<'t>,
+                        init: &Block<'t>)
+                        -> Result<mir::Value<'t>, AstError<'t>> {
+        let value = try!(self.block(name, init));
+        Ok(to_mir(value, ty))
+    }
+
+    // the first `/` or `%` in `block` whose divisor is a constant zero.
+    // `locals` are the names bound in `block`'s scope; a divisor mentioning
+    // one isn't constant, even if a const of the same name exists
+    pub fn check_divisors(&mut self,
+                          function: &str,
+                          locals: &mut Vec<String>,
+                          block: &Block<'t>)
+                          -> Result<(), AstError<'t>> {
+        let scope = locals.len();
+        for stmt in &block.stmts {
+            match *stmt {
+                Stmt::Let { ref pattern, ref value, .. } => {
+                    if let Some(ref value) = *value {
+                        try!(self.check_divisors_expr(function, locals, value));
+                    }
+                    bindings(pattern, locals);
+                }
+                Stmt::Expr(ref e) => try!(self.check_divisors_expr(function, locals, e)),
+            }
+        }
+        if let Some(ref e) = block.expr {
+            try!(self.check_divisors_expr(function, locals, e));
+        }
+        locals.truncate(scope);
+        Ok(())
+    }
+
+    fn check_divisors_expr(&mut self,
+                           function: &str,
+                           locals: &mut Vec<String>,
+                           expr: &Expr<'t>)
+                           -> Result<(), AstError<'t>> {
+        match expr.kind {
+            ExprKind::Binop { op, ref lhs, ref rhs } |
+            ExprKind::CompoundAssign { op, dst: ref lhs, src: ref rhs } => {
+                try!(self.check_divisors_expr(function, locals, lhs));
+                try!(self.check_divisors_expr(function, locals, rhs));
+                let divides = op == Operand::Div || op == Operand::Rem;
+                // anything that isn't constant, or overflows, is left to the
+                // runtime checks
+                if divides && !mentions(rhs, locals) {
+                    if let Ok(Const::Int(0)) = self.expr(function, rhs) {
+                        return Err(AstError::DivisionByZero {
+                            line: expr.line,
+                            function: function.to_owned(),
+                            compiler: fl!(),
+                        });
+                    }
+                }
+                Ok(())
+            }
+            ExprKind::Call { ref args, .. } => {
+                for arg in args {
+                    try!(self.check_divisors_expr(function, locals, arg));
+                }
+                Ok(())
+            }
+            ExprKind::Tuple(ref elements) => {
+                for elt in elements {
+                    try!(self.check_divisors_expr(function, locals, elt));
+                }
+                Ok(())
+            }
+            ExprKind::If { ref condition, ref then_value, ref else_value } => {
+                try!(self.check_divisors_expr(function, locals, condition));
+                try!(self.check_divisors(function, locals, then_value));
+                self.check_divisors(function, locals, else_value)
+            }
+            ExprKind::Block(ref block) |
+            ExprKind::Unsafe(ref block) => self.check_divisors(function, locals, block),
+            ExprKind::Assign { ref dst, ref src } => {
+                try!(self.check_divisors_expr(function, locals, dst));
+                self.check_divisors_expr(function, locals, src)
+            }
+            ExprKind::Pos(ref inner) |
+            ExprKind::Neg(ref inner) |
+            ExprKind::Not(ref inner) |
+            ExprKind::Ref(ref inner, _) |
+            ExprKind::Deref(ref inner) |
+            ExprKind::Cast(ref inner) |
+            ExprKind::Field(ref inner, _) |
+            ExprKind::Return(ref inner) |
+            ExprKind::Assert { condition: ref inner, .. } => {
+                self.check_divisors_expr(function, locals, inner)
+            }
+            ExprKind::Variable(_) |
+            ExprKind::IntLiteral(_) |
+            ExprKind::FloatLiteral(_) |
+            ExprKind::CharLiteral(_) |
+            ExprKind::BoolLiteral(_) |
+            ExprKind::UnitLiteral |
+            ExprKind::Panic { .. } => Ok(()),
+        }
+    }
+
+    // `item` is the const or static being evaluated, for errors
+    fn get_const(&mut self, item: &str, name: &str, line: u32) -> Result<Const, AstError<'t>> {
+        if let Some(&value) = self.values.get(name) {
+            return Ok(value);
+        }
+        let init = match self.consts.get(name) {
+            Some(&(_, ref init)) => init,
+            // statics can't be read at compile time
+            None => {
+                return Err(AstError::NotConstant {
+                    item: item.to_owned(),
+                    line: line,
+                    compiler: fl!(),
+                })
+            }
+        };
+        if self.evaluating.iter().any(|n| n == name) {
+            return Err(AstError::RecursiveConst {
+                name: name.to_owned(),
+                compiler: fl!(),
+            });
+        }
+        self.evaluating.push(name.to_owned());
+        // popped even on an error, so a failed divisor doesn't look like a
+        // cycle to `const_value` later
+        let value = self.block(name, init);
+        self.evaluating.pop();
+        let value = try!(value);
+        self.values.insert(name.to_owned(), value);
+        Ok(value)
+    }
+
+    fn block(&mut self, item: &str, block: &Block<'t>) -> Result<Const, AstError<'t>> {
+        match (block.stmts.is_empty(), &block.expr) {
+            (true, &Some(ref expr)) => self.expr(item, expr),
+            (true, &None) => Ok(Const::Unit),
+            (false, _) => {
+                Err(AstError::NotConstant {
+                    item: item.to_owned(),
+                    line: block.expr.as_ref().map(|e| e.line).unwrap_or(0),
+                    compiler: fl!(),
+                })
+            }
+        }
+    }
+
+    fn expr(&mut self, item: &str, expr: &Expr<'t>) -> Result<Const, AstError<'t>> {
+        let overflow = || {
+            AstError::ConstEvalOverflow {
+                item: item.to_owned(),
+                line: expr.line,
+                compiler: fl!(),
+            }
+        };
+        let value = match expr.kind {
+            ExprKind::IntLiteral(value) => Const::Int(value),
+            ExprKind::CharLiteral(c) => Const::Int(c as u64),
+            ExprKind::FloatLiteral(value) => Const::Float(value),
+            ExprKind::BoolLiteral(value) => Const::Bool(value),
+            ExprKind::UnitLiteral => Const::Unit,
+            ExprKind::Variable(ref name) => try!(self.get_const(item, name, expr.line)),
+            ExprKind::Block(ref block) |
+            ExprKind::Unsafe(ref block) => try!(self.block(item, block)),
+            ExprKind::If { ref condition, ref then_value, ref else_value } => {
+                match try!(self.expr(item, condition)) {
+                    Const::Bool(true) => try!(self.block(item, then_value)),
+                    Const::Bool(false) => try!(self.block(item, else_value)),
+                    cond => panic!("ICE: non-bool condition: {:?}", cond),
+                }
+            }
+            ExprKind::Pos(ref inner) => try!(self.expr(item, inner)),
+            ExprKind::Not(ref inner) => {
+                match try!(self.expr(item, inner)) {
+                    Const::Bool(value) => Const::Bool(!value),
+                    Const::Int(value) => {
+                        let (signed, bits) = int_info(expr.ty).expect("ICE: `!` of a non-int");
+                        Const::Int(truncate(!value, signed, bits))
+                    }
+                    value => panic!("ICE: `!` of {:?}", value),
+                }
+            }
+            ExprKind::Neg(ref inner) => {
+                let signed = int_info(expr.ty).map(|(signed, _)| signed);
+                match (&inner.kind, signed) {
+                    // the most negative value is only a literal when negated,
+                    // so only the negation is range checked
+                    (&ExprKind::IntLiteral(value), Some(true)) => Const::Int(value.wrapping_neg()),
+                    _ => {
+                        match (try!(self.expr(item, inner)), signed) {
+                            (Const::Int(value), Some(true)) => {
+                                match (value as i64).checked_neg() {
+                                    Some(value) => Const::Int(value as u64),
+                                    None => return Err(overflow()),
+                                }
+                            }
+                            (Const::Int(0), _) => Const::Int(0),
+                            (Const::Int(_), _) => return Err(overflow()),
+                            (Const::Float(value), _) => Const::Float(-value),
+                            (value, _) => panic!("ICE: `-` of {:?}", value),
+                        }
+                    }
+                }
+            }
+            ExprKind::Binop { op: Operand::AndAnd, ref lhs, ref rhs } => {
+                match try!(self.expr(item, lhs)) {
+                    Const::Bool(false) => Const::Bool(false),
+                    _ => try!(self.expr(item, rhs)),
+                }
+            }
+            ExprKind::Binop { op: Operand::OrOr, ref lhs, ref rhs } => {
+                match try!(self.expr(item, lhs)) {
+                    Const::Bool(true) => Const::Bool(true),
+                    _ => try!(self.expr(item, rhs)),
+                }
+            }
+            ExprKind::Binop { op, ref lhs, ref rhs } => {
+                let lhs_value = try!(self.expr(item, lhs));
+                let rhs_value = try!(self.expr(item, rhs));
+                match binop(op, lhs_value, lhs.ty, rhs_value, rhs.ty) {
+                    Some(value) => value,
+                    None => return Err(overflow()),
+                }
+            }
+            ExprKind::Call { .. } |
+            ExprKind::Ref(..) |
+            ExprKind::Deref(_) |
+            ExprKind::Cast(_) |
+            ExprKind::Tuple(_) |
+            ExprKind::Field(..) |
+            ExprKind::Return(_) |
+            ExprKind::Panic { .. } |
+            ExprKind::Assert { .. } |
+            ExprKind::Assign { .. } |
+            ExprKind::CompoundAssign { .. } => {
+                return Err(AstError::NotConstant {
+                    item: item.to_owned(),
+                    line: expr.line,
+                    compiler: fl!(),
+                })
+            }
+        };
+        match (value, int_info(expr.ty)) {
+            (Const::Int(value), Some((signed, bits))) if !fits(value, signed, bits) => {
+                Err(overflow())
+            }
+            (Const::Float(value), _) if *expr.ty.0 == TypeVariant::Float(ty::Float::F32) => {
+                Ok(Const::Float(value as f32 as f64))
+            }
+            (value, _) => Ok(value),
+        }
+    }
+}
+
+fn bindings(pattern: &Pattern, names: &mut Vec<String>) {
+    match *pattern {
+        Pattern::Binding { ref name, .. } => names.push(name.clone()),
+        Pattern::Tuple(ref patterns) => {
+            for pattern in patterns {
+                bindings(pattern, names);
+            }
+        }
+    }
+}
+
+// whether `expr` reads one of `locals`. Only the expressions which could be
+// constant are looked into
+fn mentions(expr: &Expr, locals: &[String]) -> bool {
+    let mentions_block = |block: &Block| {
+        block.stmts.is_empty() && block.expr.as_ref().map_or(false, |e| mentions(e, locals))
+    };
+    match expr.kind {
+        ExprKind::Variable(ref name) => locals.contains(name),
+        ExprKind::Block(ref block) |
+        ExprKind::Unsafe(ref block) => mentions_block(block),
+        ExprKind::If { ref condition, ref then_value, ref else_value } => {
+            mentions(condition, locals) || mentions_block(then_value) ||
+            mentions_block(else_value)
+        }
+        ExprKind::Pos(ref inner) |
+        ExprKind::Neg(ref inner) |
+        ExprKind::Not(ref inner) => mentions(inner, locals),
+        ExprKind::Binop { ref lhs, ref rhs, .. } => {
+            mentions(lhs, locals) || mentions(rhs, locals)
+        }
+        _ => false,
+    }
+}
+
+// `None` if the operation overflows, or divides by zero
+fn binop(op: Operand, lhs: Const, lhs_ty: Type, rhs: Const, rhs_ty: Type) -> Option<Const> {
+    if let Some(ordering) = compare(lhs, lhs_ty, rhs) {
+        let result = match op {
+            Operand::EqualsEquals => ordering == Some(Ordering::Equal),
+            Operand::NotEquals => ordering != Some(Ordering::Equal),
+            Operand::LessThan => ordering == Some(Ordering::Less),
+            Operand::LessThanEquals => {
+                ordering == Some(Ordering::Less) || ordering == Some(Ordering::Equal)
+            }
+            Operand::GreaterThan => ordering == Some(Ordering::Greater),
+            Operand::GreaterThanEquals => {
+                ordering == Some(Ordering::Greater) || ordering == Some(Ordering::Equal)
+            }
+            _ => return binop_arith(op, lhs, lhs_ty, rhs, rhs_ty),
+        };
+        Some(Const::Bool(result))
+    } else {
+        binop_arith(op, lhs, lhs_ty, rhs, rhs_ty)
+    }
+}
+
+// `None` if the values can't be compared at all; `Some(None)` if they're
+// unordered, like NaN
+fn compare(lhs: Const, lhs_ty: Type, rhs: Const) -> Option<Option<Ordering>> {
+    match (lhs, rhs) {
+        (Const::Int(lhs), Const::Int(rhs)) => {
+            match int_info(lhs_ty) {
+                Some((true, _)) => Some((lhs as i64).partial_cmp(&(rhs as i64))),
+                _ => Some(lhs.partial_cmp(&rhs)),
+            }
+        }
+        (Const::Float(lhs), Const::Float(rhs)) => Some(lhs.partial_cmp(&rhs)),
+        (Const::Bool(lhs), Const::Bool(rhs)) => Some(lhs.partial_cmp(&rhs)),
+        (Const::Unit, Const::Unit) => Some(Some(Ordering::Equal)),
+        _ => None,
+    }
+}
+
+fn binop_arith(op: Operand, lhs: Const, lhs_ty: Type, rhs: Const, rhs_ty: Type) -> Option<Const> {
+    match (lhs, rhs) {
+        (Const::Int(lhs), Const::Int(rhs)) => {
+            let (signed, bits) = int_info(lhs_ty).expect("ICE: arithmetic on a non-int");
+            let value = match op {
+                Operand::Shl | Operand::Shr => {
+                    let amount = match int_info(rhs_ty) {
+                        Some((true, _)) if (rhs as i64) < 0 => return None,
+                        _ if rhs >= bits as u64 || rhs >= 64 => return None,
+                        _ => rhs as u32,
+                    };
+                    match (op, signed) {
+                        (Operand::Shl, _) => truncate(lhs << amount, signed, bits),
+                        (_, true) => ((lhs as i64) >> amount) as u64,
+                        (_, false) => lhs >> amount,
+                    }
+                }
+                Operand::And => lhs & rhs,
+                Operand::Xor => lhs ^ rhs,
+                Operand::Or => lhs | rhs,
+                _ if signed => {
+                    let (lhs, rhs) = (lhs as i64, rhs as i64);
+                    let value = match op {
+                        Operand::Plus => lhs.checked_add(rhs),
+                        Operand::Minus => lhs.checked_sub(rhs),
+                        Operand::Mul => lhs.checked_mul(rhs),
+                        Operand::Div => lhs.checked_div(rhs),
+                        Operand::Rem => lhs.checked_rem(rhs),
+                        op => panic!("ICE: {:?} on ints", op),
+                    };
+                    match value {
+                        Some(value) => value as u64,
+                        None => return None,
+                    }
+                }
+                _ => {
+                    let value = match op {
+                        Operand::Plus => lhs.checked_add(rhs),
+                        Operand::Minus => lhs.checked_sub(rhs),
+                        Operand::Mul => lhs.checked_mul(rhs),
+                        Operand::Div => lhs.checked_div(rhs),
+                        Operand::Rem => lhs.checked_rem(rhs),
+                        op => panic!("ICE: {:?} on ints", op),
+                    };
+                    match value {
+                        Some(value) => value,
+                        None => return None,
+                    }
+                }
+            };
+            Some(Const::Int(value))
+        }
+        (Const::Float(lhs), Const::Float(rhs)) => {
+            Some(Const::Float(match op {
+                Operand::Plus => lhs + rhs,
+                Operand::Minus => lhs - rhs,
+                Operand::Mul => lhs * rhs,
+                Operand::Div => lhs / rhs,
+                Operand::Rem => lhs % rhs,
+                op => panic!("ICE: {:?} on floats", op),
+            }))
+        }
+        (Const::Bool(lhs), Const::Bool(rhs)) => {
+            Some(Const::Bool(match op {
+                Operand::And => lhs & rhs,
+                Operand::Xor => lhs ^ rhs,
+                Operand::Or => lhs | rhs,
+                op => panic!("ICE: {:?} on bools", op),
+            }))
+        }
+        (lhs, rhs) => panic!("ICE: {:?} {:?} {:?}", lhs, op, rhs),
+    }
+}
+
+// (signed, bits) of an int type. Pointer-sized ints are treated as 64 bits
+// wide; the pointer size isn't known here
+fn int_info(ty: Type) -> Option<(bool, u32)> {
+    match *ty.0 {
+        TypeVariant::SInt(int) => Some((true, int.bits().unwrap_or(64))),
+        TypeVariant::UInt(int) => Some((false, int.bits().unwrap_or(64))),
+        TypeVariant::Char => Some((false, 32)),
+        _ => None,
+    }
+}
+
+// signed values are stored sign extended; anything wider than 64 bits fits,
+// since the arithmetic is done in 64 bits
+fn fits(value: u64, signed: bool, bits: u32) -> bool {
+    bits >= 64 || truncate(value, signed, bits) == value
+}
+
+fn truncate(value: u64, signed: bool, bits: u32) -> u64 {
+    if bits >= 64 {
+        value
+    } else if signed {
+        (((value << (64 - bits)) as i64) >> (64 - bits)) as u64
+    } else {
+        value & ((1 << bits) - 1)
+    }
+}
+
+fn to_mir<'t>(value: Const, ty: Type<'t>) -> mir::Value<'t> {
+    match value {
+        Const::Int(value) => mir::Value::const_int(value, ty),
+        Const::Float(value) => mir::Value::const_float(value, ty),
+        Const::Bool(value) => mir::Value::const_bool(value),
+        Const::Unit => mir::Value::const_unit(),
+    }
+}
@@ -1,24 +1,43 @@
 use ast::{AstError, Block, Function};
+use ast::builtin::{Builtin, Panicking};
 use std::collections::HashMap;
-use ty::{self, TypeContext, Type, TypeVariant};
+use ty::{self, TypeContext, Type, TypeVariant, Mutability};
 use parse::Operand;
 use mir;
 
 #[derive(Debug)]
 pub enum Stmt<'t> {
     Let {
-        name: String,
+        pattern: Pattern,
         ty: Type<'t>,
         value: Option<Box<Expr<'t>>>,
     },
     Expr(Expr<'t>),
 }
 
+// the left hand side of a `let`
+#[derive(Debug)]
+pub enum Pattern {
+    // `name` or `mut name`
+    Binding {
+        name: String,
+        mutability: Mutability,
+    },
+    // `(a, b, ...)`; matches a tuple of exactly as many elements
+    Tuple(Vec<Pattern>),
+}
+
 #[derive(Debug)]
 pub enum ExprKind<'t> {
     Call {
         callee: String,
+        // `callee::<T, ...>(args)`; only builtins take any
+        ty_args: Vec<Type<'t>>,
         args: Vec<Expr<'t>>,
+        // only the panicking builtins take one
+        message: Option<Message>,
+        // whether it was written `args[0].callee(args[1..])`
+        method: bool,
     },
     If {
         condition: Box<Expr<'t>>,
@@ -26,6 +45,8 @@ pub enum ExprKind<'t> {
         else_value: Box<Block<'t>>,
     },
     Block(Box<Block<'t>>),
+    // `unsafe { ... }`; raw pointers may only be dereferenced inside one
+    Unsafe(Box<Block<'t>>),
     Binop {
         op: Operand,
         lhs: Box<Expr<'t>>,
@@ -34,34 +55,99 @@ pub enum ExprKind<'t> {
     Pos(Box<Expr<'t>>), // unary plus
     Neg(Box<Expr<'t>>), // unary minus
     Not(Box<Expr<'t>>), // !expr
-    Ref(Box<Expr<'t>>), // &expr
+    Ref(Box<Expr<'t>>, Mutability), // &expr or &mut expr
     Deref(Box<Expr<'t>>),
+    Cast(Box<Expr<'t>>), // expr as ty; the target is the type of the cast
+    Tuple(Vec<Expr<'t>>), // (a, b, ...)
+    Field(Box<Expr<'t>>, u32), // expr.0
     Variable(String),
     IntLiteral(u64),
+    FloatLiteral(f64),
+    CharLiteral(char),
     BoolLiteral(bool),
     UnitLiteral,
     Return(Box<Expr<'t>>),
+    // `panic(..)` or `unreachable(..)`, with the user's message, if any
+    Panic {
+        builtin: Panicking,
+        message: Option<String>,
+    },
+    // `assert(condition, ..)`, or `assert_eq(lhs, rhs, ..)`, whose condition
+    // is `lhs == rhs`
+    Assert {
+        builtin: Panicking,
+        condition: Box<Expr<'t>>,
+        message: Option<String>,
+    },
     Assign {
         dst: Box<Expr<'t>>,
         src: Box<Expr<'t>>,
     },
+    // dst op= src
+    CompoundAssign {
+        op: Operand,
+        dst: Box<Expr<'t>>,
+        src: Box<Expr<'t>>,
+    },
 }
 
 #[derive(Debug)]
 pub struct Expr<'t> {
     pub kind: ExprKind<'t>,
     pub ty: Type<'t>,
+    pub line: u32,
+    // for a literal, where its token starts in the source; the formatter
+    // looks up how it was written by that
+    pub offset: usize,
+}
+
+// the string literal after a call's arguments, and where its token starts
+#[derive(Debug)]
+pub struct Message {
+    pub text: String,
+    pub offset: usize,
 }
 
 // constructors
 impl<'t> Expr<'t> {
-    pub fn call(callee: String, args: Vec<Expr<'t>>, ctxt: &'t TypeContext<'t>) -> Self {
+    pub fn call(callee: String,
+                ty_args: Vec<Type<'t>>,
+                args: Vec<Expr<'t>>,
+                message: Option<Message>,
+                ctxt: &'t TypeContext<'t>)
+                -> Self {
         Expr {
             kind: ExprKind::Call {
                 callee: callee,
+                ty_args: ty_args,
                 args: args,
+                message: message,
+                method: false,
             },
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    // `args[0].callee::<ty_args>(args[1..])`
+    pub fn method_call(callee: String,
+                       ty_args: Vec<Type<'t>>,
+                       args: Vec<Expr<'t>>,
+                       message: Option<Message>,
+                       ctxt: &'t TypeContext<'t>)
+                       -> Self {
+        Expr {
+            kind: ExprKind::Call {
+                callee: callee,
+                ty_args: ty_args,
+                args: args,
+                message: message,
+                method: true,
+            },
+            ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -69,6 +155,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Variable(name),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -84,6 +172,8 @@ impl<'t> Expr<'t> {
                 else_value: Box::new(else_),
             },
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -91,6 +181,17 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Block(Box::new(inner)),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn unsafe_block(inner: Block<'t>, ctxt: &'t TypeContext<'t>) -> Self {
+        Expr {
+            kind: ExprKind::Unsafe(Box::new(inner)),
+            ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -98,6 +199,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::IntLiteral(value),
             ty: Type::infer_int(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -105,6 +208,35 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::IntLiteral(value),
             ty: ty,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn float_lit(value: f64, ctxt: &'t TypeContext<'t>) -> Self {
+        Expr {
+            kind: ExprKind::FloatLiteral(value),
+            ty: Type::infer_float(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn float_lit_with_ty(value: f64, ty: Type<'t>) -> Self {
+        Expr {
+            kind: ExprKind::FloatLiteral(value),
+            ty: ty,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn char_lit(value: char, ctxt: &'t TypeContext<'t>) -> Self {
+        Expr {
+            kind: ExprKind::CharLiteral(value),
+            ty: Type::char(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -112,6 +244,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::BoolLiteral(value),
             ty: Type::bool(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -119,6 +253,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::UnitLiteral,
             ty: Type::unit(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -126,6 +262,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Neg(Box::new(inner)),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -133,6 +271,8 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Pos(Box::new(inner)),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -140,13 +280,17 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Not(Box::new(inner)),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
-    pub fn ref_(inner: Expr<'t>, ctxt: &'t TypeContext<'t>) -> Self {
+    pub fn ref_(inner: Expr<'t>, mutability: Mutability, ctxt: &'t TypeContext<'t>) -> Self {
         Expr {
-            kind: ExprKind::Ref(Box::new(inner)),
-            ty: Type::ref_(Type::infer(ctxt), ctxt),
+            kind: ExprKind::Ref(Box::new(inner), mutability),
+            ty: Type::reference(Type::infer(ctxt), mutability, ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -154,6 +298,35 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Deref(Box::new(inner)),
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn cast(inner: Expr<'t>, ty: Type<'t>) -> Self {
+        Expr {
+            kind: ExprKind::Cast(Box::new(inner)),
+            ty: ty,
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn tuple(elements: Vec<Expr<'t>>, ctxt: &'t TypeContext<'t>) -> Self {
+        Expr {
+            kind: ExprKind::Tuple(elements),
+            ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn field(tuple: Expr<'t>, index: u32, ctxt: &'t TypeContext<'t>) -> Self {
+        Expr {
+            kind: ExprKind::Field(Box::new(tuple), index),
+            ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -161,6 +334,40 @@ impl<'t> Expr<'t> {
         Expr {
             kind: ExprKind::Return(Box::new(ret)),
             ty: Type::diverging(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn panic(builtin: Panicking,
+                 message: Option<String>,
+                 ctxt: &'t TypeContext<'t>)
+                 -> Self {
+        Expr {
+            kind: ExprKind::Panic {
+                builtin: builtin,
+                message: message,
+            },
+            ty: Type::diverging(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn assert(builtin: Panicking,
+                  condition: Expr<'t>,
+                  message: Option<String>,
+                  ctxt: &'t TypeContext<'t>)
+                  -> Self {
+        Expr {
+            kind: ExprKind::Assert {
+                builtin: builtin,
+                condition: Box::new(condition),
+                message: message,
+            },
+            ty: Type::unit(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 
@@ -171,28 +378,65 @@ impl<'t> Expr<'t> {
                 src: Box::new(src),
             },
             ty: Type::unit(ctxt),
+            line: 0,
+            offset: 0,
+        }
+    }
+
+    pub fn compound_assign(op: Operand,
+                           dst: Expr<'t>,
+                           src: Expr<'t>,
+                           ctxt: &'t TypeContext<'t>)
+                           -> Self {
+        Expr {
+            kind: ExprKind::CompoundAssign {
+                op: op,
+                dst: Box::new(dst),
+                src: Box::new(src),
+            },
+            ty: Type::unit(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 }
 
 // parsing
 impl<'t> Expr<'t> {
+    pub fn at(mut self, line: u32) -> Self {
+        self.line = line;
+        self
+    }
+
+    pub fn spelled_at(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
     pub fn is_block(&self) -> bool {
         match self.kind {
-            ExprKind::If { .. } | ExprKind::Block(_) => true,
+            ExprKind::If { .. } | ExprKind::Block(_) | ExprKind::Unsafe(_) => true,
             ExprKind::Call { .. } |
             ExprKind::Binop { .. } |
             ExprKind::Pos(_) |
             ExprKind::Neg(_) |
             ExprKind::Not(_) |
             ExprKind::Variable(_) |
-            ExprKind::Ref(_) |
+            ExprKind::Ref(..) |
             ExprKind::Deref(_) |
+            ExprKind::Cast(_) |
+            ExprKind::Tuple(_) |
+            ExprKind::Field(..) |
             ExprKind::IntLiteral(_) |
+            ExprKind::FloatLiteral(_) |
+            ExprKind::CharLiteral(_) |
             ExprKind::BoolLiteral(_) |
             ExprKind::UnitLiteral |
             ExprKind::Return(_) |
-            ExprKind::Assign { .. } => false,
+            ExprKind::Panic { .. } |
+            ExprKind::Assert { .. } |
+            ExprKind::Assign { .. } |
+            ExprKind::CompoundAssign { .. } => false,
         }
     }
 }
@@ -203,19 +447,28 @@ impl<'t> Expr<'t> {
                         ctxt: &'t TypeContext<'t>,
                         to_unify: Type<'t>,
                         uf: &mut ty::UnionFind<'t>,
-                        variables: &mut HashMap<String, Type<'t>>,
+                        variables: &mut HashMap<String, (Type<'t>, Mutability, bool)>,
                         function: &Function<'t>,
                         functions: &HashMap<String, ty::Function<'t>>)
                         -> Result<(), AstError<'t>> {
+        // bindings introduced by this block end at its `}`; a `let` of an
+        // existing name shadows it until then
+        let outer_scope = variables.clone();
         let mut live_blk = true;
         for stmt in block.stmts.iter_mut() {
             match *stmt {
-                Stmt::Let { ref name, ref mut ty, ref mut value } => {
+                Stmt::Let { ref pattern, ref mut ty, ref mut value } => {
                     ty.generate_inference_id(uf, ctxt);
                     if let Some(ref mut v) = *value {
                         try!(v.unify_type(ctxt, *ty, uf, variables, function, functions));
                     }
-                    variables.insert(name.to_owned(), *ty);
+                    try!(Self::bind_pattern(pattern,
+                                            *ty,
+                                            value.is_none(),
+                                            ctxt,
+                                            uf,
+                                            variables,
+                                            function));
                 }
                 Stmt::Expr(ref mut e @ Expr { kind: ExprKind::Return(_), .. }) => {
                     try!(e.unify_type(ctxt,
@@ -225,16 +478,27 @@ impl<'t> Expr<'t> {
                                       function,
                                       functions));
                     live_blk = false;
-                    break;
                 }
                 Stmt::Expr(ref mut e) => {
                     let mut ty = Type::infer(ctxt);
                     ty.generate_inference_id(uf, ctxt);
                     try!(e.unify_type(ctxt, ty, uf, variables, function, functions));
+                    // a `panic(..)`, or a call of a function returning `!`
+                    if *e.ty.0 == TypeVariant::Diverging {
+                        live_blk = false;
+                    }
                 }
             }
         }
-        if live_blk {
+        if !live_blk {
+            // anything after a `return` (or anything else which diverges) is
+            // still typechecked, but its value goes nowhere
+            if let Some(ref mut expr) = block.expr {
+                let mut ty = Type::infer(ctxt);
+                ty.generate_inference_id(uf, ctxt);
+                try!(expr.unify_type(ctxt, ty, uf, variables, function, functions));
+            }
+        } else {
             match block.expr {
                 Some(ref mut expr) => {
                     try!(expr.unify_type(ctxt, to_unify, uf, variables, function, functions))
@@ -252,20 +516,117 @@ impl<'t> Expr<'t> {
                 }
             };
         }
+        *variables = outer_scope;
         Ok(())
     }
 
+    // a `&mut T` can be used where a `&T` is expected: the expression is
+    // typed on its own, and is cast if it turns out to be a `&mut T`
     pub fn unify_type(&mut self,
                       ctxt: &'t TypeContext<'t>,
                       to_unify: Type<'t>,
                       uf: &mut ty::UnionFind<'t>,
-                      variables: &mut HashMap<String, Type<'t>>,
+                      variables: &mut HashMap<String, (Type<'t>, Mutability, bool)>,
                       function: &Function<'t>,
                       functions: &HashMap<String, ty::Function<'t>>)
                       -> Result<(), AstError<'t>> {
+        let pointee = match uf.resolve(to_unify).map(|ty| *ty.0) {
+            Some(TypeVariant::Reference(pointee, Mutability::Immutable)) => pointee,
+            _ => return self.unify_exact(ctxt, to_unify, uf, variables, function, functions),
+        };
+        let mut actual = Type::infer(ctxt);
+        actual.generate_inference_id(uf, ctxt);
+        try!(self.unify_exact(ctxt, actual, uf, variables, function, functions));
+        let could_not_unify = |()| {
+            AstError::CouldNotUnify {
+                first: actual,
+                second: to_unify,
+                function: function.name.clone(),
+                compiler: fl!(),
+            }
+        };
+        match uf.resolve(actual).map(|ty| *ty.0) {
+            Some(TypeVariant::Reference(inner, Mutability::Mutable)) => {
+                try!(uf.unify(inner, pointee).map_err(could_not_unify));
+                let line = self.line;
+                let inner = ::std::mem::replace(self, Expr::unit_lit(ctxt));
+                *self = Expr::cast(inner, to_unify).at(line);
+                Ok(())
+            }
+            _ => uf.unify(actual, to_unify).map_err(could_not_unify),
+        }
+    }
+
+    // `panic`, `unreachable`, `assert` and `assert_eq` are only builtins if
+    // there's no function of that name; a call of one becomes a `Panic` or
+    // an `Assert`
+    fn resolve_panicking(&mut self,
+                         ctxt: &'t TypeContext<'t>,
+                         function: &Function<'t>,
+                         functions: &HashMap<String, ty::Function<'t>>)
+                         -> Result<(), AstError<'t>> {
+        let builtin = match self.kind {
+            ExprKind::Call { ref callee, .. } if !functions.contains_key(callee) => {
+                match Panicking::from_name(callee) {
+                    Some(builtin) => builtin,
+                    None => return Ok(()),
+                }
+            }
+            _ => return Ok(()),
+        };
+        let line = self.line;
+        let (ty_args, mut args, message) =
+            match ::std::mem::replace(&mut self.kind, ExprKind::UnitLiteral) {
+                ExprKind::Call { ty_args, args, message, .. } => {
+                    (ty_args, args, message.map(|message| message.text))
+                }
+                _ => unreachable!(),
+            };
+        if !ty_args.is_empty() {
+            return Err(AstError::IncorrectNumberOfTypeArguments {
+                passed: ty_args.len(),
+                expected: 0,
+                callee: builtin.name().to_owned(),
+                caller: function.name.clone(),
+                compiler: fl!(),
+            });
+        }
+        if args.len() != builtin.arity() {
+            return Err(AstError::IncorrectNumberOfArguments {
+                passed: args.len(),
+                expected: builtin.arity(),
+                callee: builtin.name().to_owned(),
+                caller: function.name.clone(),
+                compiler: fl!(),
+            });
+        }
+        *self = match builtin {
+            Panicking::Panic | Panicking::Unreachable => Expr::panic(builtin, message, ctxt),
+            Panicking::Assert => Expr::assert(builtin, args.pop().unwrap(), message, ctxt),
+            Panicking::AssertEq => {
+                let rhs = args.pop().unwrap();
+                let lhs = args.pop().unwrap();
+                let condition = Operand::EqualsEquals.expr(lhs, rhs, ctxt).at(line);
+                Expr::assert(builtin, condition, message, ctxt)
+            }
+        }.at(line);
+        Ok(())
+    }
+
+    fn unify_exact(&mut self,
+                   ctxt: &'t TypeContext<'t>,
+                   to_unify: Type<'t>,
+                   uf: &mut ty::UnionFind<'t>,
+                   variables: &mut HashMap<String, (Type<'t>, Mutability, bool)>,
+                   function: &Function<'t>,
+                   functions: &HashMap<String, ty::Function<'t>>)
+                   -> Result<(), AstError<'t>> {
+        try!(self.resolve_panicking(ctxt, function, functions));
         self.ty.generate_inference_id(uf, ctxt);
         match self.kind {
             ExprKind::IntLiteral(_) |
+            ExprKind::FloatLiteral(_) |
+            ExprKind::CharLiteral(_) |
             ExprKind::BoolLiteral(_) |
             ExprKind::UnitLiteral => {
                 uf.unify(self.ty, to_unify).map_err(|()| {
@@ -278,17 +639,17 @@ impl<'t> Expr<'t> {
                 })
             }
             ExprKind::Variable(ref name) => {
-                if let Some(ty) = variables.get(name) {
-                    self.ty = *ty;
-                    uf.unify(*ty, to_unify).map_err(|()| {
+                if let Some(&(ty, _, _)) = variables.get(name) {
+                    self.ty = ty;
+                    uf.unify(ty, to_unify).map_err(|()| {
                         AstError::CouldNotUnify {
-                            first: *ty,
+                            first: ty,
                             second: to_unify,
                             function: function.name.clone(),
                             compiler: fl!(),
                         }
                     })
-                } else if let Some(&(_, ty)) = function.args.get(name) {
+                } else if let Some(&(_, ty, _)) = function.args.get(name) {
                     self.ty = ty;
                     uf.unify(ty, to_unify).map_err(|()| {
                         AstError::CouldNotUnify {
@@ -321,11 +682,25 @@ impl<'t> Expr<'t> {
                     }
                 })
             }
-            ExprKind::Ref(ref mut inner) => {
+            ExprKind::Ref(ref mut inner, mutability) => {
                 let mut inner_ty = Type::infer(ctxt);
                 inner_ty.generate_inference_id(uf, ctxt);
                 try!(inner.unify_type(ctxt, inner_ty, uf, variables, function, functions));
-                let ref_ty = Type::ref_(inner_ty, ctxt);
+                if mutability == Mutability::Mutable {
+                    if let ExprKind::Variable(ref name) = inner.kind {
+                        if Self::binding_mutability(name, variables, function) !=
+                           Some(Mutability::Mutable) {
+                            return Err(AstError::MutBorrowOfImmutable {
+                                name: name.clone(),
+                                line: self.line,
+                                function: function.name.clone(),
+                                compiler: fl!(),
+                            });
+                        }
+                    }
+                    try!(Self::check_mutable(inner, self.line, uf, variables, function));
+                }
+                let ref_ty = Type::reference(inner_ty, mutability, ctxt);
                 try!(uf.unify(to_unify, ref_ty).map_err(|()| {
                     AstError::CouldNotUnify {
                         first: to_unify,
@@ -373,8 +748,99 @@ impl<'t> Expr<'t> {
                     }
                 }));
 
-                let inner_ty = Type::ref_(outer_ty, ctxt);
-                inner.unify_type(ctxt, inner_ty, uf, variables, function, functions)
+                let mut ptr_ty = Type::infer(ctxt);
+                ptr_ty.generate_inference_id(uf, ctxt);
+                try!(inner.unify_type(ctxt, ptr_ty, uf, variables, function, functions));
+                let default = Mutability::Immutable;
+                Self::unify_pointee(ptr_ty, outer_ty, default, uf, ctxt).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: ptr_ty,
+                        second: Type::ref_(outer_ty, ctxt),
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
+            }
+            ExprKind::Cast(ref mut inner) => {
+                // whether the cast is allowed is checked in `finalize_type`,
+                // once the type of `inner` is known
+                let mut inner_ty = Type::infer(ctxt);
+                inner_ty.generate_inference_id(uf, ctxt);
+                try!(inner.unify_type(ctxt, inner_ty, uf, variables, function, functions));
+                let ty = self.ty;
+                uf.unify(self.ty, to_unify).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: ty,
+                        second: to_unify,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
+            }
+            ExprKind::Tuple(ref mut elements) => {
+                let mut types = Vec::with_capacity(elements.len());
+                for elt in elements {
+                    let mut ty = Type::infer(ctxt);
+                    ty.generate_inference_id(uf, ctxt);
+                    try!(elt.unify_type(ctxt, ty, uf, variables, function, functions));
+                    types.push(ty);
+                }
+                let tuple_ty = Type::tuple(types, ctxt);
+                let self_ty = self.ty;
+                try!(uf.unify(self.ty, tuple_ty).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: self_ty,
+                        second: tuple_ty,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                }));
+                uf.unify(tuple_ty, to_unify).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: tuple_ty,
+                        second: to_unify,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
+            }
+            ExprKind::Field(ref mut tuple, index) => {
+                // the type of the tuple must already be known, as the
+                // number of elements can't be inferred from `.n`
+                let mut tuple_ty = Type::infer(ctxt);
+                tuple_ty.generate_inference_id(uf, ctxt);
+                try!(tuple.unify_type(ctxt, tuple_ty, uf, variables, function, functions));
+                let elt_ty = match uf.resolve(tuple_ty).map(|ty| *ty.0) {
+                    Some(TypeVariant::Tuple(elements)) if (index as usize) < elements.len() => {
+                        elements[index as usize]
+                    }
+                    _ => {
+                        return Err(AstError::NoSuchField {
+                            ty: uf.resolve(tuple_ty).unwrap_or(tuple_ty),
+                            field: index,
+                            line: self.line,
+                            function: function.name.clone(),
+                            compiler: fl!(),
+                        })
+                    }
+                };
+                let self_ty = self.ty;
+                try!(uf.unify(self.ty, elt_ty).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: self_ty,
+                        second: elt_ty,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                }));
+                uf.unify(elt_ty, to_unify).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: elt_ty,
+                        second: to_unify,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
             }
             ExprKind::Binop { op, ref mut lhs, ref mut rhs } => {
                 match op {
@@ -446,8 +912,13 @@ impl<'t> Expr<'t> {
                     }
 
                     Operand::AndAnd | Operand::OrOr => {
-                        rhs.ty.generate_inference_id(uf, ctxt);
-                        match lhs.unify_type(ctxt, rhs.ty, uf, variables, function, functions) {
+                        self.ty = Type::bool(ctxt);
+                        match lhs.unify_type(ctxt,
+                                             Type::bool(ctxt),
+                                             uf,
+                                             variables,
+                                             function,
+                                             functions) {
                             Err(AstError::CouldNotUnify { first, second, function, .. }) => {
                                 return Err(AstError::BinopUnsupported {
                                     op: op,
@@ -492,12 +963,24 @@ impl<'t> Expr<'t> {
                     Operand::Not => panic!("ICE: Not (`!`) is not a binop"),
                 }
             }
-            ExprKind::Call { ref callee, ref mut args } => {
+            ExprKind::Call { ref callee, ref ty_args, ref mut args, ref message, .. } => {
+                // a message is only taken by the panicking builtins, which
+                // aren't `Call`s anymore
+                let passed = args.len() + message.is_some() as usize;
                 match functions.get(callee) {
                     Some(f) => {
-                        if f.input().len() != args.len() {
+                        if !ty_args.is_empty() {
+                            return Err(AstError::IncorrectNumberOfTypeArguments {
+                                passed: ty_args.len(),
+                                expected: 0,
+                                callee: callee.clone(),
+                                caller: function.name.clone(),
+                                compiler: fl!(),
+                            });
+                        }
+                        if f.input().len() != passed {
                             return Err(AstError::IncorrectNumberOfArguments {
-                                passed: args.len(),
+                                passed: passed,
                                 expected: f.input().len(),
                                 callee: callee.clone(),
                                 caller: function.name.clone(),
@@ -514,6 +997,11 @@ impl<'t> Expr<'t> {
                                                  function,
                                                  functions));
                         }
+                        // like a `return`, a call which never returns
+                        // fits wherever a value is expected
+                        if *self.ty.0 == TypeVariant::Diverging {
+                            return Ok(());
+                        }
                         let ty = self.ty;
                         uf.unify(self.ty, to_unify).map_err(|()| {
                             AstError::CouldNotUnify {
@@ -525,9 +1013,100 @@ impl<'t> Expr<'t> {
                         })
                     }
                     None => {
-                        return Err(AstError::FunctionDoesntExist {
-                            function: callee.clone(),
-                            compiler: fl!(),
+                        let builtin = match Builtin::from_name(callee) {
+                            Some(b) => b,
+                            None => {
+                                return Err(AstError::FunctionDoesntExist {
+                                    function: callee.clone(),
+                                    compiler: fl!(),
+                                })
+                            }
+                        };
+                        if builtin.arity() != passed {
+                            return Err(AstError::IncorrectNumberOfArguments {
+                                passed: passed,
+                                expected: builtin.arity(),
+                                callee: callee.clone(),
+                                caller: function.name.clone(),
+                                compiler: fl!(),
+                            });
+                        }
+
+                        if ty_args.len() > 1 {
+                            return Err(AstError::IncorrectNumberOfTypeArguments {
+                                passed: ty_args.len(),
+                                expected: 1,
+                                callee: callee.clone(),
+                                caller: function.name.clone(),
+                                compiler: fl!(),
+                            });
+                        }
+
+                        // the arithmetic builtins take and return any one
+                        // integer type
+                        let mut param = if builtin.is_arithmetic() {
+                            Type::infer_int(ctxt)
+                        } else {
+                            Type::infer(ctxt)
+                        };
+                        param.generate_inference_id(uf, ctxt);
+                        if let Some(&explicit) = ty_args.first() {
+                            try!(uf.unify(param, explicit).map_err(|()| {
+                                AstError::CouldNotUnify {
+                                    first: param,
+                                    second: explicit,
+                                    function: function.name.clone(),
+                                    compiler: fl!(),
+                                }
+                            }));
+                        }
+                        // the raw pointer builtins work on both `*const T`
+                        // and `*mut T`; like with `*p`, the mutability is
+                        // taken from the pointer if that's already known
+                        let mut ptr_ty = None;
+                        let mut mutability = Mutability::Immutable;
+                        if builtin.takes_raw_pointer() {
+                            let mut ty = Type::infer(ctxt);
+                            ty.generate_inference_id(uf, ctxt);
+                            try!(args[0].unify_type(ctxt, ty, uf, variables, function, functions));
+                            if let Some(TypeVariant::RawPointer(_, m)) =
+                                   uf.resolve(ty).map(|ty| *ty.0) {
+                                mutability = m;
+                            }
+                            ptr_ty = Some(ty);
+                        }
+                        let (input, output) = builtin.signature(param, mutability, ctxt);
+                        self.ty = output;
+                        for (i, (arg_ty, expr)) in input.into_iter().zip(args).enumerate() {
+                            match ptr_ty {
+                                Some(ty) if i == 0 => {
+                                    try!(uf.unify(ty, arg_ty).map_err(|()| {
+                                        AstError::CouldNotUnify {
+                                            first: ty,
+                                            second: arg_ty,
+                                            function: function.name.clone(),
+                                            compiler: fl!(),
+                                        }
+                                    }));
+                                }
+                                _ => {
+                                    try!(expr.unify_type(ctxt,
+                                                         arg_ty,
+                                                         uf,
+                                                         variables,
+                                                         function,
+                                                         functions));
+                                }
+                            }
+                        }
+                        let ty = self.ty;
+                        uf.unify(self.ty, to_unify).map_err(|()| {
+                            AstError::CouldNotUnify {
+                                first: ty,
+                                second: to_unify,
+                                function: function.name.clone(),
+                                compiler: fl!(),
+                            }
                         })
                     }
                 }
@@ -563,7 +1142,8 @@ impl<'t> Expr<'t> {
                     }
                 })
             }
-            ExprKind::Block(ref mut blk) => {
+            ExprKind::Block(ref mut blk) |
+            ExprKind::Unsafe(ref mut blk) => {
                 try!(Self::typeck_block(blk, ctxt, to_unify, uf, variables, function, functions));
                 let ty = self.ty;
                 uf.unify(self.ty, to_unify).map_err(|()| {
@@ -579,11 +1159,36 @@ impl<'t> Expr<'t> {
                 self.ty = Type::diverging(ctxt);
                 ret.unify_type(ctxt, function.ret_ty, uf, variables, function, functions)
             }
+            ExprKind::Panic { .. } => {
+                self.ty = Type::diverging(ctxt);
+                Ok(())
+            }
+            ExprKind::Assert { ref mut condition, .. } => {
+                debug_assert!(self.ty == Type::unit(ctxt));
+                try!(condition.unify_type(ctxt,
+                                          Type::bool(ctxt),
+                                          uf,
+                                          variables,
+                                          function,
+                                          functions));
+                uf.unify(self.ty, to_unify).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: Type::unit(ctxt),
+                        second: to_unify,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
+            }
             ExprKind::Assign { ref mut dst, ref mut src } => {
                 debug_assert!(self.ty == Type::unit(ctxt));
                 match dst.kind {
                     ExprKind::Variable(ref name) => {
-                        if let Some(&ty) = variables.get(name) {
+                        if let Some(&(ty, _, _)) = variables.get(name) {
+                            dst.ty = ty;
+                            try!(src.unify_type(ctxt, ty, uf, variables, function, functions));
+                        } else if let Some(&(_, ty, _)) = function.args.get(name) {
+                            dst.ty = ty;
                             try!(src.unify_type(ctxt, ty, uf, variables, function, functions));
                         } else {
                             return Err(AstError::UndefinedVariableName {
@@ -593,15 +1198,30 @@ impl<'t> Expr<'t> {
                             });
                         }
                     }
-                    ExprKind::Deref(ref mut dst) => {
+                    ExprKind::Deref(ref mut ptr) => {
+                        let mut ptr_ty = Type::infer(ctxt);
+                        ptr_ty.generate_inference_id(uf, ctxt);
+                        try!(ptr.unify_type(ctxt, ptr_ty, uf, variables, function, functions));
                         let mut inner_ty = Type::infer(ctxt);
                         inner_ty.generate_inference_id(uf, ctxt);
-                        try!(dst.unify_type(ctxt,
-                                            Type::ref_(inner_ty, ctxt),
-                                            uf,
-                                            variables,
-                                            function,
-                                            functions));
+                        // writing needs a `&mut`, so that's what a pointer of
+                        // unknown type is taken to be
+                        try!(Self::unify_pointee(ptr_ty,
+                                                 inner_ty,
+                                                 Mutability::Mutable,
+                                                 uf,
+                                                 ctxt)
+                                 .map_err(|()| {
+                                     AstError::CouldNotUnify {
+                                         first: ptr_ty,
+                                         second: Type::reference(inner_ty,
+                                                                 Mutability::Mutable,
+                                                                 ctxt),
+                                         function: function.name.clone(),
+                                         compiler: fl!(),
+                                     }
+                                 }));
+                        dst.ty = inner_ty;
                         try!(src.unify_type(ctxt, inner_ty, uf, variables, function, functions));
                     }
                     _ => {
@@ -612,6 +1232,10 @@ impl<'t> Expr<'t> {
                         })
                     }
                 }
+                // the one assignment to a `let x;` is checked on the MIR
+                if !Self::is_write_once(dst, variables) {
+                    try!(Self::check_mutable(dst, self.line, uf, variables, function));
+                }
                 uf.unify(self.ty, Type::unit(ctxt)).map_err(|()| {
                     AstError::CouldNotUnify {
                         first: Type::unit(ctxt),
@@ -621,23 +1245,188 @@ impl<'t> Expr<'t> {
                     }
                 })
             }
-        }
-    }
-
-    pub fn finalize_block_ty(block: &mut Block<'t>,
-                             uf: &mut ty::UnionFind<'t>,
-                             function: &Function<'t>,
-                             ctxt: &'t TypeContext<'t>)
-                             -> Result<(), AstError<'t>> {
-        let mut live_blk = true;
-
-        for stmt in block.stmts.iter_mut() {
-            if !live_blk {
-                return Err(AstError::StatementsAfterReturn {
-                    function: function.name.clone(),
-                    compiler: fl!(),
-                });
+            ExprKind::CompoundAssign { op, ref mut dst, ref mut src } => {
+                debug_assert!(self.ty == Type::unit(ctxt));
+                match dst.kind {
+                    ExprKind::Variable(_) | ExprKind::Deref(_) => {}
+                    _ => {
+                        return Err(AstError::NotAnLvalue {
+                            expr: format!("{:?}", dst),
+                            function: function.name.clone(),
+                            compiler: fl!(),
+                        })
+                    }
+                }
+                // `dst op= src` is typed like `dst op src`
+                let mut ty = Type::infer(ctxt);
+                ty.generate_inference_id(uf, ctxt);
+                try!(dst.unify_type(ctxt, ty, uf, variables, function, functions));
+                match src.unify_type(ctxt, dst.ty, uf, variables, function, functions) {
+                    Err(AstError::CouldNotUnify { first, second, function, .. }) => {
+                        return Err(AstError::BinopUnsupported {
+                            op: op,
+                            lhs: second,
+                            rhs: first,
+                            function: function,
+                            compiler: fl!(),
+                        })
+                    }
+                    Err(e) => return Err(e),
+                    Ok(()) => {}
+                }
+                try!(Self::check_mutable(dst, self.line, uf, variables, function));
+                uf.unify(self.ty, to_unify).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: Type::unit(ctxt),
+                        second: to_unify,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                })
+            }
+        }
+    }
+
+    // brings the names in `pattern` into scope, with `ty` being the type of
+    // the whole
+    // `deferred` if the `let` has no initializer
+    fn bind_pattern(pattern: &Pattern,
+                    ty: Type<'t>,
+                    deferred: bool,
+                    ctxt: &'t TypeContext<'t>,
+                    uf: &mut ty::UnionFind<'t>,
+                    variables: &mut HashMap<String, (Type<'t>, Mutability, bool)>,
+                    function: &Function<'t>)
+                    -> Result<(), AstError<'t>> {
+        match *pattern {
+            Pattern::Binding { ref name, mutability } => {
+                let write_once = deferred && mutability == Mutability::Immutable;
+                variables.insert(name.clone(), (ty, mutability, write_once));
+                Ok(())
+            }
+            Pattern::Tuple(ref patterns) => {
+                let elements = patterns.iter()
+                                       .map(|_| {
+                                           let mut ty = Type::infer(ctxt);
+                                           ty.generate_inference_id(uf, ctxt);
+                                           ty
+                                       })
+                                       .collect::<Vec<_>>();
+                let tuple_ty = Type::tuple(elements.clone(), ctxt);
+                try!(uf.unify(ty, tuple_ty).map_err(|()| {
+                    AstError::CouldNotUnify {
+                        first: ty,
+                        second: tuple_ty,
+                        function: function.name.clone(),
+                        compiler: fl!(),
+                    }
+                }));
+                for (pattern, elt) in patterns.iter().zip(elements) {
+                    try!(Self::bind_pattern(pattern, elt, deferred, ctxt, uf, variables, function));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn binding_mutability(name: &str,
+                          variables: &HashMap<String, (Type<'t>, Mutability, bool)>,
+                          function: &Function<'t>)
+                          -> Option<Mutability> {
+        if let Some(&(_, mutability, _)) = variables.get(name) {
+            Some(mutability)
+        } else if let Some(&(_, _, mutability)) = function.args.get(name) {
+            Some(mutability)
+        } else {
+            None
+        }
+    }
+
+    // whether `dst` is a binding declared by a `let` without `mut` or an
+    // initializer
+    fn is_write_once(dst: &Expr<'t>,
+                     variables: &HashMap<String, (Type<'t>, Mutability, bool)>)
+                     -> bool {
+        match dst.kind {
+            ExprKind::Variable(ref name) => {
+                variables.get(name).map_or(false, |&(_, _, write_once)| write_once)
             }
+            _ => false,
+        }
+    }
+
+    // `dst` has already been unified; checks that it can be written to
+    fn check_mutable(dst: &Expr<'t>,
+                     line: u32,
+                     uf: &mut ty::UnionFind<'t>,
+                     variables: &HashMap<String, (Type<'t>, Mutability, bool)>,
+                     function: &Function<'t>)
+                     -> Result<(), AstError<'t>> {
+        match dst.kind {
+            ExprKind::Variable(ref name) => {
+                match Self::binding_mutability(name, variables, function) {
+                    Some(Mutability::Mutable) => Ok(()),
+                    _ => {
+                        Err(AstError::AssignToImmutable {
+                            name: name.clone(),
+                            line: line,
+                            function: function.name.clone(),
+                            compiler: fl!(),
+                        })
+                    }
+                }
+            }
+            ExprKind::Deref(ref ptr) => {
+                match uf.resolve(ptr.ty) {
+                    Some(ty) => {
+                        match *ty.0 {
+                            TypeVariant::Reference(_, Mutability::Immutable) |
+                            TypeVariant::RawPointer(_, Mutability::Immutable) => {
+                                Err(AstError::WriteThroughSharedRef {
+                                    ty: ty,
+                                    line: line,
+                                    function: function.name.clone(),
+                                    compiler: fl!(),
+                                })
+                            }
+                            _ => Ok(()),
+                        }
+                    }
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // unifies `ptr_ty` with a pointer to `pointee`. `*p` works through `&T`,
+    // `&mut T` and both kinds of raw pointer, so the kind of pointer is taken
+    // from `ptr_ty` if that's already known, and is a reference with
+    // `default` mutability otherwise
+    fn unify_pointee(ptr_ty: Type<'t>,
+                     pointee: Type<'t>,
+                     default: Mutability,
+                     uf: &mut ty::UnionFind<'t>,
+                     ctxt: &'t TypeContext<'t>)
+                     -> Result<(), ()> {
+        let ptr = match uf.resolve(ptr_ty).map(|ty| *ty.0) {
+            Some(TypeVariant::Reference(_, mutability)) => {
+                Type::reference(pointee, mutability, ctxt)
+            }
+            Some(TypeVariant::RawPointer(_, mutability)) => {
+                Type::raw_pointer(pointee, mutability, ctxt)
+            }
+            _ => Type::reference(pointee, default, ctxt),
+        };
+        uf.unify(ptr_ty, ptr)
+    }
+
+    pub fn finalize_block_ty(block: &mut Block<'t>,
+                             uf: &mut ty::UnionFind<'t>,
+                             function: &Function<'t>,
+                             ctxt: &'t TypeContext<'t>)
+                             -> Result<(), AstError<'t>> {
+        for stmt in block.stmts.iter_mut() {
             match *stmt {
                 Stmt::Let { ref mut ty, ref mut value, .. } => {
                     try!(ty.finalize(uf, ctxt).map_err(|()| {
@@ -650,10 +1439,6 @@ impl<'t> Expr<'t> {
                         try!(v.finalize_type(uf, function, ctxt));
                     }
                 }
-                Stmt::Expr(ref mut e @ Expr { kind: ExprKind::Return(_), .. }) => {
-                    try!(e.finalize_type(uf, function, ctxt));
-                    live_blk = false;
-                }
                 Stmt::Expr(ref mut e) => {
                     try!(e.finalize_type(uf, function, ctxt));
                 }
@@ -661,12 +1446,6 @@ impl<'t> Expr<'t> {
         }
 
         if let Some(ref mut expr) = block.expr {
-            if !live_blk {
-                return Err(AstError::StatementsAfterReturn {
-                    function: function.name.clone(),
-                    compiler: fl!(),
-                });
-            }
             try!(expr.finalize_type(uf, function, ctxt));
         }
         Ok(())
@@ -685,7 +1464,11 @@ impl<'t> Expr<'t> {
         }));
 
         match self.kind {
-            ExprKind::IntLiteral(_) |
+            ExprKind::IntLiteral(value) => {
+                Self::check_int_literal(value, false, self.ty, self.line, function, None)
+            }
+            ExprKind::FloatLiteral(_) |
+            ExprKind::CharLiteral(_) |
             ExprKind::BoolLiteral(_) |
             ExprKind::UnitLiteral |
             ExprKind::Variable(_) => Ok(()),
@@ -693,7 +1476,7 @@ impl<'t> Expr<'t> {
                 try!(inner.finalize_type(uf, function, ctxt));
                 assert!(self.ty == inner.ty);
                 match *self.ty.0 {
-                    TypeVariant::SInt(_) | TypeVariant::UInt(_) => Ok(()),
+                    TypeVariant::SInt(_) | TypeVariant::UInt(_) | TypeVariant::Float(_) => Ok(()),
                     _ => {
                         Err(AstError::UnopUnsupported {
                             op: Operand::Plus,
@@ -705,10 +1488,22 @@ impl<'t> Expr<'t> {
                 }
             }
             ExprKind::Neg(ref mut inner) => {
-                try!(inner.finalize_type(uf, function, ctxt));
+                if let ExprKind::IntLiteral(value) = inner.kind {
+                    // `-128s8` is fine, even though `128s8` isn't
+                    try!(inner.ty.finalize(uf, ctxt).map_err(|()| {
+                        AstError::NoActualType {
+                            compiler: fl!(),
+                            function: function.name.clone(),
+                        }
+                    }));
+                    try!(Self::check_int_literal(value, true, inner.ty, inner.line, function,
+                                                 None));
+                } else {
+                    try!(inner.finalize_type(uf, function, ctxt));
+                }
                 assert!(self.ty == inner.ty);
                 match *self.ty.0 {
-                    TypeVariant::SInt(_) => Ok(()),
+                    TypeVariant::SInt(_) | TypeVariant::Float(_) => Ok(()),
                     _ => {
                         Err(AstError::UnopUnsupported {
                             op: Operand::Minus,
@@ -736,9 +1531,9 @@ impl<'t> Expr<'t> {
                     }
                 }
             }
-            ExprKind::Ref(ref mut inner) => {
+            ExprKind::Ref(ref mut inner, mutability) => {
                 try!(inner.finalize_type(uf, function, ctxt));
-                assert!(self.ty == Type::ref_(inner.ty, ctxt),
+                assert!(self.ty == Type::reference(inner.ty, mutability, ctxt),
                         "self: {:?}, inner: &{:?}",
                         self.ty,
                         inner.ty);
@@ -746,15 +1541,66 @@ impl<'t> Expr<'t> {
             }
             ExprKind::Deref(ref mut inner) => {
                 try!(inner.finalize_type(uf, function, ctxt));
-                assert!(Type::ref_(self.ty, ctxt) == inner.ty,
-                        "self: {:?}, inner: *{:?}",
-                        self.ty,
-                        inner.ty);
+                match *inner.ty.0 {
+                    TypeVariant::Reference(pointee, _) |
+                    TypeVariant::RawPointer(pointee, _) if pointee == self.ty => Ok(()),
+                    _ => panic!("self: {:?}, inner: *{:?}", self.ty, inner.ty),
+                }
+            }
+            ExprKind::Cast(ref mut inner) => {
+                try!(inner.finalize_type(uf, function, ctxt));
+                if inner.ty == self.ty {
+                    return Ok(());
+                }
+                match (*inner.ty.0, *self.ty.0) {
+                    (TypeVariant::SInt(_), TypeVariant::SInt(_)) |
+                    (TypeVariant::SInt(_), TypeVariant::UInt(_)) |
+                    (TypeVariant::UInt(_), TypeVariant::SInt(_)) |
+                    (TypeVariant::UInt(_), TypeVariant::UInt(_)) |
+                    (TypeVariant::Bool, TypeVariant::SInt(_)) |
+                    (TypeVariant::Bool, TypeVariant::UInt(_)) |
+                    (TypeVariant::SInt(_), TypeVariant::Float(_)) |
+                    (TypeVariant::UInt(_), TypeVariant::Float(_)) |
+                    (TypeVariant::Float(_), TypeVariant::SInt(_)) |
+                    (TypeVariant::Float(_), TypeVariant::UInt(_)) |
+                    (TypeVariant::Float(_), TypeVariant::Float(_)) |
+                    (TypeVariant::Char, TypeVariant::SInt(_)) |
+                    (TypeVariant::Char, TypeVariant::UInt(_)) |
+                    (TypeVariant::UInt(ty::Int::I8), TypeVariant::Char) |
+                    (TypeVariant::UInt(ty::Int::I32), TypeVariant::Char) |
+                    (TypeVariant::RawPointer(..), TypeVariant::RawPointer(..)) |
+                    (TypeVariant::RawPointer(..), TypeVariant::UInt(ty::Int::Pointer)) |
+                    (TypeVariant::UInt(ty::Int::Pointer), TypeVariant::RawPointer(..)) |
+                    (TypeVariant::Reference(_, Mutability::Mutable), TypeVariant::RawPointer(..)) |
+                    (TypeVariant::Reference(_, Mutability::Mutable),
+                     TypeVariant::Reference(_, Mutability::Immutable)) |
+                    (TypeVariant::Reference(_, Mutability::Immutable),
+                     TypeVariant::RawPointer(_, Mutability::Immutable)) |
+                    (TypeVariant::Reference(..), TypeVariant::UInt(ty::Int::Pointer)) |
+                    (TypeVariant::RawPointer(..), TypeVariant::Reference(_, Mutability::Immutable)) |
+                    (TypeVariant::RawPointer(_, Mutability::Mutable),
+                     TypeVariant::Reference(_, Mutability::Mutable)) => Ok(()),
+                    _ => {
+                        Err(AstError::InvalidCast {
+                            from: inner.ty,
+                            to: self.ty,
+                            function: function.name.clone(),
+                            compiler: fl!(),
+                        })
+                    }
+                }
+            }
+            ExprKind::Tuple(ref mut elements) => {
+                for elt in elements {
+                    try!(elt.finalize_type(uf, function, ctxt));
+                }
                 Ok(())
             }
-            ExprKind::Binop { ref mut lhs, ref mut rhs, .. } => {
+            ExprKind::Field(ref mut tuple, _) => tuple.finalize_type(uf, function, ctxt),
+            ExprKind::Binop { op, ref mut lhs, ref mut rhs } => {
                 try!(lhs.finalize_type(uf, function, ctxt));
-                rhs.finalize_type(uf, function, ctxt)
+                try!(rhs.finalize_type(uf, function, ctxt));
+                Self::check_binop(op, lhs, rhs, function)
             }
             ExprKind::Call { ref mut args, .. } => {
                 for arg in args {
@@ -767,15 +1613,126 @@ impl<'t> Expr<'t> {
                 try!(Self::finalize_block_ty(then_value, uf, function, ctxt));
                 Self::finalize_block_ty(else_value, uf, function, ctxt)
             }
-            ExprKind::Block(ref mut blk) => Self::finalize_block_ty(blk, uf, function, ctxt),
+            ExprKind::Block(ref mut blk) |
+            ExprKind::Unsafe(ref mut blk) => Self::finalize_block_ty(blk, uf, function, ctxt),
             ExprKind::Return(ref mut ret) => {
                 assert!(*self.ty.0 == TypeVariant::Diverging);
                 ret.finalize_type(uf, function, ctxt)
             }
-            ExprKind::Assign { ref mut src, .. } => {
+            ExprKind::Panic { .. } => {
+                assert!(*self.ty.0 == TypeVariant::Diverging);
+                Ok(())
+            }
+            ExprKind::Assert { ref mut condition, .. } => {
                 assert!(*self.ty.0 == TypeVariant::Unit);
+                condition.finalize_type(uf, function, ctxt)
+            }
+            ExprKind::Assign { ref mut dst, ref mut src } => {
+                assert!(*self.ty.0 == TypeVariant::Unit);
+                try!(dst.finalize_type(uf, function, ctxt));
                 src.finalize_type(uf, function, ctxt)
             }
+            ExprKind::CompoundAssign { op, ref mut dst, ref mut src } => {
+                assert!(*self.ty.0 == TypeVariant::Unit);
+                try!(dst.finalize_type(uf, function, ctxt));
+                try!(src.finalize_type(uf, function, ctxt));
+                Self::check_binop(op, dst, src, function)
+            }
+        }
+    }
+
+    // `pointer_size` is only known in translation; pointer-sized literals
+    // aren't checked without it
+    fn check_int_literal(value: u64,
+                         negated: bool,
+                         ty: Type<'t>,
+                         line: u32,
+                         function: &Function<'t>,
+                         pointer_size: Option<u32>)
+                         -> Result<(), AstError<'t>> {
+        let (int, signed) = match *ty.0 {
+            TypeVariant::SInt(int) => (int, true),
+            TypeVariant::UInt(int) => (int, false),
+            _ => return Ok(()),
+        };
+        let bits = match (int.bits(), pointer_size) {
+            (Some(bits), _) | (None, Some(bits)) => bits,
+            (None, None) => return Ok(()),
+        };
+        let fits = match (signed, negated) {
+            (false, _) => bits >= 64 || value >> bits == 0,
+            (true, false) => bits > 64 || value >> (bits - 1) == 0,
+            (true, true) => bits > 64 || value <= 1 << (bits - 1),
+        };
+        if fits {
+            Ok(())
+        } else {
+            Err(AstError::IntLiteralOutOfRange {
+                literal: format!("{}{}", if negated { "-" } else { "" }, value),
+                ty: ty,
+                line: line,
+                function: function.name.clone(),
+                compiler: fl!(),
+            })
+        }
+    }
+
+    // translation can't fail, so the first pointer-sized literal which
+    // doesn't fit is kept for `add_body` to return
+    fn check_pointer_literal(value: u64,
+                             negated: bool,
+                             ty: Type<'t>,
+                             line: u32,
+                             mir: &mir::Mir<'t>,
+                             function: &mut Function<'t>) {
+        if let TypeVariant::SInt(ty::Int::Pointer) | TypeVariant::UInt(ty::Int::Pointer) = *ty.0 {
+            if function.literal_out_of_range.is_none() {
+                let pointer_size = Some(mir.pointer_size());
+                if let Err(e) = Self::check_int_literal(value, negated, ty, line, function,
+                                                        pointer_size) {
+                    function.literal_out_of_range = Some(e);
+                }
+            }
+        }
+    }
+
+    // checks that `lhs op rhs` makes sense, once the operand types are known
+    fn check_binop(op: Operand,
+                   lhs: &Expr<'t>,
+                   rhs: &Expr<'t>,
+                   function: &Function<'t>)
+                   -> Result<(), AstError<'t>> {
+        let unsupported = match (*lhs.ty.0, op) {
+            // tuples can't even be compared
+            (TypeVariant::Tuple(_), _) => true,
+            (TypeVariant::Float(_), Operand::Shl) |
+            (TypeVariant::Float(_), Operand::Shr) |
+            (TypeVariant::Float(_), Operand::And) |
+            (TypeVariant::Float(_), Operand::Xor) |
+            (TypeVariant::Float(_), Operand::Or) => true,
+            // chars can only be compared
+            (TypeVariant::Char, Operand::Mul) |
+            (TypeVariant::Char, Operand::Div) |
+            (TypeVariant::Char, Operand::Rem) |
+            (TypeVariant::Char, Operand::Plus) |
+            (TypeVariant::Char, Operand::Minus) |
+            (TypeVariant::Char, Operand::Shl) |
+            (TypeVariant::Char, Operand::Shr) |
+            (TypeVariant::Char, Operand::And) |
+            (TypeVariant::Char, Operand::Xor) |
+            (TypeVariant::Char, Operand::Or) => true,
+            _ => false,
+        };
+        if unsupported {
+            Err(AstError::BinopUnsupported {
+                op: op,
+                lhs: lhs.ty,
+                rhs: rhs.ty,
+                function: function.name.clone(),
+                compiler: fl!(),
+            })
+        } else {
+            Ok(())
         }
     }
 }
@@ -791,15 +1748,26 @@ impl<'t> Expr<'t> {
                      -> (mir::Value<'t>, Option<mir::Block>) {
         assert!(self.ty.is_final_type(), "not final type: {:?}", self);
         match self.kind {
-            ExprKind::IntLiteral(n) => (mir::Value::const_int(n, self.ty), Some(block)),
+            ExprKind::IntLiteral(n) => {
+                Self::check_pointer_literal(n, false, self.ty, self.line, mir, function);
+                (mir::Value::const_int(n, self.ty), Some(block))
+            }
+            ExprKind::FloatLiteral(n) => (mir::Value::const_float(n, self.ty), Some(block)),
+            ExprKind::CharLiteral(c) => (mir::Value::const_int(c as u64, self.ty), Some(block)),
             ExprKind::BoolLiteral(b) => (mir::Value::const_bool(b), Some(block)),
             ExprKind::UnitLiteral => (mir::Value::const_unit(), Some(block)),
             ExprKind::Variable(name) => {
                 if let Some(var) = locals.get(&name) {
                     (mir::Value::local(*var), Some(block))
-                } else if let Some(&(num, _)) = function.args.get(&name) {
+                } else if let Some(&(num, _, _)) = function.args.get(&name) {
                     (mir::Value::param(num as u32, &mut function.raw),
                      Some(block))
+                } else if let Some(value) = mir.get_const(&name) {
+                    (value, Some(block))
+                } else if mir.is_static(&name) {
+                    let ptr = mir::Value::static_ref(name, Mutability::Immutable);
+                    (mir::Value::deref(ptr, mir, &mut function.raw, &mut block, fn_types),
+                     Some(block))
                 } else {
                     panic!("ICE: unknown variable: {}", name)
                 }
@@ -814,9 +1782,18 @@ impl<'t> Expr<'t> {
                 }
             }
             ExprKind::Neg(e) => {
+                if let ExprKind::IntLiteral(n) = e.kind {
+                    // a negated literal is range checked as one, and the most
+                    // negative value only wraps to itself
+                    Self::check_pointer_literal(n, true, e.ty, e.line, mir, function);
+                    let inner = mir::Value::const_int(n, e.ty);
+                    return (mir::Value::wrapping_neg(inner, mir, &mut function.raw, &mut block,
+                                                     fn_types),
+                            Some(block));
+                }
                 let (inner, blk) = e.translate(mir, function, block, locals, fn_types);
                 if let Some(mut blk) = blk {
-                    (mir::Value::neg(inner, mir, &mut function.raw, &mut blk, fn_types),
+                    (mir::Value::neg(inner, self.line, mir, &mut function.raw, &mut blk, fn_types),
                      Some(blk))
                 } else {
                     (mir::Value::const_unit(), None)
@@ -831,10 +1808,20 @@ impl<'t> Expr<'t> {
                     (mir::Value::const_unit(), None)
                 }
             }
-            ExprKind::Ref(e) => {
+            ExprKind::Ref(e, mutability) => {
+                if let ExprKind::Variable(ref name) = e.kind {
+                    if let Some(ptr) = Self::static_ref(name, mutability, mir, function, locals) {
+                        return (ptr, Some(block));
+                    }
+                }
                 let (inner, blk) = e.translate(mir, function, block, locals, fn_types);
                 if let Some(mut blk) = blk {
-                    (mir::Value::ref_(inner, mir, &mut function.raw, &mut blk, fn_types),
+                    (mir::Value::ref_(inner,
+                                      mutability,
+                                      mir,
+                                      &mut function.raw,
+                                      &mut blk,
+                                      fn_types),
                      Some(blk))
                 } else {
                     (mir::Value::const_unit(), None)
@@ -849,15 +1836,57 @@ impl<'t> Expr<'t> {
                     (mir::Value::const_unit(), None)
                 }
             }
+            ExprKind::Cast(e) => {
+                let (inner, blk) = e.translate(mir, function, block, locals, fn_types);
+                if let Some(mut blk) = blk {
+                    (mir::Value::cast(inner,
+                                      self.ty,
+                                      self.line,
+                                      mir,
+                                      &mut function.raw,
+                                      &mut blk,
+                                      fn_types),
+                     Some(blk))
+                } else {
+                    (mir::Value::const_unit(), None)
+                }
+            }
+            ExprKind::Tuple(elements) => {
+                let mut values = Vec::with_capacity(elements.len());
+                for elt in elements {
+                    let (value, blk) = elt.translate(mir, function, block, locals, fn_types);
+                    if let Some(blk) = blk {
+                        block = blk;
+                    } else {
+                        return (mir::Value::const_unit(), None);
+                    }
+                    values.push(value);
+                }
+                (mir::Value::tuple(values, mir, &mut function.raw, &mut block, fn_types),
+                 Some(block))
+            }
+            ExprKind::Field(tuple, index) => {
+                let (tuple, blk) = tuple.translate(mir, function, block, locals, fn_types);
+                if let Some(mut blk) = blk {
+                    (mir::Value::field(tuple, index, mir, &mut function.raw, &mut blk, fn_types),
+                     Some(blk))
+                } else {
+                    (mir::Value::const_unit(), None)
+                }
+            }
             ExprKind::Binop { op: Operand::AndAnd, lhs, rhs } => {
                 let then = Block::expr(Expr::bool_lit(false, mir.ty_ctxt()));
+                let mut condition = Expr::not(*lhs, mir.ty_ctxt());
+                condition.ty = Type::bool(mir.ty_ctxt());
                 Expr {
                     kind: ExprKind::If {
-                        condition: Box::new(Expr::not(*lhs, mir.ty_ctxt())),
+                        condition: Box::new(condition),
                         then_value: Box::new(then),
                         else_value: Box::new(Block::expr(*rhs)),
                     },
                     ty: self.ty,
+                    line: self.line,
+                    offset: self.offset,
                 }
                 .translate(mir, function, block, locals, fn_types)
             }
@@ -870,10 +1899,13 @@ impl<'t> Expr<'t> {
                         else_value: Box::new(Block::expr(*rhs)),
                     },
                     ty: self.ty,
+                    line: self.line,
+                    offset: self.offset,
                 }
                 .translate(mir, function, block, locals, fn_types)
             }
             ExprKind::Binop { op, lhs, rhs } => {
+                let line = self.line;
                 let (lhs, blk) = {
                     let (lhs, blk) = lhs.translate(mir, function, block, locals, fn_types);
                     if let Some(blk) = blk {
@@ -890,67 +1922,11 @@ impl<'t> Expr<'t> {
                         return (rhs, None);
                     }
                 };
-                (match op {
-                    Operand::Plus => {
-                        mir::Value::add(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Minus => {
-                        mir::Value::sub(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-
-                    Operand::Mul => {
-                        mir::Value::mul(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Div => {
-                        mir::Value::div(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Rem => {
-                        mir::Value::rem(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-
-                    Operand::And => {
-                        mir::Value::and(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Xor => {
-                        mir::Value::xor(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Or => {
-                        mir::Value::or(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-
-                    Operand::Shl => {
-                        mir::Value::shl(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::Shr => {
-                        mir::Value::shr(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-
-                    Operand::EqualsEquals => {
-                        mir::Value::eq(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::NotEquals => {
-                        mir::Value::neq(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::LessThan => {
-                        mir::Value::lt(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::LessThanEquals => {
-                        mir::Value::lte(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::GreaterThan => {
-                        mir::Value::gt(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-                    Operand::GreaterThanEquals => {
-                        mir::Value::gte(lhs, rhs, mir, &mut function.raw, &mut blk, fn_types)
-                    }
-
-                    Operand::AndAnd => unreachable!(),
-                    Operand::OrOr => unreachable!(),
-                    Operand::Not => panic!("ICE: Not (`!`) is not a binop"),
-                },
+                let raw = &mut function.raw;
+                (Self::translate_binop(op, lhs, rhs, line, mir, raw, &mut blk, fn_types),
                  Some(blk))
             }
-            ExprKind::Call { callee, args } => {
+            ExprKind::Call { callee, args, .. } => {
                 let mut mir_args = Vec::new();
                 for arg in args {
                     let (arg, blk) = arg.translate(mir, function, block, locals, fn_types);
@@ -961,13 +1937,101 @@ impl<'t> Expr<'t> {
                     }
                     mir_args.push(arg);
                 }
-                (mir::Value::call(callee,
-                                  mir_args,
-                                  mir,
-                                  &mut function.raw,
-                                  &mut block,
-                                  fn_types),
-                 Some(block))
+                let builtin = if fn_types.contains_key(&callee) {
+                    None
+                } else {
+                    Builtin::from_name(&callee)
+                };
+                let raw = &mut function.raw;
+                let usize = Type::uint(ty::Int::Pointer, mir.ty_ctxt());
+                let value = match builtin {
+                    None => mir::Value::call(callee, mir_args, mir, raw, &mut block, fn_types),
+                    Some(Builtin::Alloc) => {
+                        let value = mir_args.pop().unwrap();
+                        let count = mir::Value::const_int(1, usize);
+                        mir::Value::alloc(count, value, self.line, mir, raw, &mut block, fn_types)
+                    }
+                    Some(Builtin::AllocArray) => {
+                        let value = mir_args.pop().unwrap();
+                        let count = mir_args.pop().unwrap();
+                        // an empty array has no element for the `&mut T` to
+                        // point to
+                        let count = block.write_to_tmp(count, mir, raw, fn_types);
+                        let zero = mir::Value::const_int(0, usize);
+                        let nonempty = mir::Value::neq(count.clone(), zero, mir, raw, &mut block,
+                                                       fn_types);
+                        block.assert(nonempty, self.line, "attempt to allocate an empty array",
+                                     mir, raw, fn_types);
+                        mir::Value::alloc(count, value, self.line, mir, raw, &mut block, fn_types)
+                    }
+                    Some(Builtin::Free) => {
+                        let ptr = mir_args.pop().unwrap();
+                        mir::Value::free(ptr, mir, raw, &mut block, fn_types)
+                    }
+                    Some(Builtin::Null) | Some(Builtin::NullMut) => {
+                        let zero = mir::Value::const_int(0, usize);
+                        mir::Value::cast(zero, self.ty, self.line, mir, raw, &mut block, fn_types)
+                    }
+                    Some(Builtin::IsNull) => {
+                        let ptr = mir_args.pop().unwrap();
+                        let address =
+                            mir::Value::cast(ptr, usize, self.line, mir, raw, &mut block, fn_types);
+                        let zero = mir::Value::const_int(0, usize);
+                        mir::Value::eq(address, zero, mir, raw, &mut block, fn_types)
+                    }
+                    Some(Builtin::Offset) => {
+                        let count = mir_args.pop().unwrap();
+                        let ptr = mir_args.pop().unwrap();
+                        mir::Value::offset(ptr, count, mir, raw, &mut block, fn_types)
+                    }
+                    Some(builtin) => {
+                        let rhs = mir_args.pop().unwrap();
+                        let lhs = mir_args.pop().unwrap();
+                        let block = &mut block;
+                        match builtin {
+                            Builtin::WrappingAdd => {
+                                mir::Value::wrapping_add(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::WrappingSub => {
+                                mir::Value::wrapping_sub(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::WrappingMul => {
+                                mir::Value::wrapping_mul(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::CheckedAdd => {
+                                mir::Value::overflowing_add(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::CheckedSub => {
+                                mir::Value::overflowing_sub(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::CheckedMul => {
+                                mir::Value::overflowing_mul(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::SaturatingAdd => {
+                                mir::Value::saturating_add(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::SaturatingSub => {
+                                mir::Value::saturating_sub(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::SaturatingMul => {
+                                mir::Value::saturating_mul(lhs, rhs, mir, raw, block, fn_types)
+                            }
+                            Builtin::Alloc |
+                            Builtin::AllocArray |
+                            Builtin::Free |
+                            Builtin::Null |
+                            Builtin::NullMut |
+                            Builtin::IsNull |
+                            Builtin::Offset => unreachable!(),
+                        }
+                    }
+                };
+                if *self.ty.0 == TypeVariant::Diverging {
+                    block.write_to_tmp(value, mir, &mut function.raw, fn_types);
+                    block.unreachable(&mut function.raw);
+                    return (mir::Value::const_unit(), None);
+                }
+                (value, Some(block))
             }
             ExprKind::If { condition, then_value, else_value } => {
                 let (cond, blk) = condition.translate(mir, function, block, locals, fn_types);
@@ -1005,14 +2069,35 @@ impl<'t> Expr<'t> {
                 }
                 (mir::Value::const_unit(), None)
             }
+            ExprKind::Panic { builtin, message } => {
+                let message = builtin.message(message.as_ref().map(|s| &s[..]));
+                block.panic(self.line, &message, mir, &mut function.raw);
+                (mir::Value::const_unit(), None)
+            }
+            ExprKind::Assert { builtin, condition, message } => {
+                let (cond, blk) = condition.translate(mir, function, block, locals, fn_types);
+                if let Some(mut blk) = blk {
+                    let message = builtin.message(message.as_ref().map(|s| &s[..]));
+                    blk.assert(cond, self.line, &message, mir, &mut function.raw, fn_types);
+                    (mir::Value::const_unit(), Some(blk))
+                } else {
+                    (mir::Value::const_unit(), None)
+                }
+            }
             ExprKind::Assign { dst, src } => {
                 let (value, blk) = src.translate(mir, function, block, locals, fn_types);
                 let blk = if let Some(mut blk) = blk {
                     match dst.kind {
                         ExprKind::Variable(name) => {
+                            let static_ref =
+                                Self::static_ref(&name, Mutability::Mutable, mir, function, locals);
+                            if let Some(ptr) = static_ref {
+                                blk.write_to_ptr(ptr, value, mir, &mut function.raw, fn_types);
+                                return (mir::Value::const_unit(), Some(blk));
+                            }
                             let var = if let Some(var) = locals.get(&name) {
                                 *var
-                            } else if let Some(&(num, _)) = function.args.get(&name) {
+                            } else if let Some(&(num, _, _)) = function.args.get(&name) {
                                 function.raw.get_param(num as u32)
                             } else {
                                 panic!("ICE: unknown variable: {}", name)
@@ -1038,12 +2123,188 @@ impl<'t> Expr<'t> {
                 };
                 (mir::Value::const_unit(), blk)
             }
-            ExprKind::Block(body) => {
+            ExprKind::CompoundAssign { op, dst, src } => {
+                let line = self.line;
+                let (value, blk) = src.translate(mir, function, block, locals, fn_types);
+                let blk = if let Some(mut blk) = blk {
+                    match dst.kind {
+                        ExprKind::Variable(name) => {
+                            let static_ref =
+                                Self::static_ref(&name, Mutability::Mutable, mir, function, locals);
+                            if let Some(ptr) = static_ref {
+                                let old = mir::Value::deref(ptr.clone(),
+                                                            mir,
+                                                            &mut function.raw,
+                                                            &mut blk,
+                                                            fn_types);
+                                let value = Self::translate_binop(op,
+                                                                  old,
+                                                                  value,
+                                                                  line,
+                                                                  mir,
+                                                                  &mut function.raw,
+                                                                  &mut blk,
+                                                                  fn_types);
+                                blk.write_to_ptr(ptr, value, mir, &mut function.raw, fn_types);
+                                return (mir::Value::const_unit(), Some(blk));
+                            }
+                            let var = if let Some(var) = locals.get(&name) {
+                                *var
+                            } else if let Some(&(num, _, _)) = function.args.get(&name) {
+                                function.raw.get_param(num as u32)
+                            } else {
+                                panic!("ICE: unknown variable: {}", name)
+                            };
+                            let value = Self::translate_binop(op,
+                                                              mir::Value::local(var),
+                                                              value,
+                                                              line,
+                                                              mir,
+                                                              &mut function.raw,
+                                                              &mut blk,
+                                                              fn_types);
+                            blk.write_to_var(var, value, &mut function.raw);
+                            Some(blk)
+                        }
+                        ExprKind::Deref(inner) => {
+                            let (ptr, blk) = inner.translate(mir,
+                                                             function,
+                                                             blk,
+                                                             locals,
+                                                             fn_types);
+                            if let Some(mut blk) = blk {
+                                // the pointer is both read from and written
+                                // to, but must only be evaluated once
+                                let ptr = blk.write_to_tmp(ptr, mir, &mut function.raw, fn_types);
+                                let old = mir::Value::deref(ptr.clone(),
+                                                            mir,
+                                                            &mut function.raw,
+                                                            &mut blk,
+                                                            fn_types);
+                                let value = Self::translate_binop(op,
+                                                                  old,
+                                                                  value,
+                                                                  line,
+                                                                  mir,
+                                                                  &mut function.raw,
+                                                                  &mut blk,
+                                                                  fn_types);
+                                blk.write_to_ptr(ptr, value, mir, &mut function.raw, fn_types);
+                                Some(blk)
+                            } else {
+                                None
+                            }
+                        }
+                        e => panic!("ICE: unsupported lvalue: {:?}", e),
+                    }
+                } else {
+                    None
+                };
+                (mir::Value::const_unit(), blk)
+            }
+            ExprKind::Block(body) |
+            ExprKind::Unsafe(body) => {
                 Self::translate_block(*body, mir, function, block, locals, fn_types)
             }
         }
     }
 
+    // the address of the static `name`, unless a local or a parameter
+    // shadows it
+    fn static_ref(name: &str,
+                  mutability: Mutability,
+                  mir: &mir::Mir<'t>,
+                  function: &Function<'t>,
+                  locals: &HashMap<String, mir::Variable>)
+                  -> Option<mir::Value<'t>> {
+        if locals.contains_key(name) || function.args.contains_key(name) || !mir.is_static(name) {
+            None
+        } else {
+            Some(mir::Value::static_ref(name.to_owned(), mutability))
+        }
+    }
+
+    fn translate_binop(op: Operand,
+                       lhs: mir::Value<'t>,
+                       rhs: mir::Value<'t>,
+                       line: u32,
+                       mir: &mir::Mir<'t>,
+                       function: &mut mir::Function<'t>,
+                       blk: &mut mir::Block,
+                       fn_types: &HashMap<String, ty::Function<'t>>)
+                       -> mir::Value<'t> {
+        match op {
+            Operand::Plus => mir::Value::add(lhs, rhs, line, mir, function, blk, fn_types),
+            Operand::Minus => mir::Value::sub(lhs, rhs, line, mir, function, blk, fn_types),
+
+            Operand::Mul => mir::Value::mul(lhs, rhs, line, mir, function, blk, fn_types),
+            Operand::Div => mir::Value::div(lhs, rhs, line, mir, function, blk, fn_types),
+            Operand::Rem => mir::Value::rem(lhs, rhs, line, mir, function, blk, fn_types),
+
+            Operand::And => mir::Value::and(lhs, rhs, mir, function, blk, fn_types),
+            Operand::Xor => mir::Value::xor(lhs, rhs, mir, function, blk, fn_types),
+            Operand::Or => mir::Value::or(lhs, rhs, mir, function, blk, fn_types),
+
+            Operand::Shl => mir::Value::shl(lhs, rhs, line, mir, function, blk, fn_types),
+            Operand::Shr => mir::Value::shr(lhs, rhs, line, mir, function, blk, fn_types),
+
+            Operand::EqualsEquals => mir::Value::eq(lhs, rhs, mir, function, blk, fn_types),
+            Operand::NotEquals => mir::Value::neq(lhs, rhs, mir, function, blk, fn_types),
+            Operand::LessThan => mir::Value::lt(lhs, rhs, mir, function, blk, fn_types),
+            Operand::LessThanEquals => mir::Value::lte(lhs, rhs, mir, function, blk, fn_types),
+            Operand::GreaterThan => mir::Value::gt(lhs, rhs, mir, function, blk, fn_types),
+            Operand::GreaterThanEquals => {
+                mir::Value::gte(lhs, rhs, mir, function, blk, fn_types)
+            }
+
+            Operand::AndAnd => unreachable!(),
+            Operand::OrOr => unreachable!(),
+            Operand::Not => panic!("ICE: Not (`!`) is not a binop"),
+        }
+    }
+
+    // creates a local for each name in `pattern`, and writes the matching
+    // part of `value` to it, if there is one
+    fn translate_pattern(pattern: Pattern,
+                         ty: Type<'t>,
+                         value: Option<mir::Value<'t>>,
+                         mir: &mir::Mir<'t>,
+                         function: &mut Function<'t>,
+                         blk: &mut mir::Block,
+                         bindings: &mut Vec<(String, mir::Variable)>,
+                         fn_types: &HashMap<String, ty::Function<'t>>) {
+        match pattern {
+            Pattern::Binding { name, mutability } => {
+                let var = if value.is_none() && mutability == Mutability::Immutable {
+                    function.raw.new_write_once_local(ty, name.clone())
+                } else {
+                    function.raw.new_local(ty, Some(name.clone()))
+                };
+                if let Some(value) = value {
+                    blk.write_to_var(var, value, &mut function.raw);
+                }
+                bindings.push((name, var));
+            }
+            Pattern::Tuple(patterns) => {
+                let elements = match *ty.0 {
+                    TypeVariant::Tuple(elements) => elements,
+                    _ => panic!("ICE: tuple pattern of a {}", ty),
+                };
+                // the value is evaluated once, and then split up
+                let value = value.map(|value| {
+                    blk.write_to_tmp(value, mir, &mut function.raw, fn_types)
+                });
+                for (i, (pattern, &elt_ty)) in patterns.into_iter().zip(elements).enumerate() {
+                    let elt = value.clone().map(|value| {
+                        mir::Value::field(value, i as u32, mir, &mut function.raw, blk, fn_types)
+                    });
+                    Self::translate_pattern(pattern, elt_ty, elt, mir, function, blk, bindings,
+                                            fn_types);
+                }
+            }
+        }
+    }
+
     pub fn translate_block(body: Block<'t>,
                            mir: &mir::Mir<'t>,
                            function: &mut Function<'t>,
@@ -1051,24 +2312,35 @@ impl<'t> Expr<'t> {
                            locals: &mut HashMap<String, mir::Variable>,
                            fn_types: &HashMap<String, ty::Function<'t>>)
                            -> (mir::Value<'t>, Option<mir::Block>) {
+        let outer_scope = locals.clone();
         let mut block = Some(block);
         for stmt in body.stmts {
             if let Some(blk) = block.take() {
                 match stmt {
-                    Stmt::Let { name, ty, value } => {
-                        let var = function.raw.new_local(ty);
-                        locals.insert(name, var);
-                        if let Some(value) = value {
+                    Stmt::Let { pattern, ty, value } => {
+                        // the initializer still sees the bindings being
+                        // shadowed
+                        let (value, blk) = if let Some(value) = value {
                             let (value, blk) = value.translate(mir,
                                                                function,
                                                                blk,
                                                                locals,
                                                                fn_types);
-                            if let Some(mut blk) = blk {
-                                blk.write_to_var(var, value, &mut function.raw);
-                                block = Some(blk);
-                            }
+                            (Some(value), blk)
                         } else {
+                            (None, Some(blk))
+                        };
+                        if let Some(mut blk) = blk {
+                            let mut bindings = Vec::new();
+                            Self::translate_pattern(pattern,
+                                                    ty,
+                                                    value,
+                                                    mir,
+                                                    function,
+                                                    &mut blk,
+                                                    &mut bindings,
+                                                    fn_types);
+                            locals.extend(bindings);
                             block = Some(blk);
                         }
                     }
@@ -1084,7 +2356,7 @@ impl<'t> Expr<'t> {
                 break;
             }
         }
-        if let Some(e) = body.expr {
+        let ret = if let Some(e) = body.expr {
             if let Some(blk) = block {
                 e.translate(mir, function, blk, locals, fn_types)
             } else {
@@ -1092,6 +2364,8 @@ impl<'t> Expr<'t> {
             }
         } else {
             (mir::Value::const_unit(), block)
-        }
+        };
+        *locals = outer_scope;
+        ret
     }
 }
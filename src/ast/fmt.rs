@@ -0,0 +1,660 @@
+use parse::{Lexer, Parser, ParserError, Operand};
+use ast::{Item, Block, Attribute};
+use ast::expr::{Stmt, Expr, ExprKind, Pattern, Message};
+use ty::{Type, TypeContext, TypeVariant, Mutability};
+
+// how tightly each kind of expression binds; binops are in between, at
+// `Operand::precedence`
+const ASSIGN: u8 = 0; // also `return`
+const CAST: u8 = 10;
+const UNARY: u8 = 11;
+const POSTFIX: u8 = 12; // method calls and fields
+const PRIMARY: u8 = 13;
+
+const INDENT: &'static str = "    ";
+// chains of `&&` and `||` are broken up to fit in this many columns
+const WIDTH: usize = 100;
+
+// the source, formatted; comments are kept, and moved to the end of the
+// statement they're in
+pub fn format(src: &str) -> Result<String, ParserError> {
+    let ctxt = TypeContext::new();
+    let mut parser = Parser::unresolved(Lexer::new(src));
+    let mut items = Vec::new();
+    loop {
+        let line = try!(parser.peek_line());
+        match parser.item(&ctxt) {
+            Ok(item) => items.push((line, item)),
+            Err(ParserError::ExpectedEof) => break,
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut fmt = Formatter {
+        parser: &parser,
+        next_comment: 0,
+        separate: false,
+        indent: 0,
+        out: String::new(),
+    };
+    for (i, &(line, ref item)) in items.iter().enumerate() {
+        // one line items are only separated if they were in the source
+        if i != 0 {
+            fmt.separate = is_function(item) || is_function(&items[i - 1].1);
+        }
+        fmt.comments_before(line);
+        fmt.own_line(line);
+        fmt.item(item);
+        fmt.out.push('\n');
+    }
+    fmt.comments_before(!0);
+    Ok(fmt.out)
+}
+
+fn is_function(item: &Item) -> bool {
+    match *item {
+        Item::Function { .. } => true,
+        _ => false,
+    }
+}
+
+struct Formatter<'a> {
+    parser: &'a Parser<'a>,
+    // the first comment which hasn't been written yet
+    next_comment: usize,
+    // whether the next line which starts something should be preceded by a
+    // blank line, whether or not there was one in the source
+    separate: bool,
+    indent: usize,
+    out: String,
+}
+
+impl<'a> Formatter<'a> {
+    fn start_line(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    // before the start of something at `line` in the source; blank lines are
+    // kept, but never more than one, nor at the start of a block
+    fn own_line(&mut self, line: u32) {
+        let blank = line > 1 && self.parser.blank_lines().binary_search(&(line - 1)).is_ok();
+        if (blank || self.separate) && !self.out.is_empty() && !self.out.ends_with("{\n") &&
+           !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.separate = false;
+    }
+
+    // writes out the comments before `line`. Those after a token go at the
+    // end of the last line written
+    fn comments_before(&mut self, line: u32) {
+        let comments = self.parser.comments();
+        while let Some(comment) = comments.get(self.next_comment) {
+            if comment.line >= line {
+                break;
+            }
+            self.next_comment += 1;
+            if comment.trailing && self.out.ends_with('\n') {
+                self.out.pop();
+                self.out.push(' ');
+            } else {
+                self.own_line(comment.line);
+                self.start_line();
+            }
+            self.out.push_str(&comment.text);
+            self.out.push('\n');
+        }
+    }
+
+    fn has_comments_before(&self, line: u32) -> bool {
+        match self.parser.comments().get(self.next_comment) {
+            Some(comment) => comment.line < line,
+            None => false,
+        }
+    }
+
+    fn item(&mut self, item: &Item) {
+        match *item {
+            Item::Function { ref name, ret, ref args, ref attributes, ref body } => {
+                for attribute in attributes {
+                    self.attribute(attribute);
+                }
+                self.start_line();
+                self.out.push_str("fn ");
+                self.signature(name, args, ret);
+                self.out.push(' ');
+                self.block(body, false);
+            }
+            Item::ExternFunction { ref name, ret, ref args } => {
+                self.start_line();
+                self.out.push_str("extern fn ");
+                self.signature(name, args, ret);
+                self.out.push(';');
+            }
+            Item::Const { ref name, ty, ref value } => {
+                self.start_line();
+                self.global("const ", name, ty, value);
+            }
+            Item::Static { ref name, ty, mutability, ref value } => {
+                self.start_line();
+                match mutability {
+                    Mutability::Immutable => self.global("static ", name, ty, value),
+                    Mutability::Mutable => self.global("static mut ", name, ty, value),
+                }
+            }
+            Item::TypeAlias { ref name, ty } => {
+                self.start_line();
+                self.out.push_str("type ");
+                self.out.push_str(name);
+                self.out.push_str(" = ");
+                let ty = self.ty(ty);
+                self.out.push_str(&ty);
+                self.out.push(';');
+            }
+        }
+    }
+
+    fn attribute(&mut self, attribute: &Attribute) {
+        self.start_line();
+        self.out.push_str("#[");
+        self.out.push_str(&attribute.name);
+        if !attribute.args.is_empty() {
+            self.out.push('(');
+            self.out.push_str(&attribute.args.join(", "));
+            self.out.push(')');
+        }
+        self.out.push_str("]\n");
+    }
+
+    // `name(args) -> ret`; `-> ()` is left off
+    fn signature(&mut self, name: &str, args: &[(String, Type, Mutability)], ret: Type) {
+        self.out.push_str(name);
+        self.out.push('(');
+        for (i, &(ref name, ty, mutability)) in args.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(", ");
+            }
+            if let Mutability::Mutable = mutability {
+                self.out.push_str("mut ");
+            }
+            self.out.push_str(name);
+            self.out.push_str(": ");
+            let ty = self.ty(ty);
+            self.out.push_str(&ty);
+        }
+        self.out.push(')');
+        if *ret.0 != TypeVariant::Unit {
+            self.out.push_str(" -> ");
+            let ret = self.ty(ret);
+            self.out.push_str(&ret);
+        }
+    }
+
+    // `NAME: ty = value;`, after `keyword`
+    fn global(&mut self, keyword: &str, name: &str, ty: Type, value: &Expr) {
+        self.out.push_str(keyword);
+        self.out.push_str(name);
+        self.out.push_str(": ");
+        let ty = self.ty(ty);
+        self.out.push_str(&ty);
+        self.out.push_str(" = ");
+        self.expr(value, 1);
+        self.out.push(';');
+    }
+
+    fn ty(&self, ty: Type) -> String {
+        match *ty.0 {
+            TypeVariant::Infer(Some(id)) => self.parser.type_name(id).to_owned(),
+            TypeVariant::Reference(inner, Mutability::Immutable) => format!("&{}", self.ty(inner)),
+            TypeVariant::Reference(inner, Mutability::Mutable) => {
+                format!("&mut {}", self.ty(inner))
+            }
+            TypeVariant::RawPointer(inner, Mutability::Immutable) => {
+                format!("*const {}", self.ty(inner))
+            }
+            TypeVariant::RawPointer(inner, Mutability::Mutable) => {
+                format!("*mut {}", self.ty(inner))
+            }
+            TypeVariant::Tuple(elements) => {
+                let elements: Vec<_> = elements.iter().map(|&elt| self.ty(elt)).collect();
+                format!("({})", elements.join(", "))
+            }
+            _ => format!("{}", ty),
+        }
+    }
+
+    // `{ expr }` stays on one line if it was written that way, and if
+    // `inline` is set
+    fn block(&mut self, block: &Block, inline: bool) {
+        if block.stmts.is_empty() && !self.has_comments_before(block.end_line) {
+            match block.expr {
+                None => {
+                    self.out.push_str("{}");
+                    return;
+                }
+                Some(ref expr) if inline && block.lines.first() == Some(&block.end_line) => {
+                    let len = self.out.len();
+                    self.out.push_str("{ ");
+                    self.expr(expr, ASSIGN);
+                    self.out.push_str(" }");
+                    if !self.out[len..].contains('\n') {
+                        return;
+                    }
+                    self.out.truncate(len);
+                }
+                Some(_) => {}
+            }
+        }
+
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for (i, stmt) in block.stmts.iter().enumerate() {
+            let line = block.lines.get(i).cloned().unwrap_or(0);
+            self.comments_before(line);
+            self.own_line(line);
+            self.start_line();
+            // a block-like expression only needs a `;` if it would otherwise
+            // be the value of the block, or the start of a binop
+            let semicolon = match *stmt {
+                Stmt::Expr(ref e) if e.is_block() => {
+                    match block.stmts.get(i + 1) {
+                        Some(&Stmt::Expr(ref next)) => starts_with_operator(next),
+                        Some(&Stmt::Let { .. }) => false,
+                        None => {
+                            match block.expr {
+                                Some(ref next) => starts_with_operator(next),
+                                None => true,
+                            }
+                        }
+                    }
+                }
+                _ => true,
+            };
+            self.stmt(stmt);
+            if semicolon {
+                self.out.push(';');
+            }
+            self.out.push('\n');
+        }
+        if let Some(ref expr) = block.expr {
+            let line = block.lines.get(block.stmts.len()).cloned().unwrap_or(0);
+            self.comments_before(line);
+            self.own_line(line);
+            self.start_line();
+            self.expr(expr, ASSIGN);
+            self.out.push('\n');
+        }
+        self.comments_before(block.end_line);
+        self.indent -= 1;
+        self.start_line();
+        self.out.push('}');
+    }
+
+    // without the `;`
+    fn stmt(&mut self, stmt: &Stmt) {
+        match *stmt {
+            Stmt::Let { ref pattern, ty, ref value } => {
+                self.out.push_str("let ");
+                self.pattern(pattern);
+                if *ty.0 != TypeVariant::Infer(None) {
+                    self.out.push_str(": ");
+                    let ty = self.ty(ty);
+                    self.out.push_str(&ty);
+                }
+                if let Some(ref value) = *value {
+                    self.out.push_str(" = ");
+                    self.expr(value, 1);
+                }
+            }
+            Stmt::Expr(ref e) => self.expr(e, ASSIGN),
+        }
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match *pattern {
+            Pattern::Binding { ref name, mutability } => {
+                if let Mutability::Mutable = mutability {
+                    self.out.push_str("mut ");
+                }
+                self.out.push_str(name);
+            }
+            Pattern::Tuple(ref patterns) => {
+                self.out.push('(');
+                for (i, pattern) in patterns.iter().enumerate() {
+                    if i != 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.pattern(pattern);
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i != 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr, 1);
+        }
+    }
+
+    // `expr`, in parentheses if it binds less tightly than `min`
+    fn expr(&mut self, expr: &Expr, min: u8) {
+        let parens = precedence(expr) < min;
+        if parens {
+            self.out.push('(');
+        }
+        match expr.kind {
+            ExprKind::Call { ref callee, ref ty_args, ref args, ref message, method } => {
+                let args = if method {
+                    self.receiver(&args[0]);
+                    self.out.push('.');
+                    &args[1..]
+                } else {
+                    &args[..]
+                };
+                self.out.push_str(callee);
+                if !ty_args.is_empty() {
+                    let ty_args: Vec<_> = ty_args.iter().map(|&ty| self.ty(ty)).collect();
+                    self.out.push_str("::<");
+                    self.out.push_str(&ty_args.join(", "));
+                    self.out.push('>');
+                }
+                self.out.push('(');
+                self.exprs(args);
+                self.message(message, !args.is_empty());
+                self.out.push(')');
+            }
+            ExprKind::If { ref condition, ref then_value, ref else_value } => {
+                self.out.push_str("if ");
+                self.expr(condition, 1);
+                self.out.push(' ');
+                self.block(then_value, true);
+                // a missing `else`, and `else if`, are blocks which weren't
+                // written out
+                if else_value.lines.is_empty() && else_value.stmts.is_empty() {
+                    match else_value.expr {
+                        Some(Expr { kind: ExprKind::UnitLiteral, .. }) => {}
+                        Some(ref else_if @ Expr { kind: ExprKind::If { .. }, .. }) => {
+                            self.out.push_str(" else ");
+                            self.expr(else_if, ASSIGN);
+                        }
+                        _ => {
+                            self.out.push_str(" else ");
+                            self.block(else_value, true);
+                        }
+                    }
+                } else {
+                    self.out.push_str(" else ");
+                    self.block(else_value, true);
+                }
+            }
+            ExprKind::Block(ref block) => self.block(block, true),
+            ExprKind::Unsafe(ref block) => {
+                self.out.push_str("unsafe ");
+                self.block(block, true);
+            }
+            ExprKind::Binop { op: Operand::AndAnd, .. } |
+            ExprKind::Binop { op: Operand::OrOr, .. } => self.chain(expr),
+            ExprKind::Binop { op, ref lhs, ref rhs } => {
+                self.expr(lhs, op.precedence());
+                self.out.push(' ');
+                self.out.push_str(operator(op));
+                self.out.push(' ');
+                self.expr(rhs, op.precedence() + 1);
+            }
+            ExprKind::Pos(ref inner) => self.unary("+", inner),
+            ExprKind::Neg(ref inner) => self.unary("-", inner),
+            ExprKind::Not(ref inner) => self.unary("!", inner),
+            ExprKind::Ref(ref inner, Mutability::Immutable) => self.unary("&", inner),
+            ExprKind::Ref(ref inner, Mutability::Mutable) => self.unary("&mut ", inner),
+            ExprKind::Deref(ref inner) => self.unary("*", inner),
+            ExprKind::Cast(ref inner) => {
+                self.expr(inner, CAST);
+                self.out.push_str(" as ");
+                let ty = self.ty(expr.ty);
+                self.out.push_str(&ty);
+            }
+            ExprKind::Tuple(ref elements) => {
+                self.out.push('(');
+                self.exprs(elements);
+                self.out.push(')');
+            }
+            ExprKind::Field(ref tuple, index) => {
+                self.receiver(tuple);
+                self.out.push('.');
+                self.out.push_str(&index.to_string());
+            }
+            ExprKind::Variable(ref name) => self.out.push_str(name),
+            ExprKind::IntLiteral(value) => {
+                let literal = match self.parser.literal(expr.offset) {
+                    Some(literal) => literal.to_owned(),
+                    None => {
+                        match *expr.ty.0 {
+                            TypeVariant::InferInt(_) => value.to_string(),
+                            _ => format!("{}{}", value, expr.ty),
+                        }
+                    }
+                };
+                self.out.push_str(&literal);
+            }
+            ExprKind::FloatLiteral(value) => {
+                let literal = match self.parser.literal(expr.offset) {
+                    Some(literal) => literal.to_owned(),
+                    None => {
+                        match *expr.ty.0 {
+                            TypeVariant::InferFloat(_) => format!("{:?}", value),
+                            _ => format!("{:?}{}", value, expr.ty),
+                        }
+                    }
+                };
+                self.out.push_str(&literal);
+            }
+            ExprKind::CharLiteral(c) => {
+                match self.parser.literal(expr.offset) {
+                    Some(literal) => self.out.push_str(literal),
+                    None => {
+                        self.out.push('\'');
+                        escape(c, '\'', &mut self.out);
+                        self.out.push('\'');
+                    }
+                }
+            }
+            ExprKind::BoolLiteral(true) => self.out.push_str("true"),
+            ExprKind::BoolLiteral(false) => self.out.push_str("false"),
+            ExprKind::UnitLiteral => self.out.push_str("()"),
+            ExprKind::Return(ref ret) => {
+                self.out.push_str("return");
+                if let ExprKind::UnitLiteral = ret.kind {
+                } else {
+                    self.out.push(' ');
+                    self.expr(ret, ASSIGN);
+                }
+            }
+            // only made by typeck
+            ExprKind::Panic { .. } | ExprKind::Assert { .. } => unreachable!(),
+            ExprKind::Assign { ref dst, ref src } => {
+                self.expr(dst, CAST);
+                self.out.push_str(" = ");
+                self.expr(src, 1);
+            }
+            ExprKind::CompoundAssign { op, ref dst, ref src } => {
+                self.expr(dst, CAST);
+                self.out.push(' ');
+                self.out.push_str(operator(op));
+                self.out.push_str("= ");
+                self.expr(src, 1);
+            }
+        }
+        if parens {
+            self.out.push(')');
+        }
+    }
+
+    // `a && b && ...`, broken before an operator whenever the next operand
+    // wouldn't fit
+    fn chain(&mut self, expr: &Expr) {
+        let op = match expr.kind {
+            ExprKind::Binop { op, .. } => op,
+            _ => unreachable!(),
+        };
+        let mut operands = vec![];
+        let mut lhs = expr;
+        while let ExprKind::Binop { op: lhs_op, lhs: ref inner, ref rhs } = lhs.kind {
+            if lhs_op != op {
+                break;
+            }
+            operands.push(&**rhs);
+            lhs = inner;
+        }
+        self.expr(lhs, op.precedence());
+        for rhs in operands.into_iter().rev() {
+            let start = self.out.len();
+            self.out.push(' ');
+            self.out.push_str(operator(op));
+            self.out.push(' ');
+            self.expr(rhs, op.precedence() + 1);
+            let line = self.out.rfind('\n').map_or(0, |i| i + 1);
+            if self.out[line..].chars().count() > WIDTH && !self.out[start..].contains('\n') {
+                self.out.truncate(start);
+                self.out.push('\n');
+                self.indent += 1;
+                self.start_line();
+                self.indent -= 1;
+                self.out.push_str(operator(op));
+                self.out.push(' ');
+                self.expr(rhs, op.precedence() + 1);
+            }
+        }
+    }
+
+    fn unary(&mut self, op: &str, inner: &Expr) {
+        self.out.push_str(op);
+        self.expr(inner, UNARY);
+    }
+
+    // the left hand side of `.`. `1.f()` would be lexed as a float, so
+    // literals need parentheses
+    fn receiver(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::IntLiteral(_) | ExprKind::FloatLiteral(_) => {
+                self.out.push('(');
+                self.expr(expr, ASSIGN);
+                self.out.push(')');
+            }
+            _ => self.expr(expr, POSTFIX),
+        }
+    }
+
+    // the message of a panicking builtin, if there is one
+    fn message(&mut self, message: &Option<Message>, after_args: bool) {
+        if let Some(ref message) = *message {
+            if after_args {
+                self.out.push_str(", ");
+            }
+            match self.parser.literal(message.offset) {
+                Some(literal) => self.out.push_str(literal),
+                None => {
+                    self.out.push('"');
+                    for c in message.text.chars() {
+                        escape(c, '"', &mut self.out);
+                    }
+                    self.out.push('"');
+                }
+            }
+        }
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr.kind {
+        ExprKind::Assign { .. } | ExprKind::CompoundAssign { .. } | ExprKind::Return(_) => ASSIGN,
+        ExprKind::Binop { op, .. } => op.precedence(),
+        ExprKind::Cast(_) => CAST,
+        ExprKind::Pos(_) |
+        ExprKind::Neg(_) |
+        ExprKind::Not(_) |
+        ExprKind::Ref(..) |
+        ExprKind::Deref(_) => UNARY,
+        ExprKind::Field(..) | ExprKind::Call { method: true, .. } => POSTFIX,
+        ExprKind::Call { .. } |
+        ExprKind::If { .. } |
+        ExprKind::Block(_) |
+        ExprKind::Unsafe(_) |
+        ExprKind::Tuple(_) |
+        ExprKind::Variable(_) |
+        ExprKind::IntLiteral(_) |
+        ExprKind::FloatLiteral(_) |
+        ExprKind::CharLiteral(_) |
+        ExprKind::BoolLiteral(_) |
+        ExprKind::UnitLiteral |
+        ExprKind::Panic { .. } |
+        ExprKind::Assert { .. } => PRIMARY,
+    }
+}
+
+// whether `expr` would continue a block-like expression before it, i.e. `-x`
+// after `if c {}` is a subtraction. Parentheses aren't taken into account, so
+// this may say yes when it's not needed
+fn starts_with_operator(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Pos(_) |
+        ExprKind::Neg(_) |
+        ExprKind::Not(_) |
+        ExprKind::Ref(..) |
+        ExprKind::Deref(_) => true,
+        ExprKind::Binop { lhs: ref first, .. } |
+        ExprKind::Assign { dst: ref first, .. } |
+        ExprKind::CompoundAssign { dst: ref first, .. } |
+        ExprKind::Cast(ref first) |
+        ExprKind::Field(ref first, _) => starts_with_operator(first),
+        ExprKind::Call { ref args, method: true, .. } => starts_with_operator(&args[0]),
+        _ => false,
+    }
+}
+
+pub fn operator(op: Operand) -> &'static str {
+    match op {
+        Operand::Mul => "*",
+        Operand::Div => "/",
+        Operand::Rem => "%",
+        Operand::Plus => "+",
+        Operand::Minus => "-",
+        Operand::Shl => "<<",
+        Operand::Shr => ">>",
+        Operand::And => "&",
+        Operand::Xor => "^",
+        Operand::Or => "|",
+        Operand::EqualsEquals => "==",
+        Operand::NotEquals => "!=",
+        Operand::LessThan => "<",
+        Operand::LessThanEquals => "<=",
+        Operand::GreaterThan => ">",
+        Operand::GreaterThanEquals => ">=",
+        Operand::AndAnd => "&&",
+        Operand::OrOr => "||",
+        Operand::Not => "!",
+    }
+}
+
+// `c`, as it's written inside a literal delimited by `quote`
+fn escape(c: char, quote: char, out: &mut String) {
+    match c {
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        '\0' => out.push_str("\\0"),
+        '\\' => out.push_str("\\\\"),
+        c if c == quote => {
+            out.push('\\');
+            out.push(c);
+        }
+        c if c.is_control() && (c as u32) < 0x80 => out.push_str(&format!("\\x{:02X}", c as u32)),
+        c if c.is_control() => out.push_str(&format!("\\u{{{:X}}}", c as u32)),
+        c => out.push(c),
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashSet;
+use super::Block;
+use super::builtin::Panicking;
+use ty::TypeVariant;
+use super::expr::{Stmt, Expr, ExprKind};
+
+// what the lints need to know about a function body, gathered in one walk
+// over it
+pub struct BodyInfo {
+    // every function called from the body
+    pub callees: HashSet<String>,
+    // the `return`s, `panic(..)`s, `unreachable()`s and calls of functions
+    // returning `!` which are followed by more statements, and their lines
+    pub unreachable: Vec<(String, u32)>,
+    // calls to `#[must_use]` functions whose result is thrown away, and their
+    // lines
+    pub unused_results: Vec<(String, u32)>,
+}
+
+impl BodyInfo {
+    pub fn new(body: &Block, must_use: &HashSet<String>) -> Self {
+        let mut info = BodyInfo {
+            callees: HashSet::new(),
+            unreachable: Vec::new(),
+            unused_results: Vec::new(),
+        };
+        info.block(body, must_use);
+        info
+    }
+
+    fn block(&mut self, block: &Block, must_use: &HashSet<String>) {
+        let mut returned = None;
+        for stmt in &block.stmts {
+            if let Some(diverged) = returned.take() {
+                self.unreachable.push(diverged);
+            }
+            match *stmt {
+                Stmt::Let { ref value, .. } => {
+                    if let Some(ref value) = *value {
+                        self.expr(value, must_use);
+                    }
+                }
+                Stmt::Expr(ref e) => {
+                    match e.kind {
+                        ExprKind::Return(_) => returned = Some(("return".to_owned(), e.line)),
+                        ExprKind::Panic { builtin: Panicking::Panic, .. } => {
+                            returned = Some(("panic(..)".to_owned(), e.line))
+                        }
+                        ExprKind::Panic { builtin: Panicking::Unreachable, .. } => {
+                            returned = Some(("unreachable()".to_owned(), e.line))
+                        }
+                        ExprKind::Call { ref callee, .. } if *e.ty.0 == TypeVariant::Diverging => {
+                            returned = Some((format!("{}(..)", callee), e.line))
+                        }
+                        ExprKind::Call { ref callee, .. } if must_use.contains(callee) => {
+                            self.unused_results.push((callee.clone(), e.line));
+                        }
+                        _ => {}
+                    }
+                    self.expr(e, must_use);
+                }
+            }
+        }
+        if let Some(ref e) = block.expr {
+            if let Some(diverged) = returned.take() {
+                self.unreachable.push(diverged);
+            }
+            self.expr(e, must_use);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, must_use: &HashSet<String>) {
+        match expr.kind {
+            ExprKind::Call { ref callee, ref args, .. } => {
+                self.callees.insert(callee.clone());
+                for arg in args {
+                    self.expr(arg, must_use);
+                }
+            }
+            ExprKind::Tuple(ref elements) => {
+                for elt in elements {
+                    self.expr(elt, must_use);
+                }
+            }
+            ExprKind::If { ref condition, ref then_value, ref else_value } => {
+                self.expr(condition, must_use);
+                self.block(then_value, must_use);
+                self.block(else_value, must_use);
+            }
+            ExprKind::Block(ref block) |
+            ExprKind::Unsafe(ref block) => self.block(block, must_use),
+            ExprKind::Binop { ref lhs, ref rhs, .. } |
+            ExprKind::Assign { dst: ref lhs, src: ref rhs } |
+            ExprKind::CompoundAssign { dst: ref lhs, src: ref rhs, .. } => {
+                self.expr(lhs, must_use);
+                self.expr(rhs, must_use);
+            }
+            ExprKind::Pos(ref inner) |
+            ExprKind::Neg(ref inner) |
+            ExprKind::Not(ref inner) |
+            ExprKind::Ref(ref inner, _) |
+            ExprKind::Deref(ref inner) |
+            ExprKind::Cast(ref inner) |
+            ExprKind::Field(ref inner, _) |
+            ExprKind::Return(ref inner) |
+            ExprKind::Assert { condition: ref inner, .. } => self.expr(inner, must_use),
+            ExprKind::Variable(_) |
+            ExprKind::IntLiteral(_) |
+            ExprKind::FloatLiteral(_) |
+            ExprKind::CharLiteral(_) |
+            ExprKind::BoolLiteral(_) |
+            ExprKind::UnitLiteral |
+            ExprKind::Panic { .. } => {}
+        }
+    }
+}
@@ -1,73 +1,247 @@
-use std;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use parse;
-use ty::{self, Type};
+use ty::{self, Type, Mutability};
 use mir;
+use lint::{Lint, Level, Lints};
 
+pub mod builtin;
 pub mod expr;
+pub mod fmt;
+mod dump;
+mod lint;
+mod eval;
+mod unsafety;
+mod prelude;
 use self::expr::{Stmt, Expr};
+use self::lint::BodyInfo;
+use self::eval::ConstEval;
 
 pub struct Ast<'t> {
     functions: HashMap<String, (Function<'t>, Block<'t>)>,
     function_types: HashMap<String, ty::Function<'t>>,
+    // functions defined outside of the program, i.e. in C. Their types are
+    // also in `function_types`
+    externs: HashMap<String, ty::Function<'t>>,
+    // the initializers are blocks with only an expression, so that they can
+    // be typechecked like function bodies
+    consts: HashMap<String, (Type<'t>, Block<'t>)>,
+    statics: HashMap<String, (Type<'t>, Mutability, Block<'t>)>,
+    // the names of the items which came from the prelude
+    prelude: HashSet<String>,
     ctxt: &'t ty::TypeContext<'t>
 }
 
 impl<'t> Ast<'t> {
-    pub fn create(lexer: parse::Lexer, ctxt: &'t ty::TypeContext<'t>)
-        -> Result<Self, parse::ParserError> {
-            let mut parser = parse::Parser::new(lexer);
-            let mut functions = HashMap::new();
-            let mut function_types = HashMap::new();
-
-            loop {
-                match parser.item(ctxt) {
-                    Ok(Item::Function {
-                        name,
-                        ret,
-                        args,
-                        body,
-                    }) => {
-                        let ty = ty::Function::new(
-                            args.iter().map(|&(_, t)| t).collect(), ret);
-                        let f = try!(Function::new(name.clone(), ret, args));
-                        function_types.insert(name.clone(), ty);
-                        if let Some(_) =
-                            functions.insert(name.clone(), (f, body)) {
-                                return Err(parse::ParserError::DuplicatedFunction {
-                                    function: name,
-                                    compiler: fl!(),
-                                });
-                            }
-                    }
+    // the items of the prelude, if there is one, and of the program are put
+    // together into one Ast. The program's items shadow the prelude's.
+    pub fn create(prelude: Option<parse::Lexer>,
+                  program: parse::Lexer,
+                  ctxt: &'t ty::TypeContext<'t>)
+                  -> Result<Self, parse::ParserError> {
+        let program = try!(Self::parse_items(program, ctxt));
+        let mut prelude_items = match prelude {
+            Some(lexer) => try!(Self::parse_items(lexer, ctxt)),
+            None => Vec::new(),
+        };
+        prelude::hide_shadowed(&mut prelude_items, &program);
+
+        let mut ast = Ast {
+            functions: HashMap::new(),
+            function_types: HashMap::new(),
+            externs: HashMap::new(),
+            consts: HashMap::new(),
+            statics: HashMap::new(),
+            prelude: HashSet::new(),
+            ctxt: ctxt,
+        };
+        for item in prelude_items {
+            if let Some(name) = item.name() {
+                ast.prelude.insert(name.to_owned());
+            }
+            try!(ast.add_item(item));
+        }
+        for item in program {
+            try!(ast.add_item(item));
+        }
+        Ok(ast)
+    }
 
-                    Err(parse::ParserError::ExpectedEof) => break,
-                    Err(e) => return Err(e),
+    fn parse_items(lexer: parse::Lexer, ctxt: &'t ty::TypeContext<'t>)
+                   -> Result<Vec<Item<'t>>, parse::ParserError> {
+        let mut parser = parse::Parser::new(lexer);
+        let mut items = Vec::new();
+        // aliases are local to the file they're defined in
+        let mut type_aliases = HashSet::new();
+        loop {
+            match parser.item(ctxt) {
+                Ok(Item::TypeAlias { name, .. }) => {
+                    if !type_aliases.insert(name.clone()) {
+                        return Err(parse::ParserError::DuplicatedItem {
+                            name: name,
+                            compiler: fl!(),
+                        });
+                    }
                 }
+                Ok(item) => items.push(item),
+                Err(parse::ParserError::ExpectedEof) => return Ok(items),
+                Err(e) => return Err(e),
             }
+        }
+    }
 
-            Ok(Ast {
-                functions: functions,
-                function_types: function_types,
-                ctxt: ctxt,
-            })
+    fn add_item(&mut self, item: Item<'t>) -> Result<(), parse::ParserError> {
+        let functions = &mut self.functions;
+        let function_types = &mut self.function_types;
+        let externs = &mut self.externs;
+        let consts = &mut self.consts;
+        let statics = &mut self.statics;
+        match item {
+            Item::Function {
+                name,
+                ret,
+                args,
+                attributes,
+                body,
+            } => {
+                let ty = ty::Function::new(
+                    args.iter().map(|&(_, t, _)| t).collect(), ret);
+                let f = try!(Function::new(name.clone(), ret, args, attributes));
+                if consts.contains_key(&name) || statics.contains_key(&name) {
+                    return Err(parse::ParserError::DuplicatedItem {
+                        name: name,
+                        compiler: fl!(),
+                    });
+                }
+                function_types.insert(name.clone(), ty);
+                if externs.contains_key(&name) ||
+                   functions.insert(name.clone(), (f, body)).is_some() {
+                        return Err(parse::ParserError::DuplicatedFunction {
+                            function: name,
+                            compiler: fl!(),
+                        });
+                    }
+            }
+            Item::ExternFunction { name, ret, args } => {
+                let ty = ty::Function::new(
+                    args.into_iter().map(|(_, t, _)| t).collect(), ret);
+                if consts.contains_key(&name) || statics.contains_key(&name) {
+                    return Err(parse::ParserError::DuplicatedItem {
+                        name: name,
+                        compiler: fl!(),
+                    });
+                }
+                function_types.insert(name.clone(), ty.clone());
+                if functions.contains_key(&name) ||
+                   externs.insert(name.clone(), ty).is_some() {
+                    return Err(parse::ParserError::DuplicatedFunction {
+                        function: name,
+                        compiler: fl!(),
+                    });
+                }
+            }
+            Item::Const { name, ty, value } => {
+                if functions.contains_key(&name) || externs.contains_key(&name) ||
+                   consts.contains_key(&name) || statics.contains_key(&name) {
+                    return Err(parse::ParserError::DuplicatedItem {
+                        name: name,
+                        compiler: fl!(),
+                    });
+                }
+                consts.insert(name, (ty, Block::expr(value)));
+            }
+            Item::Static { name, ty, mutability, value } => {
+                if functions.contains_key(&name) || externs.contains_key(&name) ||
+                   consts.contains_key(&name) || statics.contains_key(&name) {
+                    return Err(parse::ParserError::DuplicatedItem {
+                        name: name,
+                        compiler: fl!(),
+                    });
+                }
+                statics.insert(name, (ty, mutability, Block::expr(value)));
+            }
+            Item::TypeAlias { .. } => panic!("ICE: type aliases are only kept by the parser"),
         }
+        Ok(())
+    }
+
+    // infers the types of every initializer and body, and checks that unsafe
+    // operations are only done in `unsafe` blocks. Returns how many denied
+    // lints were emitted
+    fn infer_types(&mut self, lints: &Lints) -> Result<u32, AstError<'t>> {
+            let mut denied = 0;
+
+            // consts can be used in the initializers of consts and statics,
+            // and both can be used in functions, unless a parameter shadows
+            // them
+            let mut globals = HashMap::new();
+            for (name, &(ty, _)) in &self.consts {
+                globals.insert(name.clone(), (ty, Mutability::Immutable, false));
+            }
+            for (name, &mut (ty, ref mut init)) in self.consts.iter_mut() {
+                try!(typeck_initializer(name, ty, init, &globals, self.ctxt,
+                                        &self.function_types));
+            }
+            for (name, &mut (ty, _, ref mut init)) in self.statics.iter_mut() {
+                try!(typeck_initializer(name, ty, init, &globals, self.ctxt,
+                                        &self.function_types));
+            }
+            for (name, &(ty, mutability, _)) in &self.statics {
+                globals.insert(name.clone(), (ty, mutability, false));
+            }
 
-    pub fn typeck(mut self, opt: bool)
-        -> Result<mir::Mir<'t>, AstError<'t>> {
             for (_, &mut (ref func, ref mut body))
                 in self.functions.iter_mut() {
                     let mut uf = ty::UnionFind::new();
-                    let mut vars = HashMap::<String, Type>::new();
+                    let mut vars = globals.iter()
+                        .filter(|&(name, _)| !func.args.contains_key(name))
+                        .map(|(name, &global)| (name.clone(), global))
+                        .collect::<HashMap<String, (Type, Mutability, bool)>>();
                     try!(Expr::typeck_block(body, &self.ctxt, func.ret_ty,
                                             &mut uf, &mut vars, func, &self.function_types));
+                    let lints = func.lints(lints);
+                    for ty in uf.apply_defaults(&self.ctxt) {
+                        let message = format!("a literal with no other constraints was given \
+                                               the type {}", ty);
+                        if lints.emit(Lint::LiteralDefaults, &func.name, &message) {
+                            denied += 1;
+                        }
+                    }
                     try!(Expr::finalize_block_ty(body, &mut uf, func, &self.ctxt));
+                    if let Err((operation, line)) = unsafety::check_block(body,
+                                                                          false,
+                                                                          &self.externs,
+                                                                          &self.function_types) {
+                        return Err(AstError::RequiresUnsafe {
+                            operation: operation.description(),
+                            line: line,
+                            function: func.name.clone(),
+                            compiler: fl!(),
+                        });
+                    }
+                    // the consts are typechecked by now, so divisors can be
+                    // evaluated
+                    let mut eval = ConstEval::new(&self.consts);
+                    let mut locals = func.args.keys().cloned().collect();
+                    try!(eval.check_divisors(&func.name, &mut locals, body));
                 }
+            Ok(denied)
+        }
+
+    // `print_typed_ast` prints the tree once every type has been inferred, or
+    // as far as inference got before an error
+    pub fn typeck(mut self, file: &str, opt: bool, overflow_checks: bool, lints: &Lints,
+                  print_typed_ast: bool)
+        -> Result<mir::Mir<'t>, AstError<'t>> {
+            let inferred = self.infer_types(lints);
+            if print_typed_ast {
+                println!("{}", self);
+            }
+            let mut denied = try!(inferred);
             if let Some(&(ref f, _)) = self.functions.get("main") {
                 if *f.ret_ty.0 != ty::TypeVariant::SInt(ty::Int::I32) ||
                     f.args.len() != 0 {
                         let mut input = Vec::new();
-                        for (_, &(_, ty)) in &f.args {
+                        for (_, &(_, ty, _)) in &f.args {
                             input.push(ty);
                         }
                         return Err(AstError::IncorrectMainType {
@@ -82,14 +256,146 @@ impl<'t> Ast<'t> {
                     compiler: fl!(),
                 })
             }
-            let mut mir = mir::Mir::new(self.ctxt, opt);
-            let functions = std::mem::replace(&mut self.functions, HashMap::new());
+            denied += self.lint_bodies(lints);
+
+            let mut mir = mir::Mir::new(self.ctxt, file, opt, overflow_checks);
+            for (name, ty) in &self.externs {
+                mir.add_extern(name.clone(), prelude::extern_symbol(name).to_owned(), ty.clone());
+            }
+            {
+                let mut eval = ConstEval::new(&self.consts);
+                for name in self.consts.keys() {
+                    mir.add_const(name.clone(), try!(eval.const_value(name)));
+                }
+                for (name, &(ty, mutability, ref init)) in &self.statics {
+                    let init = try!(eval.static_value(name, ty, init));
+                    mir.add_static(name.clone(), mutability, init);
+                }
+            }
+            let functions = ::std::mem::replace(&mut self.functions, HashMap::new());
             for (name, (func, body)) in functions {
-                let mir_func = func.add_body(body, &mir, &self);
+                let lints = func.lints(lints);
+                let mir_func = try!(func.add_body(body, &mir, &self));
+                for variable in mir_func.unread_variables() {
+                    let message = format!("the variable `{}` is never read", variable);
+                    if lints.emit(Lint::UnusedVariables, &name, &message) {
+                        denied += 1;
+                    }
+                }
+                if let Err(variable) = mir_func.check_initialization() {
+                    return Err(AstError::UseOfPossiblyUninitialized {
+                        variable: variable,
+                        function: name,
+                        compiler: fl!(),
+                    });
+                }
+                if let Err(variable) = mir_func.check_single_assignment() {
+                    return Err(AstError::AssignTwiceToImmutable {
+                        variable: variable,
+                        function: name,
+                        compiler: fl!(),
+                    });
+                }
+                match mir_func.check_escapes(&mir, &self.function_types) {
+                    Ok(()) => {}
+                    Err(mir::Escape::Returned { referent }) => {
+                        return Err(AstError::ReturnsReferenceToLocal {
+                            referent: referent,
+                            function: name,
+                            compiler: fl!(),
+                        });
+                    }
+                    Err(mir::Escape::Stored { referent }) => {
+                        return Err(AstError::StoresReferenceToLocal {
+                            referent: referent,
+                            function: name,
+                            compiler: fl!(),
+                        });
+                    }
+                }
                 mir.add_function(name, mir_func);
             }
+            if denied != 0 {
+                return Err(AstError::DeniedLints {
+                    count: denied,
+                    compiler: fl!(),
+                });
+            }
             Ok(mir)
         }
+
+    // the lints which look at the bodies as written: dead code, unreachable
+    // code and unused results. Returns how many denied lints were hit.
+    fn lint_bodies(&self, lints: &Lints) -> u32 {
+        let must_use = self.functions.iter()
+            .filter(|&(_, &(ref func, _))| func.must_use)
+            .map(|(name, _)| name.clone())
+            .collect::<HashSet<_>>();
+        let mut names = self.functions.keys().collect::<Vec<_>>();
+        names.sort();
+
+        let mut denied = 0;
+        let mut infos = HashMap::new();
+        for name in names.iter().cloned() {
+            let (ref func, ref body) = self.functions[name];
+            let lints = func.lints(lints);
+            let info = BodyInfo::new(body, &must_use);
+            for &(ref diverged, line) in &info.unreachable {
+                let message = format!("the statements after the `{}` on line {} are \
+                                       unreachable",
+                                      diverged,
+                                      line);
+                if lints.emit(Lint::UnreachableCode, name, &message) {
+                    denied += 1;
+                }
+            }
+            for &(ref callee, line) in &info.unused_results {
+                let message = format!("the result of `{}` on line {} is unused", callee, line);
+                if lints.emit(Lint::UnusedMustUse, name, &message) {
+                    denied += 1;
+                }
+            }
+            infos.insert(name.clone(), info);
+        }
+
+        let mut reachable = HashSet::new();
+        let mut worklist = vec!["main".to_owned()];
+        while let Some(name) = worklist.pop() {
+            if let Some(info) = infos.get(&name) {
+                if reachable.insert(name.clone()) {
+                    worklist.extend(info.callees.iter().cloned());
+                }
+            }
+        }
+        for name in names {
+            // a program needn't use all of the prelude
+            if !reachable.contains(name) && !self.prelude.contains(name) {
+                let lints = self.functions[name].0.lints(lints);
+                if lints.emit(Lint::DeadCode, name, "the function is never called") {
+                    denied += 1;
+                }
+            }
+        }
+        denied
+    }
+}
+
+// typechecks the initializer of the const or static `name` like the body of
+// a function returning `ty`
+fn typeck_initializer<'t>(name: &str,
+                          ty: Type<'t>,
+                          init: &mut Block<'t>,
+                          globals: &HashMap<String, (Type<'t>, Mutability, bool)>,
+                          ctxt: &'t ty::TypeContext<'t>,
+                          function_types: &HashMap<String, ty::Function<'t>>)
+                          -> Result<(), AstError<'t>> {
+    let func = Function::new(name.to_owned(), ty, Vec::new(), Vec::new())
+        .expect("ICE: a function without arguments or attributes is always valid");
+    let mut uf = ty::UnionFind::new();
+    let mut vars = globals.clone();
+    try!(Expr::typeck_block(init, ctxt, ty, &mut uf, &mut vars, &func, function_types));
+    uf.apply_defaults(ctxt);
+    Expr::finalize_block_ty(init, &mut uf, &func, ctxt)
 }
 
 #[derive(Debug)]
@@ -101,6 +407,13 @@ pub enum AstError<'t> {
         caller: String,
         compiler: (&'static str, u32),
     },
+    IncorrectNumberOfTypeArguments {
+        passed: usize,
+        expected: usize,
+        callee: String,
+        caller: String,
+        compiler: (&'static str, u32),
+    },
     UndefinedVariableName {
         name: String,
         function: String,
@@ -131,10 +444,6 @@ pub enum AstError<'t> {
         function: String,
         compiler: (&'static str, u32),
     },
-    StatementsAfterReturn {
-        function: String,
-        compiler: (&'static str, u32),
-    },
     NotAnLvalue {
         expr: String,
         function: String,
@@ -147,6 +456,98 @@ pub enum AstError<'t> {
         function: String,
         compiler: (&'static str, u32),
     },
+    DivisionByZero {
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    InvalidCast {
+        from: Type<'t>,
+        to: Type<'t>,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    AssignToImmutable {
+        name: String,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    MutBorrowOfImmutable {
+        name: String,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    WriteThroughSharedRef {
+        ty: Type<'t>,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    // `expr.n` where `expr` isn't known to be a tuple of more than `n`
+    // elements
+    NoSuchField {
+        ty: Type<'t>,
+        field: u32,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    // e.g. dereferencing a raw pointer outside of an `unsafe` block
+    RequiresUnsafe {
+        operation: &'static str,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    UseOfPossiblyUninitialized {
+        variable: String,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    // a `let x;` without `mut` which may be assigned more than once
+    AssignTwiceToImmutable {
+        variable: String,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    ReturnsReferenceToLocal {
+        referent: String,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    StoresReferenceToLocal {
+        referent: String,
+        function: String,
+        compiler: (&'static str, u32),
+    },
+    DeniedLints {
+        count: u32,
+        compiler: (&'static str, u32),
+    },
+    // `item` is the const or static with the initializer
+    NotConstant {
+        item: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    ConstEvalOverflow {
+        item: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    RecursiveConst {
+        name: String,
+        compiler: (&'static str, u32),
+    },
+    IntLiteralOutOfRange {
+        literal: String,
+        ty: Type<'t>,
+        line: u32,
+        function: String,
+        compiler: (&'static str, u32),
+    },
 }
 
 #[derive(Debug)]
@@ -154,8 +555,70 @@ pub enum Item<'t> {
     Function {
         name: String,
         ret: Type<'t>,
-        args: Vec<(String, Type<'t>)>,
+        args: Vec<(String, Type<'t>, Mutability)>,
+        attributes: Vec<Attribute>,
         body: Block<'t>,
+    },
+    Const {
+        name: String,
+        ty: Type<'t>,
+        value: Expr<'t>,
+    },
+    Static {
+        name: String,
+        ty: Type<'t>,
+        mutability: Mutability,
+        value: Expr<'t>,
+    },
+    // `extern fn name(args) -> ret;`
+    ExternFunction {
+        name: String,
+        ret: Type<'t>,
+        args: Vec<(String, Type<'t>, Mutability)>,
+    },
+    // aliases are expanded by the parser; this is only kept to check for
+    // duplicates, and for the formatter
+    TypeAlias {
+        name: String,
+        ty: Type<'t>,
+    },
+}
+
+impl<'t> Item<'t> {
+    // type aliases don't count, since they're local to their file
+    fn name(&self) -> Option<&str> {
+        match *self {
+            Item::Function { ref name, .. } |
+            Item::Const { ref name, .. } |
+            Item::Static { ref name, .. } |
+            Item::ExternFunction { ref name, .. } => Some(name),
+            Item::TypeAlias { .. } => None,
+        }
+    }
+
+    fn name_mut(&mut self) -> Option<&mut String> {
+        match *self {
+            Item::Function { ref mut name, .. } |
+            Item::Const { ref mut name, .. } |
+            Item::Static { ref mut name, .. } |
+            Item::ExternFunction { ref mut name, .. } => Some(name),
+            Item::TypeAlias { .. } => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    name: String,
+    args: Vec<String>,
+}
+
+impl Attribute {
+    pub fn new(name: String, args: Vec<String>) -> Self {
+        Attribute {
+            name: name,
+            args: args,
+        }
     }
 }
 
@@ -163,23 +626,57 @@ pub enum Item<'t> {
 pub struct Function<'t> {
     name: String,
     ret_ty: Type<'t>,
-    args: HashMap<String, (usize, Type<'t>)>,
+    args: HashMap<String, (usize, Type<'t>, Mutability)>,
+    lint_levels: Vec<(Lint, Level)>,
+    must_use: bool,
     raw: mir::Function<'t>,
+    // the first pointer-sized literal which doesn't fit, found in translation
+    literal_out_of_range: Option<AstError<'t>>,
 }
 
 impl<'t> Function<'t> {
-    fn new(name: String, ret_ty: Type<'t>, args: Vec<(String, Type<'t>)>)
+    fn new(name: String, ret_ty: Type<'t>, args: Vec<(String, Type<'t>, Mutability)>,
+           attributes: Vec<Attribute>)
         -> Result<Function<'t>, parse::ParserError> {
+            let mut inline = mir::Inline::Default;
+            let mut lint_levels = Vec::new();
+            let mut must_use = false;
+            for attr in attributes {
+                match &*attr.name {
+                    "inline" if attr.args.is_empty() => inline = mir::Inline::Always,
+                    "inline" if attr.args == ["always"] => inline = mir::Inline::Always,
+                    "inline" if attr.args == ["never"] => inline = mir::Inline::Never,
+                    "must_use" if attr.args.is_empty() => must_use = true,
+                    // #[allow(lint, ...)], #[warn(lint, ...)] or #[deny(lint, ...)]
+                    level if Level::from_attribute(level).is_some() && !attr.args.is_empty() &&
+                             attr.args.iter().all(|arg| Lint::from_name(arg).is_some()) => {
+                        let level = Level::from_attribute(level).unwrap();
+                        for arg in &attr.args {
+                            lint_levels.push((Lint::from_name(arg).unwrap(), level));
+                        }
+                    }
+                    _ => {
+                        return Err(parse::ParserError::InvalidAttribute {
+                            attribute: attr,
+                            function: name,
+                            compiler: fl!(),
+                        });
+                    }
+                };
+            }
+
             let mut args_ty = Vec::new();
+            let mut arg_names = Vec::new();
             let mut args_hashmap = HashMap::new();
             let mut arg_index = 0;
 
 
-            for (arg_name, arg_ty) in args {
+            for (arg_name, arg_ty, mutability) in args {
                 if !args_hashmap.contains_key(&arg_name) {
                     args_ty.push(arg_ty);
+                    arg_names.push(arg_name.clone());
                     debug_assert!(
-                        args_hashmap.insert(arg_name, (arg_index, arg_ty))
+                        args_hashmap.insert(arg_name, (arg_index, arg_ty, mutability))
                         .is_none());
                     arg_index += 1;
                 } else {
@@ -191,18 +688,33 @@ impl<'t> Function<'t> {
                 }
             }
 
-            let raw = mir::Function::new(ty::Function::new(args_ty, ret_ty));
+            let raw = mir::Function::new(ty::Function::new(args_ty, ret_ty), inline, arg_names);
 
             Ok(Function {
                 name: name,
                 ret_ty: ret_ty,
                 args: args_hashmap,
+                lint_levels: lint_levels,
+                must_use: must_use,
                 raw: raw,
+                literal_out_of_range: None,
             })
         }
 
+    // `lints`, as changed by this function's attributes
+    fn lints(&self, lints: &Lints) -> Lints {
+        let mut lints = lints.clone();
+        for &(lint, level) in &self.lint_levels {
+            lints.set(lint, level);
+        }
+        lints
+    }
+
     fn add_body(mut self, body: Block<'t>, mir: &mir::Mir<'t>, ast: &Ast<'t>)
-        -> mir::Function<'t> {
+        -> Result<mir::Function<'t>, AstError<'t>> {
+            if ast.prelude.contains(&self.name) {
+                self.raw.set_file(prelude::FILE);
+            }
             let block = self.raw.start_block();
             let mut locals = HashMap::new();
             let (ret, blk) = Expr::translate_block(body, mir, &mut self, block,
@@ -210,7 +722,10 @@ impl<'t> Function<'t> {
             if let Some(blk) = blk {
                 blk.finish(&mut self.raw, ret);
             }
-            self.raw
+            match self.literal_out_of_range {
+                Some(e) => Err(e),
+                None => Ok(self.raw),
+            }
         }
 }
 
@@ -218,13 +733,24 @@ impl<'t> Function<'t> {
 pub struct Block<'t> {
     stmts: Vec<Stmt<'t>>,
     expr: Option<Expr<'t>>,
+    // the line each of `stmts`, then `expr`, starts on, and the line of the
+    // `}`; only used by the formatter, and empty for blocks which weren't
+    // written out
+    lines: Vec<u32>,
+    end_line: u32,
 }
 
 impl<'t> Block<'t> {
-    pub fn new(stmts: Vec<Stmt<'t>>, expr: Option<Expr<'t>>) -> Self {
+    pub fn new(stmts: Vec<Stmt<'t>>,
+               expr: Option<Expr<'t>>,
+               lines: Vec<u32>,
+               end_line: u32)
+               -> Self {
         Block {
             stmts: stmts,
             expr: expr,
+            lines: lines,
+            end_line: end_line,
         }
     }
 
@@ -232,6 +758,8 @@ impl<'t> Block<'t> {
         Block {
             stmts: vec![],
             expr: Some(e),
+            lines: vec![],
+            end_line: 0,
         }
     }
 }
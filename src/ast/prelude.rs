@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use super::{Block, Item};
+use super::expr::{Stmt, Expr, ExprKind};
+
+// the file named in the prelude's panics
+pub const FILE: &'static str = "<prelude>";
+
+// a program may define an item with the same name as one of the prelude's,
+// which is then renamed to this. It can't be written in a program, since
+// `::` is only ever followed by `<`
+fn hidden_name(name: &str) -> String {
+    format!("prelude::{}", name)
+}
+
+// the C name of an extern which may have been renamed by `hide_shadowed`
+pub fn extern_symbol(name: &str) -> &str {
+    name.rsplit("::").next().unwrap()
+}
+
+// renames the prelude's items which are also defined by the program, and
+// the prelude's calls of them. The prelude has no consts or statics, so
+// calls are the only uses there are.
+pub fn hide_shadowed(items: &mut [Item], program: &[Item]) {
+    let program = program.iter().filter_map(Item::name).collect::<Vec<_>>();
+    let mut renamed = HashMap::new();
+    for item in items.iter_mut() {
+        if let Some(name) = item.name_mut() {
+            if program.contains(&&name[..]) {
+                let hidden = hidden_name(name);
+                renamed.insert(name.clone(), hidden.clone());
+                *name = hidden;
+            }
+        }
+    }
+    if renamed.is_empty() {
+        return;
+    }
+    for item in items {
+        if let Item::Function { ref mut body, .. } = *item {
+            rename_calls_in_block(body, &renamed);
+        }
+    }
+}
+
+fn rename_calls_in_block(block: &mut Block, renamed: &HashMap<String, String>) {
+    for stmt in &mut block.stmts {
+        match *stmt {
+            Stmt::Let { ref mut value, .. } => {
+                if let Some(ref mut value) = *value {
+                    rename_calls(value, renamed);
+                }
+            }
+            Stmt::Expr(ref mut e) => rename_calls(e, renamed),
+        }
+    }
+    if let Some(ref mut e) = block.expr {
+        rename_calls(e, renamed);
+    }
+}
+
+fn rename_calls(expr: &mut Expr, renamed: &HashMap<String, String>) {
+    match expr.kind {
+        ExprKind::Call { ref mut callee, ref mut args, .. } => {
+            if let Some(hidden) = renamed.get(callee) {
+                *callee = hidden.clone();
+            }
+            for arg in args {
+                rename_calls(arg, renamed);
+            }
+        }
+        ExprKind::Tuple(ref mut elements) => {
+            for elt in elements {
+                rename_calls(elt, renamed);
+            }
+        }
+        ExprKind::If { ref mut condition, ref mut then_value, ref mut else_value } => {
+            rename_calls(condition, renamed);
+            rename_calls_in_block(then_value, renamed);
+            rename_calls_in_block(else_value, renamed);
+        }
+        ExprKind::Block(ref mut block) |
+        ExprKind::Unsafe(ref mut block) => rename_calls_in_block(block, renamed),
+        ExprKind::Binop { ref mut lhs, ref mut rhs, .. } |
+        ExprKind::Assign { dst: ref mut lhs, src: ref mut rhs } |
+        ExprKind::CompoundAssign { dst: ref mut lhs, src: ref mut rhs, .. } => {
+            rename_calls(lhs, renamed);
+            rename_calls(rhs, renamed);
+        }
+        ExprKind::Pos(ref mut inner) |
+        ExprKind::Neg(ref mut inner) |
+        ExprKind::Not(ref mut inner) |
+        ExprKind::Ref(ref mut inner, _) |
+        ExprKind::Deref(ref mut inner) |
+        ExprKind::Cast(ref mut inner) |
+        ExprKind::Field(ref mut inner, _) |
+        ExprKind::Return(ref mut inner) |
+        ExprKind::Assert { condition: ref mut inner, .. } => rename_calls(inner, renamed),
+        ExprKind::Variable(_) |
+        ExprKind::IntLiteral(_) |
+        ExprKind::FloatLiteral(_) |
+        ExprKind::CharLiteral(_) |
+        ExprKind::BoolLiteral(_) |
+        ExprKind::UnitLiteral |
+        ExprKind::Panic { .. } => {}
+    }
+}
@@ -0,0 +1,122 @@
+use super::Block;
+use super::builtin::Builtin;
+use super::expr::{Stmt, Expr, ExprKind};
+use std::collections::HashMap;
+use ty::{self, TypeVariant};
+
+// something which may only be done inside an `unsafe` block
+#[derive(Copy, Clone, Debug)]
+pub enum Operation {
+    DerefOfRawPointer,
+    CastOfRawPointerToReference,
+    CallOfExternFunction,
+    CallOfUnsafeBuiltin,
+}
+
+impl Operation {
+    pub fn description(self) -> &'static str {
+        match self {
+            Operation::DerefOfRawPointer => "dereference of a raw pointer",
+            Operation::CastOfRawPointerToReference => "cast of a raw pointer to a reference",
+            Operation::CallOfExternFunction => "call of an extern function",
+            Operation::CallOfUnsafeBuiltin => "call of an unsafe builtin",
+        }
+    }
+}
+
+// the first unsafe operation outside of an `unsafe` block, and its line. Must
+// be called once the types are final
+pub fn check_block(block: &Block,
+                   in_unsafe: bool,
+                   externs: &HashMap<String, ty::Function>,
+                   functions: &HashMap<String, ty::Function>)
+                   -> Result<(), (Operation, u32)> {
+    for stmt in &block.stmts {
+        match *stmt {
+            Stmt::Let { ref value, .. } => {
+                if let Some(ref value) = *value {
+                    try!(check_expr(value, in_unsafe, externs, functions));
+                }
+            }
+            Stmt::Expr(ref e) => try!(check_expr(e, in_unsafe, externs, functions)),
+        }
+    }
+    if let Some(ref e) = block.expr {
+        try!(check_expr(e, in_unsafe, externs, functions));
+    }
+    Ok(())
+}
+
+fn is_raw_pointer(expr: &Expr) -> bool {
+    match *expr.ty.0 {
+        TypeVariant::RawPointer(..) => true,
+        _ => false,
+    }
+}
+
+fn check_expr(expr: &Expr,
+              in_unsafe: bool,
+              externs: &HashMap<String, ty::Function>,
+              functions: &HashMap<String, ty::Function>)
+              -> Result<(), (Operation, u32)> {
+    match expr.kind {
+        ExprKind::Unsafe(ref block) => check_block(block, true, externs, functions),
+        ExprKind::Block(ref block) => check_block(block, in_unsafe, externs, functions),
+        ExprKind::If { ref condition, ref then_value, ref else_value } => {
+            try!(check_expr(condition, in_unsafe, externs, functions));
+            try!(check_block(then_value, in_unsafe, externs, functions));
+            check_block(else_value, in_unsafe, externs, functions)
+        }
+        ExprKind::Deref(ref ptr) => {
+            if !in_unsafe && is_raw_pointer(ptr) {
+                return Err((Operation::DerefOfRawPointer, expr.line));
+            }
+            check_expr(ptr, in_unsafe, externs, functions)
+        }
+        ExprKind::Cast(ref inner) => {
+            if let TypeVariant::Reference(..) = *expr.ty.0 {
+                if !in_unsafe && is_raw_pointer(inner) {
+                    return Err((Operation::CastOfRawPointerToReference, expr.line));
+                }
+            }
+            check_expr(inner, in_unsafe, externs, functions)
+        }
+        ExprKind::Call { ref callee, .. } if !in_unsafe && externs.contains_key(callee) => {
+            Err((Operation::CallOfExternFunction, expr.line))
+        }
+        ExprKind::Call { ref callee, .. } if !in_unsafe && !functions.contains_key(callee) &&
+                                             Builtin::from_name(callee)
+                                                 .map_or(false, Builtin::is_unsafe) => {
+            Err((Operation::CallOfUnsafeBuiltin, expr.line))
+        }
+        ExprKind::Call { args: ref elements, .. } |
+        ExprKind::Tuple(ref elements) => {
+            for arg in elements {
+                try!(check_expr(arg, in_unsafe, externs, functions));
+            }
+            Ok(())
+        }
+        ExprKind::Binop { ref lhs, ref rhs, .. } |
+        ExprKind::Assign { dst: ref lhs, src: ref rhs } |
+        ExprKind::CompoundAssign { dst: ref lhs, src: ref rhs, .. } => {
+            try!(check_expr(lhs, in_unsafe, externs, functions));
+            check_expr(rhs, in_unsafe, externs, functions)
+        }
+        ExprKind::Pos(ref inner) |
+        ExprKind::Neg(ref inner) |
+        ExprKind::Not(ref inner) |
+        ExprKind::Ref(ref inner, _) |
+        ExprKind::Field(ref inner, _) |
+        ExprKind::Return(ref inner) |
+        ExprKind::Assert { condition: ref inner, .. } => {
+            check_expr(inner, in_unsafe, externs, functions)
+        }
+        ExprKind::Variable(_) |
+        ExprKind::IntLiteral(_) |
+        ExprKind::FloatLiteral(_) |
+        ExprKind::CharLiteral(_) |
+        ExprKind::BoolLiteral(_) |
+        ExprKind::UnitLiteral |
+        ExprKind::Panic { .. } => Ok(()),
+    }
+}
@@ -0,0 +1,132 @@
+use std;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+use std::rc::Rc;
+use argparse::action::{Action, TypedAction, IArgAction, ParseResult};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Lint {
+    // a named local or parameter which is never read
+    UnusedVariables,
+    // a function which can't be reached from `main`
+    DeadCode,
+    // statements after a `return`, `panic(..)`, `unreachable()` or a call of
+    // a function returning `!`
+    UnreachableCode,
+    // the result of a `#[must_use]` function is thrown away
+    UnusedMustUse,
+    // a literal with no other constraints got the type s32 or f64
+    LiteralDefaults,
+}
+
+impl Lint {
+    pub fn from_name(name: &str) -> Option<Lint> {
+        match name {
+            "unused_variables" => Some(Lint::UnusedVariables),
+            "dead_code" => Some(Lint::DeadCode),
+            "unreachable_code" => Some(Lint::UnreachableCode),
+            "unused_must_use" => Some(Lint::UnusedMustUse),
+            "literal_defaults" => Some(Lint::LiteralDefaults),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Lint::UnusedVariables => "unused_variables",
+            Lint::DeadCode => "dead_code",
+            Lint::UnreachableCode => "unreachable_code",
+            Lint::UnusedMustUse => "unused_must_use",
+            Lint::LiteralDefaults => "literal_defaults",
+        }
+    }
+
+    fn default_level(self) -> Level {
+        match self {
+            Lint::LiteralDefaults => Level::Allow,
+            _ => Level::Warn,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl Level {
+    // the attribute which sets this level, i.e. `#[allow(...)]`
+    pub fn from_attribute(name: &str) -> Option<Level> {
+        match name {
+            "allow" => Some(Level::Allow),
+            "warn" => Some(Level::Warn),
+            "deny" => Some(Level::Deny),
+            _ => None,
+        }
+    }
+}
+
+// the argparse action behind -A, -W and -D. They all push onto one list, so
+// that the lints can be set in the order they were passed and the last wins
+pub struct SetLevel(pub Level);
+
+struct SetLevelAction<'a> {
+    level: Level,
+    cell: Rc<RefCell<&'a mut Vec<(Level, String)>>>,
+}
+
+impl TypedAction<Vec<(Level, String)>> for SetLevel {
+    fn bind<'x>(&self, cell: Rc<RefCell<&'x mut Vec<(Level, String)>>>) -> Action<'x> {
+        Action::Single(Box::new(SetLevelAction {
+            level: self.0,
+            cell: cell,
+        }))
+    }
+}
+
+impl<'a> IArgAction for SetLevelAction<'a> {
+    fn parse_arg(&self, arg: &str) -> ParseResult {
+        self.cell.borrow_mut().push((self.level, arg.to_owned()));
+        ParseResult::Parsed
+    }
+}
+
+// the level of every lint; those which were never set are at their default
+#[derive(Clone, Debug)]
+pub struct Lints {
+    levels: HashMap<Lint, Level>,
+}
+
+impl Lints {
+    pub fn new() -> Self {
+        Lints { levels: HashMap::new() }
+    }
+
+    pub fn set(&mut self, lint: Lint, level: Level) {
+        self.levels.insert(lint, level);
+    }
+
+    pub fn level(&self, lint: Lint) -> Level {
+        self.levels.get(&lint).cloned().unwrap_or(lint.default_level())
+    }
+
+    // reports `message` about `function` at the level of `lint`. Returns
+    // whether the lint is denied, in which case compilation must fail.
+    pub fn emit(&self, lint: Lint, function: &str, message: &str) -> bool {
+        let level = match self.level(lint) {
+            Level::Allow => return false,
+            Level::Warn => "warning",
+            Level::Deny => "error",
+        };
+        let _ = writeln!(std::io::stderr(),
+                         "{}: in function `{}`, {} [{}]",
+                         level,
+                         function,
+                         message,
+                         lint.name());
+        self.level(lint) == Level::Deny
+    }
+}
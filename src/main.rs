@@ -25,25 +25,53 @@ mod parse;
 mod ast;
 mod ty;
 mod mir;
+mod lint;
 use parse::Lexer;
 use ast::Ast;
+use lint::{Lint, Level, Lints, SetLevel};
+
+// compiled along with every program, unless --no-prelude is passed
+const PRELUDE: &'static str = include_str!("prelude.sva");
 
 fn main() {
-    use std::io::Read;
+    use std::io::{Read, Write};
+
+    if std::env::args().nth(1).map_or(false, |arg| arg == "fmt") {
+        return fmt();
+    }
 
     let mut name = "".to_owned();
     let mut output = None;
+    let mut print_tokens = false;
+    let mut print_ast = false;
+    let mut print_typed_ast = false;
     let mut print_mir = false;
     let mut print_llir = false;
     let mut opt = false;
+    let mut validate_mir = false;
+    let mut no_prelude = false;
+    let mut overflow_checks = None;
+    let mut warn_defaults = false;
+    let mut levels: Vec<(Level, String)> = Vec::new();
     {
-        use argparse::{ArgumentParser, Store, StoreOption, StoreTrue};
+        use argparse::{ArgumentParser, Store, StoreConst, StoreOption, StoreTrue};
 
         let mut ap = ArgumentParser::new();
         ap.set_description("The syavac compiler for the syava language.\n\
             Written in Rust.");
         ap.refer(&mut name).required().add_argument("name", Store, "The file to compile");
         ap.refer(&mut output).add_option(&["-o", "--output"], StoreOption, "The file to output to");
+        ap.refer(&mut print_tokens).add_option(&["--print-tokens"],
+                                               StoreTrue,
+                                               "Pass if you would like to print the tokens of the \
+                                                file");
+        ap.refer(&mut print_ast).add_option(&["--print-ast"],
+                                            StoreTrue,
+                                            "Pass if you would like to print the parsed AST");
+        ap.refer(&mut print_typed_ast).add_option(&["--print-typed-ast"],
+                                                  StoreTrue,
+                                                  "Pass if you would like to print the AST \
+                                                   with every expression's inferred type");
         ap.refer(&mut print_mir).add_option(&["--print-mir"],
                                             StoreTrue,
                                             "Pass if you would like to print the generated MIR");
@@ -51,14 +79,58 @@ fn main() {
                                              StoreTrue,
                                              "Pass if you would like to print the generated LLVM \
                                               IR");
+        ap.refer(&mut validate_mir).add_option(&["--validate-mir"],
+                                               StoreTrue,
+                                               "Pass if you would like to validate the generated \
+                                                MIR (always done in debug builds)");
+        ap.refer(&mut no_prelude).add_option(&["--no-prelude"],
+                                             StoreTrue,
+                                             "Pass if you would like to compile without the \
+                                              prelude (print_int, read_int, exit, ...)");
         ap.refer(&mut opt).add_option(&["--opt", "-O"],
                                       StoreTrue,
                                       "Pass if you would like to optimize the generated LLVM IR");
+        ap.refer(&mut overflow_checks)
+          .add_option(&["--overflow-checks"],
+                      StoreConst(Some(true)),
+                      "Pass if you would like arithmetic overflow to abort (the default without \
+                       -O)")
+          .add_option(&["--no-overflow-checks"],
+                      StoreConst(Some(false)),
+                      "Pass if you would like arithmetic overflow to wrap (the default with -O)");
+        ap.refer(&mut warn_defaults)
+          .add_option(&["--warn-defaults"],
+                      StoreTrue,
+                      "Pass if you would like a warning whenever a literal's type defaults to s32 \
+                       or f64 (the same as -W literal_defaults)");
+        ap.refer(&mut levels)
+          .add_option(&["-W", "--warn"],
+                      SetLevel(Level::Warn),
+                      "Report this lint as a warning")
+          .add_option(&["-A", "--allow"], SetLevel(Level::Allow), "Don't report this lint")
+          .add_option(&["-D", "--deny"],
+                      SetLevel(Level::Deny),
+                      "Report this lint as an error (the last of -W, -A and -D wins)");
 
         ap.parse_args_or_exit();
     }
 
+    let mut lints = Lints::new();
+    if warn_defaults {
+        lints.set(Lint::LiteralDefaults, Level::Warn);
+    }
+    for &(level, ref name) in &levels {
+        match Lint::from_name(name) {
+            Some(lint) => lints.set(lint, level),
+            None => {
+                let _ = writeln!(std::io::stderr(), "error: unknown lint `{}`", name);
+                std::process::exit(2);
+            }
+        }
+    }
+
     let output = output.unwrap_or(get_output_from_name(&name));
+    let overflow_checks = overflow_checks.unwrap_or(!opt);
 
     let mut file = Vec::new();
     std::fs::File::open(&name)
@@ -66,17 +138,33 @@ fn main() {
         .read_to_end(&mut file)
         .unwrap();
     let file = String::from_utf8(file).unwrap();
-    let lexer = Lexer::new(&file);
+    if print_tokens {
+        print_tokens_of(&file);
+    }
+    let prelude = if no_prelude {
+        None
+    } else {
+        Some(Lexer::new(PRELUDE))
+    };
     let tyctxt = ty::TypeContext::new();
 
-    let ast = match Ast::create(lexer, &tyctxt) {
+    let ast = match Ast::create(prelude, Lexer::new(&file), &tyctxt) {
         Ok(ast) => ast,
         Err(e) => panic!("\n{:#?}", e),
     };
-    let mir = match ast.typeck(opt) {
+    if print_ast {
+        println!("{}", ast);
+    }
+    let mut mir = match ast.typeck(&name, opt, overflow_checks, &lints, print_typed_ast) {
         Ok(mir) => mir,
         Err(e) => panic!("\n{:#?}", e),
     };
+    mir::inline(&mut mir, opt);
+    if validate_mir || cfg!(debug_assertions) {
+        if let Err(e) = mir::validate(&mir) {
+            panic!("\n{:#?}", e);
+        }
+    }
     if print_mir {
         println!("{}", mir);
     }
@@ -84,6 +172,77 @@ fn main() {
     mir.build_and_write(&output, print_llir)
 }
 
+// every token of `src`, one per line, after the line it's on
+fn print_tokens_of(src: &str) {
+    let mut lexer = Lexer::new(src);
+    loop {
+        match lexer.next_token() {
+            Ok(parse::Token::Eof) => break,
+            Ok(token) => println!("{}: {:?}", lexer.line(), token),
+            Err(e) => panic!("\n{:#?}", e),
+        }
+    }
+}
+
+// `syavac fmt [--check] files...`; each file is formatted in place, or with
+// --check, the ones which aren't formatted are listed, and the exit code is 1
+fn fmt() {
+    use std::io::{Read, Write};
+
+    let mut names: Vec<String> = Vec::new();
+    let mut check = false;
+    {
+        use argparse::{ArgumentParser, List, StoreTrue};
+
+        let mut ap = ArgumentParser::new();
+        ap.set_description("Formats syava source files.");
+        ap.refer(&mut names).required().add_argument("names", List, "The files to format");
+        ap.refer(&mut check).add_option(&["--check"],
+                                        StoreTrue,
+                                        "Pass if you would like to check whether the files are \
+                                         formatted, without changing them");
+
+        // `syavac fmt` is the name of the program, as far as argparse knows
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        args[0] = "syavac fmt".to_owned();
+        if let Err(code) = ap.parse(args, &mut std::io::stdout(), &mut std::io::stderr()) {
+            std::process::exit(code);
+        }
+    }
+    if names.is_empty() {
+        let _ = writeln!(std::io::stderr(), "error: no files to format");
+        std::process::exit(2);
+    }
+
+    let mut unformatted = false;
+    for name in &names {
+        let mut file = String::new();
+        std::fs::File::open(name)
+            .expect(name)
+            .read_to_string(&mut file)
+            .unwrap();
+        let formatted = match ast::fmt::format(&file) {
+            Ok(formatted) => formatted,
+            Err(e) => panic!("\n{}: {:#?}", name, e),
+        };
+        if formatted == file {
+            continue;
+        }
+        if check {
+            println!("{}", name);
+            unformatted = true;
+        } else {
+            std::fs::File::create(name)
+                .expect(name)
+                .write_all(formatted.as_bytes())
+                .unwrap();
+        }
+    }
+    if unformatted {
+        std::process::exit(1);
+    }
+}
+
 // TODO: take off the ".sva" of the input file
 fn get_output_from_name(name: &str) -> String {
     format!("{}.s", name)
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use ty::{self, TypeVariant, Mutability};
+use super::{Mir, Function, Lvalue, Value, ValueKind, ValueLeaf, Variable};
+
+// a reference to a stack slot of the function outliving the function
+#[derive(Debug)]
+pub enum Escape {
+    // it's returned
+    Returned {
+        referent: String,
+    },
+    // it's written through a pointer which may point outside of the function
+    Stored {
+        referent: String,
+    },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Slot {
+    Variable(u32),
+    Temporary(u32),
+    // anywhere which isn't on this function's stack: whatever a parameter or
+    // a call's result points to, a static or the heap
+    Outside,
+}
+
+impl<'t> Function<'t> {
+    // a flow-insensitive points-to analysis: for every local and temporary,
+    // the places that it may hold a reference to. Once that's known, no
+    // reference to a stack slot of this function may be returned, or written
+    // through a pointer which may point outside of the function.
+    pub fn check_escapes(&self,
+                         mir: &Mir<'t>,
+                         fn_types: &HashMap<String, ty::Function<'t>>)
+                         -> Result<(), Escape> {
+        let mut points_to = PointsTo {
+            locals: vec![Vec::new(); self.locals.len()],
+            temporaries: vec![Vec::new(); self.temporaries.len()],
+        };
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for block in &self.blocks {
+                for stmt in &block.statements {
+                    if let Some((dsts, targets)) = points_to.call_stores(&stmt.1, mir, self) {
+                        for dst in dsts {
+                            changed |= points_to.add(dst, &targets);
+                        }
+                    }
+                    let targets = points_to.value(&stmt.1, mir, self, fn_types);
+                    if targets.is_empty() {
+                        continue;
+                    }
+                    let dsts = match stmt.0 {
+                        Lvalue::Variable(var) => vec![Slot::Variable(var.0)],
+                        Lvalue::Temporary(tmp) => vec![Slot::Temporary(tmp.0)],
+                        Lvalue::Deref(ptr) => points_to.leaf(ptr),
+                        Lvalue::Return => continue,
+                    };
+                    for dst in dsts {
+                        changed |= points_to.add(dst, &targets);
+                    }
+                }
+            }
+        }
+
+        for block in &self.blocks {
+            for stmt in &block.statements {
+                if let Some((dsts, targets)) = points_to.call_stores(&stmt.1, mir, self) {
+                    if dsts.contains(&Slot::Outside) {
+                        if let Some(&slot) = targets.iter().find(|&&slot| slot != Slot::Outside) {
+                            return Err(Escape::Stored { referent: self.referent(slot) });
+                        }
+                    }
+                }
+                let targets = points_to.value(&stmt.1, mir, self, fn_types);
+                let slot = match targets.into_iter().find(|&slot| slot != Slot::Outside) {
+                    Some(slot) => slot,
+                    None => continue,
+                };
+                match stmt.0 {
+                    Lvalue::Return => {
+                        return Err(Escape::Returned { referent: self.referent(slot) });
+                    }
+                    // a pointer nothing is known about may point anywhere
+                    Lvalue::Deref(ptr) => {
+                        let dsts = points_to.leaf(ptr);
+                        if dsts.is_empty() || dsts.contains(&Slot::Outside) {
+                            return Err(Escape::Stored { referent: self.referent(slot) });
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn referent(&self, slot: Slot) -> String {
+        match slot {
+            Slot::Variable(var) => format!("`{}`", self.local_name(Variable(var))),
+            Slot::Temporary(_) => "a temporary".to_owned(),
+            Slot::Outside => unreachable!("ICE: only stack slots are referents"),
+        }
+    }
+}
+
+struct PointsTo {
+    locals: Vec<Vec<Slot>>,
+    temporaries: Vec<Vec<Slot>>,
+}
+
+impl PointsTo {
+    // what a reference stored in `slot` may point to
+    fn slot(&self, slot: Slot) -> Vec<Slot> {
+        match slot {
+            Slot::Variable(var) => self.locals[var as usize].clone(),
+            Slot::Temporary(tmp) => self.temporaries[tmp as usize].clone(),
+            Slot::Outside => vec![Slot::Outside],
+        }
+    }
+
+    // returns whether anything changed
+    fn add(&mut self, dst: Slot, targets: &[Slot]) -> bool {
+        let dst = match dst {
+            Slot::Variable(var) => &mut self.locals[var as usize],
+            Slot::Temporary(tmp) => &mut self.temporaries[tmp as usize],
+            // checked once the analysis is done
+            Slot::Outside => return false,
+        };
+        let mut changed = false;
+        for &target in targets {
+            if !dst.contains(&target) {
+                dst.push(target);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    fn leaf(&self, leaf: ValueLeaf) -> Vec<Slot> {
+        match leaf {
+            ValueLeaf::Variable(var) => self.locals[var.0 as usize].clone(),
+            ValueLeaf::Temporary(tmp) => self.temporaries[tmp.0 as usize].clone(),
+            ValueLeaf::Parameter(_) => vec![Slot::Outside],
+            ValueLeaf::Const(_) => Vec::new(),
+        }
+    }
+
+    fn leaves(&self, leaves: &[ValueLeaf]) -> Vec<Slot> {
+        let mut targets = Vec::new();
+        for &leaf in leaves {
+            for target in self.leaf(leaf) {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        targets
+    }
+
+    // `leaves`, and everything which can be reached from them by following
+    // references
+    fn reachable(&self, leaves: &[ValueLeaf]) -> Vec<Slot> {
+        let mut reached = self.leaves(leaves);
+        let mut i = 0;
+        while i < reached.len() {
+            for target in self.slot(reached[i]) {
+                if !reached.contains(&target) {
+                    reached.push(target);
+                }
+            }
+            i += 1;
+        }
+        reached
+    }
+
+    // a callee may store any reference it's passed through any `&mut` it's
+    // passed which points to something that can hold one. Returns the places
+    // it may store to, and the references it may store there. A `&mut` which
+    // isn't known to point anywhere may point outside of the function.
+    fn call_stores<'t>(&self,
+                       value: &Value<'t>,
+                       mir: &Mir<'t>,
+                       function: &Function<'t>)
+                       -> Option<(Vec<Slot>, Vec<Slot>)> {
+        let args = match value.0 {
+            ValueKind::Call { ref args, .. } => args,
+            _ => return None,
+        };
+        let mut dsts = Vec::new();
+        for &arg in args {
+            self.store_dsts(&self.leaf(arg), arg.ty(mir, function), &mut dsts);
+        }
+        if dsts.is_empty() {
+            return None;
+        }
+        // the places stored to come last, so that an error names what was
+        // stored rather than where
+        let (mut targets, stored_to): (Vec<_>, Vec<_>) =
+            self.reachable(args).into_iter().partition(|slot| !dsts.contains(slot));
+        targets.extend(stored_to);
+        Some((dsts, targets))
+    }
+
+    // the places a reference may be stored to through a value of type `ty`
+    // which points to `slots`; only `&mut`s can be stored through
+    fn store_dsts(&self, slots: &[Slot], ty: ty::Type, dsts: &mut Vec<Slot>) {
+        match *ty.0 {
+            TypeVariant::Reference(pointee, mutability) => {
+                if mutability == Mutability::Mutable && holds_reference(pointee) {
+                    if slots.is_empty() && !dsts.contains(&Slot::Outside) {
+                        dsts.push(Slot::Outside);
+                    }
+                    for &slot in slots {
+                        if !dsts.contains(&slot) {
+                            dsts.push(slot);
+                        }
+                    }
+                }
+                let mut pointees = Vec::new();
+                for &slot in slots {
+                    for target in self.slot(slot) {
+                        if !pointees.contains(&target) {
+                            pointees.push(target);
+                        }
+                    }
+                }
+                self.store_dsts(&pointees, pointee, dsts);
+            }
+            // a tuple points to whatever any of its elements do
+            TypeVariant::Tuple(elements) => {
+                for &elt in elements {
+                    self.store_dsts(slots, elt, dsts);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn value<'t>(&self,
+                 value: &Value<'t>,
+                 mir: &Mir<'t>,
+                 function: &Function<'t>,
+                 fn_types: &HashMap<String, ty::Function<'t>>)
+                 -> Vec<Slot> {
+        match value.0 {
+            ValueKind::Leaf(leaf) => self.leaf(leaf),
+            ValueKind::Ref(ValueLeaf::Variable(var), _) => vec![Slot::Variable(var.0)],
+            ValueKind::Ref(ValueLeaf::Temporary(tmp), _) => vec![Slot::Temporary(tmp.0)],
+            ValueKind::Deref(ptr) if is_reference(value, mir, function, fn_types) => {
+                let mut targets = Vec::new();
+                for slot in self.leaf(ptr) {
+                    for target in self.slot(slot) {
+                        if !targets.contains(&target) {
+                            targets.push(target);
+                        }
+                    }
+                }
+                targets
+            }
+            // a tuple holds the references of all of its elements
+            ValueKind::Tuple(ref elements) => self.leaves(elements),
+            ValueKind::Field(tuple, _) if is_reference(value, mir, function, fn_types) => {
+                self.leaf(tuple)
+            }
+            // `&mut T` to `&T`
+            ValueKind::Cast(inner, _) if is_reference(value, mir, function, fn_types) => {
+                self.leaf(inner)
+            }
+            // a returned reference may be derived from any of the arguments,
+            // or point somewhere else entirely
+            ValueKind::Call { ref args, .. } if is_reference(value, mir, function, fn_types) => {
+                let mut targets = self.leaves(args);
+                if !targets.contains(&Slot::Outside) {
+                    targets.push(Slot::Outside);
+                }
+                targets
+            }
+            ValueKind::Static(..) | ValueKind::Alloc { .. } => vec![Slot::Outside],
+            _ => Vec::new(),
+        }
+    }
+}
+
+fn is_reference<'t>(value: &Value<'t>,
+                    mir: &Mir<'t>,
+                    function: &Function<'t>,
+                    fn_types: &HashMap<String, ty::Function<'t>>)
+                    -> bool {
+    holds_reference(value.ty(mir, function, fn_types))
+}
+
+// a reference, or a tuple with one in it
+fn holds_reference(ty: ty::Type) -> bool {
+    match *ty.0 {
+        TypeVariant::Reference(..) => true,
+        TypeVariant::Tuple(elements) => elements.iter().any(|&elt| holds_reference(elt)),
+        _ => false,
+    }
+}
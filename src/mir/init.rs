@@ -0,0 +1,157 @@
+use super::{Function, Block, Statement, Terminator, Lvalue, Value, ValueLeaf, Variable,
+            START_BLOCK};
+
+impl<'t> Function<'t> {
+    // a forward dataflow analysis over the blocks: a variable is initialized at
+    // the start of a block if it's written on every path from the start block
+    // to that block. Returns the source name of a variable which may be read
+    // before it's been written.
+    pub fn check_initialization(&self) -> Result<(), String> {
+        let entry = self.entry_states(true);
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut init = entry[i].clone();
+            for stmt in &block.statements {
+                for var in stmt_reads(stmt) {
+                    if !init[var.0 as usize] {
+                        return Err(self.local_name(var));
+                    }
+                }
+                if let Lvalue::Variable(var) = stmt.0 {
+                    init[var.0 as usize] = true;
+                }
+            }
+            if let Terminator::If { cond: ValueLeaf::Variable(var), .. } = block.terminator {
+                if !init[var.0 as usize] {
+                    return Err(self.local_name(var));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the same analysis, but a variable counts as initialized if it's written
+    // on any path. Returns the source name of a write-once variable which may
+    // be written when it's already been initialized.
+    pub fn check_single_assignment(&self) -> Result<(), String> {
+        let entry = self.entry_states(false);
+        for (i, block) in self.blocks.iter().enumerate() {
+            let mut init = entry[i].clone();
+            for stmt in &block.statements {
+                if let Lvalue::Variable(var) = stmt.0 {
+                    if self.write_once[var.0 as usize] && init[var.0 as usize] {
+                        return Err(self.local_name(var));
+                    }
+                    init[var.0 as usize] = true;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // the source names of the variables which are never read; those starting
+    // with `_` are left out
+    pub fn unread_variables(&self) -> Vec<String> {
+        let mut read = vec![false; self.locals.len()];
+        for block in &self.blocks {
+            for stmt in &block.statements {
+                for var in stmt_reads(stmt) {
+                    read[var.0 as usize] = true;
+                }
+            }
+            if let Terminator::If { cond: ValueLeaf::Variable(var), .. } = block.terminator {
+                read[var.0 as usize] = true;
+            }
+        }
+        let mut unread = Vec::new();
+        for (var, &read) in read.iter().enumerate() {
+            if let (false, &Some(ref name)) = (read, &self.local_names[var]) {
+                if !name.starts_with('_') {
+                    unread.push(name.clone());
+                }
+            }
+        }
+        unread
+    }
+
+    // which variables are initialized at the start of each block: those
+    // written on every path to it if `definitely`, and on any path otherwise
+    fn entry_states(&self, definitely: bool) -> Vec<Vec<bool>> {
+        let mut preds = vec![Vec::new(); self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            for succ in block.terminator.successors() {
+                preds[succ.0].push(i);
+            }
+        }
+
+        // when `definitely`, everything is initialized at the start of a
+        // block until proven otherwise; unreachable blocks stay that way
+        let mut entry = vec![vec![definitely; self.locals.len()]; self.blocks.len()];
+        entry[START_BLOCK.0] = vec![false; self.locals.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for blk in 0..self.blocks.len() {
+                if blk == START_BLOCK.0 {
+                    continue;
+                }
+                let mut init = vec![definitely; self.locals.len()];
+                for &pred in &preds[blk] {
+                    let out = self.exit_state(pred, entry[pred].clone());
+                    for (var, &pred_init) in init.iter_mut().zip(&out) {
+                        *var = if definitely {
+                            *var && pred_init
+                        } else {
+                            *var || pred_init
+                        };
+                    }
+                }
+                if init != entry[blk] {
+                    entry[blk] = init;
+                    changed = true;
+                }
+            }
+        }
+        entry
+    }
+
+    fn exit_state(&self, blk: usize, mut init: Vec<bool>) -> Vec<bool> {
+        for stmt in &self.blocks[blk].statements {
+            if let Lvalue::Variable(var) = stmt.0 {
+                init[var.0 as usize] = true;
+            }
+        }
+        init
+    }
+}
+
+impl<'t> Terminator<'t> {
+    fn successors(&self) -> Vec<Block> {
+        match *self {
+            Terminator::Goto(ref blk) => vec![Block(blk.0)],
+            Terminator::If { ref then_blk, ref else_blk, .. } => {
+                vec![Block(then_blk.0), Block(else_blk.0)]
+            }
+            Terminator::Return | Terminator::Panic(_) | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+fn stmt_reads<'t>(stmt: &Statement<'t>) -> Vec<Variable> {
+    let mut reads = value_reads(&stmt.1);
+    if let Lvalue::Deref(ValueLeaf::Variable(ptr)) = stmt.0 {
+        reads.push(ptr);
+    }
+    reads
+}
+
+// taking a reference to a variable counts as reading it
+fn value_reads<'t>(value: &Value<'t>) -> Vec<Variable> {
+    let mut reads = Vec::new();
+    value.map_leaves(|leaf| {
+        if let ValueLeaf::Variable(var) = leaf {
+            reads.push(var);
+        }
+        leaf
+    });
+    reads
+}
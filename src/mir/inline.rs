@@ -0,0 +1,178 @@
+use std;
+use std::collections::HashMap;
+use super::{Mir, Function, Block, BlockData, Statement, Terminator, Lvalue, Value, ValueKind,
+            ValueLeaf, Variable, Temporary, START_BLOCK};
+
+// functions bigger than this (in statements) are only inlined when they're
+// marked `#[inline]`
+const INLINE_THRESHOLD: usize = 16;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Inline {
+    Default,
+    // #[inline] or #[inline(always)]
+    Always,
+    // #[inline(never)]
+    Never,
+}
+
+// without `opt`, only the functions marked `#[inline]` are inlined
+pub fn inline<'t>(mir: &mut Mir<'t>, opt: bool) {
+    let mut names = mir.functions.keys().cloned().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        // the caller is taken out of the map while we're working on it, so
+        // directly recursive calls are never inlined
+        let mut caller = mir.functions.remove(&name).unwrap();
+        inline_calls(&mut caller, &mir.functions, opt);
+        mir.functions.insert(name, caller);
+    }
+}
+
+impl<'t> Function<'t> {
+    fn size(&self) -> usize {
+        self.blocks.iter().map(|blk| blk.statements.len() + 1).sum()
+    }
+
+    fn should_inline(&self, opt: bool) -> bool {
+        match self.inline {
+            Inline::Always => true,
+            Inline::Never => false,
+            Inline::Default => opt && self.size() <= INLINE_THRESHOLD,
+        }
+    }
+}
+
+fn inline_calls<'t>(caller: &mut Function<'t>,
+                    callees: &HashMap<String, Function<'t>>,
+                    opt: bool) {
+    // only the caller's own blocks are scanned; the spliced-in callee blocks
+    // aren't, so mutually recursive functions are only unrolled once
+    let mut worklist = (0..caller.blocks.len()).collect::<Vec<_>>();
+    while let Some(blk) = worklist.pop() {
+        let call = caller.blocks[blk].statements.iter().position(|stmt| {
+            match (stmt.1).0 {
+                ValueKind::Call { ref callee, .. } => {
+                    callees.get(callee).map_or(false, |f| f.should_inline(opt))
+                }
+                _ => false,
+            }
+        });
+        if let Some(idx) = call {
+            worklist.push(splice(caller, blk, idx, callees));
+        }
+    }
+}
+
+// splits `blk` after the call at `idx`, and puts the callee's blocks in
+// between the two halves. Returns the block containing the rest of `blk`.
+fn splice<'t>(caller: &mut Function<'t>,
+              blk: usize,
+              idx: usize,
+              callees: &HashMap<String, Function<'t>>)
+              -> usize {
+    let (dst, callee, args, rest) = {
+        let block = &mut caller.blocks[blk];
+        let rest = block.statements.split_off(idx + 1);
+        match block.statements.pop() {
+            Some(Statement(dst, Value(ValueKind::Call { callee, args }))) => {
+                (dst, callee, args, rest)
+            }
+            stmt => panic!("ICE: attempted to inline a non-call: {:?}", stmt),
+        }
+    };
+    let callee = &callees[&callee];
+
+    let remap = Remap {
+        locals: caller.locals.len() as u32,
+        temporaries: caller.temporaries.len() as u32,
+        blocks: caller.blocks.len(),
+        args: &args,
+        dst: dst,
+        cont: caller.blocks.len() + callee.blocks.len(),
+    };
+    caller.locals.extend(callee.locals.iter().cloned());
+    caller.local_names.extend(callee.local_names.iter().cloned());
+    caller.write_once.extend(callee.write_once.iter().cloned());
+    caller.temporaries.extend(callee.temporaries.iter().cloned());
+    for data in &callee.blocks {
+        caller.blocks.push(BlockData {
+            expr: remap.lvalue(data.expr),
+            statements: data.statements
+                            .iter()
+                            .map(|stmt| Statement(remap.lvalue(stmt.0), remap.value(&stmt.1)))
+                            .collect(),
+            terminator: remap.terminator(&data.terminator),
+        });
+    }
+
+    let (expr, term) = {
+        let block = &mut caller.blocks[blk];
+        let entry = Terminator::Goto(remap.block(&START_BLOCK));
+        (block.expr, std::mem::replace(&mut block.terminator, entry))
+    };
+    caller.blocks.push(BlockData {
+        expr: expr,
+        statements: rest,
+        terminator: term,
+    });
+    remap.cont
+}
+
+struct Remap<'a, 't: 'a> {
+    locals: u32,
+    temporaries: u32,
+    blocks: usize,
+    // the callee's parameters are replaced by the arguments of the call
+    args: &'a [ValueLeaf<'t>],
+    // ...its return slot by the destination of the call
+    dst: Lvalue<'t>,
+    // ...and its return by a jump to the rest of the caller's block
+    cont: usize,
+}
+
+impl<'a, 't> Remap<'a, 't> {
+    fn leaf(&self, leaf: ValueLeaf<'t>) -> ValueLeaf<'t> {
+        match leaf {
+            ValueLeaf::Const(_) => leaf,
+            ValueLeaf::Parameter(par) => self.args[par.0 as usize],
+            ValueLeaf::Variable(var) => ValueLeaf::Variable(Variable(var.0 + self.locals)),
+            ValueLeaf::Temporary(tmp) => {
+                ValueLeaf::Temporary(Temporary(tmp.0 + self.temporaries))
+            }
+        }
+    }
+
+    fn lvalue(&self, lvalue: Lvalue<'t>) -> Lvalue<'t> {
+        match lvalue {
+            Lvalue::Variable(var) => Lvalue::Variable(Variable(var.0 + self.locals)),
+            Lvalue::Temporary(tmp) => Lvalue::Temporary(Temporary(tmp.0 + self.temporaries)),
+            Lvalue::Deref(ptr) => Lvalue::Deref(self.leaf(ptr)),
+            Lvalue::Return => self.dst,
+        }
+    }
+
+    fn value(&self, value: &Value<'t>) -> Value<'t> {
+        value.map_leaves(|leaf| self.leaf(leaf))
+    }
+
+    fn block(&self, blk: &Block) -> Block {
+        Block(blk.0 + self.blocks)
+    }
+
+    fn terminator(&self, term: &Terminator<'t>) -> Terminator<'t> {
+        match *term {
+            Terminator::Goto(ref blk) => Terminator::Goto(self.block(blk)),
+            Terminator::If { cond, ref then_blk, ref else_blk } => {
+                Terminator::If {
+                    cond: self.leaf(cond),
+                    then_blk: self.block(then_blk),
+                    else_blk: self.block(else_blk),
+                }
+            }
+            Terminator::Return => Terminator::Goto(Block(self.cont)),
+            Terminator::Panic(ref message) => Terminator::Panic(message.clone()),
+            Terminator::Unreachable => Terminator::Unreachable,
+        }
+    }
+}
@@ -29,6 +29,19 @@ impl Value {
         unsafe { Value(LLVMConstInt(ty.0, value, false as LLVMBool)) }
     }
 
+    // `value` is sign extended to the width of `ty`
+    pub fn const_sint(ty: Type, value: u64) -> Value {
+        unsafe { Value(LLVMConstInt(ty.0, value, true as LLVMBool)) }
+    }
+
+    pub fn const_real(ty: Type, value: f64) -> Value {
+        unsafe { Value(LLVMConstReal(ty.0, value)) }
+    }
+
+    pub fn const_all_ones(ty: Type) -> Value {
+        unsafe { Value(LLVMConstAllOnes(ty.0)) }
+    }
+
     pub fn const_bool(value: bool) -> Value {
         unsafe { Value(LLVMConstInt(LLVMInt1Type(), value as u64, false as LLVMBool)) }
     }
@@ -41,10 +54,23 @@ impl Value {
         }
     }
 
+    pub fn undef(ty: Type) -> Value {
+        unsafe { Value(LLVMGetUndef(ty.0)) }
+    }
+
     pub fn get_param(func: Value, number: u32) -> Value {
         unsafe { Value(LLVMGetParam(func.0, number)) }
     }
 
+    // `self` must be a phi
+    pub fn add_incoming(self, value: Value, block: BasicBlock) {
+        unsafe {
+            let mut values = [value.0];
+            let mut blocks = [block.0];
+            LLVMAddIncoming(self.0, values.as_mut_ptr(), blocks.as_mut_ptr(), 1);
+        }
+    }
+
     fn llvm_slice(value_slice: &[Value]) -> &[LLVMValueRef] {
         #[allow(dead_code)]
         unsafe fn size_of_value_is_size_of_value_ref() {
@@ -110,6 +136,62 @@ impl Builder {
         }
     }
 
+    pub fn build_phi(&self, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildPhi(self.0, ty.0, cstr!(""))) }
+    }
+
+    // `ptr + index`, in elements
+    pub fn build_gep(&self, ptr: Value, index: Value) -> Value {
+        unsafe {
+            let mut indices = [index.0];
+            Value(LLVMBuildGEP(self.0, ptr.0, indices.as_mut_ptr(), 1, cstr!("")))
+        }
+    }
+
+    pub fn build_pointer_cast(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildPointerCast(self.0, value.0, ty.0, cstr!(""))) }
+    }
+
+    pub fn build_ptr_to_int(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildPtrToInt(self.0, value.0, ty.0, cstr!(""))) }
+    }
+
+    pub fn build_int_to_ptr(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildIntToPtr(self.0, value.0, ty.0, cstr!(""))) }
+    }
+
+    pub fn build_is_null(&self, value: Value) -> Value {
+        unsafe { Value(LLVMBuildIsNull(self.0, value.0, cstr!(""))) }
+    }
+
+    // `malloc(bytes)`, as an `i8*`
+    pub fn build_malloc(&self, target_data: &TargetData, bytes: Value) -> Value {
+        unsafe {
+            let mut args = [LLVMIntPtrType(target_data.0)];
+            let malloc_ty = LLVMFunctionType(LLVMPointerType(LLVMInt8Type(), 0),
+                                             args.as_mut_ptr(),
+                                             1,
+                                             false as LLVMBool);
+            let malloc = self.get_or_add_function("malloc", Type(malloc_ty));
+            self.build_call(malloc, &[bytes])
+        }
+    }
+
+    pub fn build_free(&self, ptr: Value) {
+        unsafe {
+            let i8_ptr = LLVMPointerType(LLVMInt8Type(), 0);
+            let mut args = [i8_ptr];
+            let free_ty = LLVMFunctionType(LLVMVoidType(), args.as_mut_ptr(), 1, false as LLVMBool);
+            let free = self.get_or_add_function("free", Type(free_ty));
+            let ptr = self.build_pointer_cast(ptr, Type(i8_ptr));
+            self.build_call(free, &[ptr]);
+        }
+    }
+
+    pub fn insert_block(&self) -> BasicBlock {
+        unsafe { BasicBlock(LLVMGetInsertBlock(self.0)) }
+    }
+
     pub fn build_load(&self, ptr: Value) -> Value {
         unsafe { Value(LLVMBuildLoad(self.0, ptr.0, cstr!(""))) }
     }
@@ -123,6 +205,9 @@ impl Builder {
     pub fn build_neg(&self, inner: Value) -> Value {
         unsafe { Value(LLVMBuildNeg(self.0, inner.0, cstr!(""))) }
     }
+    pub fn build_fneg(&self, inner: Value) -> Value {
+        unsafe { Value(LLVMBuildFNeg(self.0, inner.0, cstr!(""))) }
+    }
 
     pub fn build_not(&self, inner: Value) -> Value {
         unsafe { Value(LLVMBuildNot(self.0, inner.0, cstr!(""))) }
@@ -137,6 +222,22 @@ impl Builder {
     pub fn build_mul(&self, lhs: Value, rhs: Value) -> Value {
         unsafe { Value(LLVMBuildMul(self.0, lhs.0, rhs.0, cstr!(""))) }
     }
+    pub fn build_fadd(&self, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFAdd(self.0, lhs.0, rhs.0, cstr!(""))) }
+    }
+    pub fn build_fsub(&self, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFSub(self.0, lhs.0, rhs.0, cstr!(""))) }
+    }
+    pub fn build_fmul(&self, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFMul(self.0, lhs.0, rhs.0, cstr!(""))) }
+    }
+    pub fn build_fdiv(&self, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFDiv(self.0, lhs.0, rhs.0, cstr!(""))) }
+    }
+    pub fn build_frem(&self, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFRem(self.0, lhs.0, rhs.0, cstr!(""))) }
+    }
+
     pub fn build_udiv(&self, lhs: Value, rhs: Value) -> Value {
         unsafe { Value(LLVMBuildUDiv(self.0, lhs.0, rhs.0, cstr!(""))) }
     }
@@ -173,6 +274,125 @@ impl Builder {
     pub fn build_icmp(&self, pred: LLVMIntPredicate, lhs: Value, rhs: Value) -> Value {
         unsafe { Value(LLVMBuildICmp(self.0, pred, lhs.0, rhs.0, cstr!(""))) }
     }
+    pub fn build_fcmp(&self, pred: LLVMRealPredicate, lhs: Value, rhs: Value) -> Value {
+        unsafe { Value(LLVMBuildFCmp(self.0, pred, lhs.0, rhs.0, cstr!(""))) }
+    }
+
+    pub fn build_trunc(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildTrunc(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_sext(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildSExt(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_zext(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildZExt(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_bitcast(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildBitCast(self.0, value.0, ty.0, cstr!(""))) }
+    }
+
+    pub fn build_sitofp(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildSIToFP(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_uitofp(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildUIToFP(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_fptosi(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildFPToSI(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_fptoui(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildFPToUI(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_fpext(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildFPExt(self.0, value.0, ty.0, cstr!(""))) }
+    }
+    pub fn build_fptrunc(&self, value: Value, ty: Type) -> Value {
+        unsafe { Value(LLVMBuildFPTrunc(self.0, value.0, ty.0, cstr!(""))) }
+    }
+
+    pub fn build_select(&self, cond: Value, then: Value, else_: Value) -> Value {
+        unsafe { Value(LLVMBuildSelect(self.0, cond.0, then.0, else_.0, cstr!(""))) }
+    }
+
+    pub fn build_extract_value(&self, agg: Value, index: u32) -> Value {
+        unsafe { Value(LLVMBuildExtractValue(self.0, agg.0, index, cstr!(""))) }
+    }
+
+    pub fn build_insert_value(&self, agg: Value, elt: Value, index: u32) -> Value {
+        unsafe { Value(LLVMBuildInsertValue(self.0, agg.0, elt.0, index, cstr!(""))) }
+    }
+
+    pub fn build_unreachable(&self) {
+        unsafe {
+            LLVMBuildUnreachable(self.0);
+        }
+    }
+
+    // returns (result, overflowed) of `llvm.<op>.with.overflow`, where op is
+    // one of sadd, uadd, ssub, usub, smul or umul
+    pub fn build_overflow_op(&self, op: &str, lhs: Value, rhs: Value) -> (Value, Value) {
+        unsafe {
+            let int_ty = LLVMTypeOf(lhs.0);
+            let name = format!("llvm.{}.with.overflow.i{}", op, LLVMGetIntTypeWidth(int_ty));
+            let mut fields = [int_ty, LLVMInt1Type()];
+            let ret_ty = LLVMStructType(fields.as_mut_ptr(), 2, false as LLVMBool);
+            let mut args = [int_ty, int_ty];
+            let fn_ty = LLVMFunctionType(ret_ty, args.as_mut_ptr(), 2, false as LLVMBool);
+            let intrinsic = self.get_or_add_function(&name, Type(fn_ty));
+            let res = self.build_call(intrinsic, &[lhs, rhs]);
+            (self.build_extract_value(res, 0), self.build_extract_value(res, 1))
+        }
+    }
+
+    // writes `message` to stderr and aborts
+    pub fn build_panic(&self, target_data: &TargetData, message: &str) {
+        unsafe {
+            let size_ty = LLVMIntPtrType(target_data.0);
+            let mut write_args = [LLVMInt32Type(), LLVMPointerType(LLVMInt8Type(), 0), size_ty];
+            let write_ty = LLVMFunctionType(size_ty,
+                                            write_args.as_mut_ptr(),
+                                            3,
+                                            false as LLVMBool);
+            let write = self.get_or_add_function("write", Type(write_ty));
+            let abort_ty = LLVMFunctionType(LLVMVoidType(),
+                                            std::ptr::null_mut(),
+                                            0,
+                                            false as LLVMBool);
+            let abort = self.get_or_add_function("abort", Type(abort_ty));
+
+            let message = format!("{}\n", message);
+            let len = message.len() as u64;
+            let message = Value(LLVMBuildGlobalStringPtr(self.0,
+                                                         CString::new(message).unwrap().as_ptr(),
+                                                         cstr!("panic_msg")));
+            self.build_call(write,
+                            &[Value::const_int(Type(LLVMInt32Type()), 2),
+                              message,
+                              Value::const_int(Type(size_ty), len)]);
+            self.build_call(abort, &[]);
+            self.build_unreachable();
+        }
+    }
+
+    // the global `name` in the module we're currently building in
+    pub fn get_global(&self, name: &str) -> Value {
+        unsafe {
+            let module = LLVMGetGlobalParent(LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.0)));
+            let name = CString::new(name.to_owned()).unwrap();
+            let global = LLVMGetNamedGlobal(module, name.as_ptr());
+            assert!(!global.is_null(), "ICE: no global {:?}", name);
+            Value(global)
+        }
+    }
+
+    // declares `name` in the module we're currently building in, unless it's
+    // already there
+    fn get_or_add_function(&self, name: &str, ty: Type) -> Value {
+        unsafe {
+            let module = LLVMGetGlobalParent(LLVMGetBasicBlockParent(LLVMGetInsertBlock(self.0)));
+            get_or_add_function(module, name, ty)
+        }
+    }
 }
 
 impl std::ops::Drop for Builder {
@@ -190,8 +410,16 @@ impl BasicBlock {
                                             CString::new(format!("bb{}", num)).unwrap().as_ptr()))
         }
     }
+
+    // for blocks which don't correspond to a MIR block
+    pub fn append_named(func: Value, name: &str) -> Self {
+        unsafe {
+            BasicBlock(LLVMAppendBasicBlock(func.0, CString::new(name.to_owned()).unwrap().as_ptr()))
+        }
+    }
 }
 
+#[derive(Copy, Clone)]
 pub struct Type(LLVMTypeRef);
 
 #[derive(Copy, Clone, Debug)]
@@ -260,6 +488,11 @@ impl TargetData {
     pub fn from_target_machine(machine: &TargetMachine) -> Self {
         unsafe { TargetData(LLVMGetTargetMachineData(machine.0)) }
     }
+
+    // in bits
+    pub fn pointer_size(&self) -> u32 {
+        unsafe { LLVMPointerSize(self.0) * 8 }
+    }
 }
 
 pub struct Module(LLVMModuleRef);
@@ -268,6 +501,11 @@ impl Module {
         unsafe { Module(LLVMModuleCreateWithName(cstr!(""))) }
     }
 
+    // declares `name`, unless it's already there
+    pub fn get_or_add_function(&self, name: &str, ty: Type) -> Value {
+        unsafe { get_or_add_function(self.0, name, ty) }
+    }
+
     pub fn add_function(&self, name: &str, ty: Type) -> Value {
         unsafe {
             Value(LLVMAddFunction(self.0,
@@ -276,6 +514,18 @@ impl Module {
         }
     }
 
+    // a global initialized to the constant `init`
+    pub fn add_global(&self, name: &str, init: Value, constant: bool) -> Value {
+        unsafe {
+            let global = LLVMAddGlobal(self.0,
+                                       LLVMTypeOf(init.0),
+                                       CString::new(name.to_owned()).unwrap().as_ptr());
+            LLVMSetInitializer(global, init.0);
+            LLVMSetGlobalConstant(global, constant as LLVMBool);
+            Value(global)
+        }
+    }
+
     pub fn dump(&self) {
         unsafe { LLVMDumpModule(self.0) }
     }
@@ -332,6 +582,21 @@ impl std::ops::Drop for FnOptimizer {
 }
 
 
+// an existing declaration of `name` may have another type, e.g. a user's
+// extern of a function the runtime calls, so it's cast to `ty`
+unsafe fn get_or_add_function(module: LLVMModuleRef, name: &str, ty: Type) -> Value {
+    let name = CString::new(name.to_owned()).unwrap();
+    let func = LLVMGetNamedFunction(module, name.as_ptr());
+    let ptr_ty = LLVMPointerType(ty.0, 0);
+    if func.is_null() {
+        Value(LLVMAddFunction(module, name.as_ptr(), ty.0))
+    } else if LLVMTypeOf(func) != ptr_ty {
+        Value(LLVMConstBitCast(func, ptr_ty))
+    } else {
+        Value(func)
+    }
+}
+
 pub fn size_of_type(target_data: &TargetData, ty: ty::Type) -> u64 {
     unsafe {
         if let ty::TypeVariant::Diverging = *ty.0 {
@@ -342,16 +607,35 @@ pub fn size_of_type(target_data: &TargetData, ty: ty::Type) -> u64 {
     }
 }
 
+// in bytes, including padding; the distance between two elements of an array
+pub fn alloc_size_of_type(target_data: &TargetData, ty: ty::Type) -> u64 {
+    unsafe { LLVMABISizeOfType(target_data.0, get_type(target_data, ty).0) }
+}
+
 pub fn get_type(target_data: &TargetData, ty: ty::Type) -> Type {
     use ty::TypeVariant;
     unsafe {
         Type(match *ty.0 {
-            TypeVariant::SInt(ref size) | TypeVariant::UInt(ref size) => LLVMIntType(size.size()),
+            TypeVariant::SInt(ref size) | TypeVariant::UInt(ref size) => {
+                LLVMIntType(size.size(target_data.pointer_size()))
+            }
+            TypeVariant::Float(ty::Float::F32) => LLVMFloatType(),
+            TypeVariant::Float(ty::Float::F64) => LLVMDoubleType(),
+            TypeVariant::Char => LLVMInt32Type(),
             TypeVariant::Bool => LLVMInt1Type(),
-            TypeVariant::Unit => LLVMStructType(std::ptr::null_mut(), 0, false as LLVMBool),
-            TypeVariant::Reference(inner) => LLVMPointerType(get_type(target_data, inner).0, 0),
-            TypeVariant::Diverging => panic!("ICE: Attempted to get the LLVM type of Diverging"),
-            TypeVariant::Infer(_) | TypeVariant::InferInt(_) => {
+            // `!` has no values, so it's only ever the type of a value
+            // which is never used
+            TypeVariant::Unit |
+            TypeVariant::Diverging => LLVMStructType(std::ptr::null_mut(), 0, false as LLVMBool),
+            TypeVariant::Tuple(elements) => {
+                let mut elements = elements.iter()
+                                           .map(|&elt| get_type(target_data, elt).0)
+                                           .collect::<Vec<_>>();
+                LLVMStructType(elements.as_mut_ptr(), elements.len() as u32, false as LLVMBool)
+            }
+            TypeVariant::Reference(inner, _) |
+            TypeVariant::RawPointer(inner, _) => LLVMPointerType(get_type(target_data, inner).0, 0),
+            TypeVariant::Infer(_) | TypeVariant::InferInt(_) | TypeVariant::InferFloat(_) => {
                 panic!("ICE: Attempted to get the LLVM type of an \
                     inference variable: {:?}",
                        ty)
@@ -1,8 +1,16 @@
 use std;
 use std::collections::HashMap;
-use ty::{self, Type, TypeVariant, TypeContext};
+use ty::{self, Type, TypeVariant, TypeContext, Mutability};
 
 mod llvm;
+mod validate;
+mod inline;
+mod init;
+mod escape;
+
+pub use self::validate::validate;
+pub use self::inline::{inline, Inline};
+pub use self::escape::Escape;
 
 const START_BLOCK: Block = Block(0);
 const END_BLOCK: Block = Block(1);
@@ -10,9 +18,18 @@ const END_BLOCK: Block = Block(1);
 #[derive(Debug)]
 pub struct Function<'t> {
     ty: ty::Function<'t>,
+    inline: Inline,
     temporaries: Vec<Type<'t>>,
     locals: Vec<Type<'t>>,
+    // the source names of the locals; parameters don't have one
+    local_names: Vec<Option<String>>,
+    // the locals declared `let x;` without `mut`, which may only be assigned
+    // once
+    write_once: Vec<bool>,
     blocks: Vec<BlockData<'t>>,
+    // the file named in the function's panics, if it isn't the one being
+    // compiled
+    file: Option<&'static str>,
 }
 #[derive(Copy, Clone, Debug)]
 pub struct Variable(u32);
@@ -22,12 +39,16 @@ struct Temporary(u32);
 struct Parameter(u32);
 
 impl<'t> Function<'t> {
-    pub fn new(ty: ty::Function<'t>) -> Self {
+    pub fn new(ty: ty::Function<'t>, inline: Inline, param_names: Vec<String>) -> Self {
         let mut ret = Function {
             ty: ty,
+            inline: inline,
             temporaries: Vec::new(),
             locals: Vec::new(),
+            local_names: Vec::new(),
+            write_once: Vec::new(),
             blocks: Vec::new(),
+            file: None,
         };
         assert_eq!(START_BLOCK,
                    ret.new_block(Lvalue::Return, Terminator::Goto(END_BLOCK)));
@@ -35,8 +56,8 @@ impl<'t> Function<'t> {
                    ret.new_block(Lvalue::Return, Terminator::Return));
         let input_types = ret.ty.input().to_owned();
         {
-            for ty in &input_types {
-                ret.new_local(*ty);
+            for (ty, name) in input_types.iter().zip(param_names) {
+                ret.new_local(*ty, Some(name));
             }
             let blk = ret.get_block(&mut START_BLOCK);
             for i in 0..input_types.len() as u32 {
@@ -60,15 +81,32 @@ impl<'t> Function<'t> {
         self.temporaries.push(ty);
         Temporary(self.temporaries.len() as u32 - 1)
     }
-    pub fn new_local(&mut self, ty: Type<'t>) -> Variable {
+    pub fn set_file(&mut self, file: &'static str) {
+        self.file = Some(file);
+    }
+    pub fn new_local(&mut self, ty: Type<'t>, name: Option<String>) -> Variable {
         self.locals.push(ty);
+        self.local_names.push(name);
+        self.write_once.push(false);
         Variable(self.locals.len() as u32 - 1)
     }
+    pub fn new_write_once_local(&mut self, ty: Type<'t>, name: String) -> Variable {
+        let var = self.new_local(ty, Some(name));
+        self.write_once[var.0 as usize] = true;
+        var
+    }
     pub fn get_param(&mut self, n: u32) -> Variable {
         assert!(n < self.ty.input().len() as u32);
         Variable(n)
     }
 
+    fn local_name(&self, var: Variable) -> String {
+        match self.local_names[var.0 as usize] {
+            Some(ref name) => name.clone(),
+            None => format!("var{}", var.0),
+        }
+    }
+
     fn get_block(&mut self, blk: &mut Block) -> &mut BlockData<'t> {
         &mut self.blocks[blk.0 as usize]
     }
@@ -177,6 +215,43 @@ impl<'t> LlFunction<'t> {
     fn get_block(&self, blk: &Block) -> llvm::BasicBlock {
         self.blocks[blk.0]
     }
+
+    // op is one of "add", "sub" or "mul"
+    fn build_saturating(&self, mir: &Mir<'t>, op: &str, ty: Type<'t>,
+                        lhs: llvm::Value, rhs: llvm::Value) -> llvm::Value {
+        use llvm_sys::LLVMIntPredicate::*;
+        let llty = llvm::get_type(&mir.target_data, ty);
+        let signed = match *ty.0 {
+            TypeVariant::SInt(_) => true,
+            TypeVariant::UInt(_) => false,
+            _ => panic!("ICE: {} can't be used in saturating arithmetic", ty),
+        };
+        let intrinsic = format!("{}{}", if signed { "s" } else { "u" }, op);
+        let (res, overflowed) = self.builder.build_overflow_op(&intrinsic, lhs, rhs);
+        let bound = if signed {
+            // these fold to constants
+            let max = self.builder.build_lshr(llvm::Value::const_all_ones(llty),
+                                              llvm::Value::const_int(llty, 1));
+            let min = self.builder.build_not(max);
+            let zero = llvm::Value::const_int(llty, 0);
+            // which way did we overflow?
+            let towards_min = match op {
+                "add" => self.builder.build_icmp(LLVMIntSLT, rhs, zero),
+                "sub" => self.builder.build_icmp(LLVMIntSGT, rhs, zero),
+                "mul" => {
+                    let signs = self.builder.build_xor(lhs, rhs);
+                    self.builder.build_icmp(LLVMIntSLT, signs, zero)
+                }
+                op => panic!("ICE: unknown saturating op: {}", op),
+            };
+            self.builder.build_select(towards_min, min, max)
+        } else if op == "sub" {
+            llvm::Value::const_int(llty, 0)
+        } else {
+            llvm::Value::const_all_ones(llty)
+        };
+        self.builder.build_select(overflowed, bound, res)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -185,6 +260,10 @@ enum Const<'t> {
         value: u64,
         ty: Type<'t>,
     },
+    Float {
+        value: f64,
+        ty: Type<'t>,
+    },
     Bool(bool),
     Unit,
 }
@@ -196,8 +275,20 @@ impl<'t> Const<'t> {
                 value,
                 ty,
             } => {
-                llvm::Value::const_int(
-                    llvm::get_type(&mir.target_data, ty), value)
+                let llty = llvm::get_type(&mir.target_data, ty);
+                // signed constants are stored sign extended, so that they
+                // work for types wider than 64 bits
+                if let TypeVariant::SInt(_) = *ty.0 {
+                    llvm::Value::const_sint(llty, value)
+                } else {
+                    llvm::Value::const_int(llty, value)
+                }
+            }
+            Const::Float {
+                value,
+                ty,
+            } => {
+                llvm::Value::const_real(llvm::get_type(&mir.target_data, ty), value)
             }
             Const::Bool(value) => {
                 llvm::Value::const_bool(value)
@@ -214,12 +305,28 @@ impl<'t> Const<'t> {
                 ty,
                 ..
             } => ty,
+            Const::Float {
+                ty,
+                ..
+            } => ty,
             Const::Bool(_) => Type::bool(mir.ctxt),
             Const::Unit => Type::unit(mir.ctxt),
         }
     }
 }
 
+#[derive(Debug)]
+struct Static<'t> {
+    mutability: Mutability,
+    init: Const<'t>,
+}
+
+impl<'t> Static<'t> {
+    fn ty(&self, mir: &Mir<'t>) -> Type<'t> {
+        self.init.ty(mir)
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 enum ValueLeaf<'t> {
     Const(Const<'t>),
@@ -266,7 +373,7 @@ enum ValueKind<'t> {
     Neg(ValueLeaf<'t>),
     Not(ValueLeaf<'t>),
 
-    Ref(ValueLeaf<'t>),
+    Ref(ValueLeaf<'t>, Mutability),
     Deref(ValueLeaf<'t>),
 
     // -- binops --
@@ -281,6 +388,14 @@ enum ValueKind<'t> {
     Shl(ValueLeaf<'t>, ValueLeaf<'t>),
     Shr(ValueLeaf<'t>, ValueLeaf<'t>),
 
+    // -- overflow --
+    AddOverflows(ValueLeaf<'t>, ValueLeaf<'t>),
+    SubOverflows(ValueLeaf<'t>, ValueLeaf<'t>),
+    MulOverflows(ValueLeaf<'t>, ValueLeaf<'t>),
+    SaturatingAdd(ValueLeaf<'t>, ValueLeaf<'t>),
+    SaturatingSub(ValueLeaf<'t>, ValueLeaf<'t>),
+    SaturatingMul(ValueLeaf<'t>, ValueLeaf<'t>),
+
     // -- comparison --
     Eq(ValueLeaf<'t>, ValueLeaf<'t>),
     Neq(ValueLeaf<'t>, ValueLeaf<'t>),
@@ -290,6 +405,22 @@ enum ValueKind<'t> {
     Gte(ValueLeaf<'t>, ValueLeaf<'t>),
 
     // -- other --
+    Cast(ValueLeaf<'t>, Type<'t>),
+    // the address of a static
+    Static(String, Mutability),
+    // `count` copies of `value` in a fresh heap allocation, and its address;
+    // panics with `message` if the allocator fails
+    Alloc {
+        count: ValueLeaf<'t>,
+        value: ValueLeaf<'t>,
+        message: String,
+    },
+    Free(ValueLeaf<'t>),
+    // a raw pointer plus an isize, in elements
+    Offset(ValueLeaf<'t>, ValueLeaf<'t>),
+    Tuple(Vec<ValueLeaf<'t>>),
+    // element `n` of a tuple
+    Field(ValueLeaf<'t>, u32),
     Call {
         callee: String,
         args: Vec<ValueLeaf<'t>>,
@@ -303,6 +434,15 @@ pub struct Value<'t>(ValueKind<'t>);
 impl<'t> Value<'t> {
     // -- leaves --
     #[inline(always)]
+    pub fn const_float(value: f64, ty: Type<'t>) -> Self {
+        Value::leaf(
+            ValueLeaf::Const(Const::Float {
+                value: value,
+                ty: ty,
+            }
+            ))
+    }
+    #[inline(always)]
     pub fn const_int(value: u64, ty: Type<'t>) -> Self {
         Value::leaf(
             ValueLeaf::Const(Const::Int {
@@ -329,6 +469,47 @@ impl<'t> Value<'t> {
         Value::leaf(ValueLeaf::Variable(var))
     }
 
+    // `mutability` may only be `Mutable` for a `static mut`
+    pub fn static_ref(name: String, mutability: Mutability) -> Self {
+        Value(ValueKind::Static(name, mutability))
+    }
+
+    // `count` must be a usize
+    pub fn alloc(count: Self, value: Self, line: u32, mir: &Mir<'t>,
+                 function: &mut Function<'t>, block: &mut Block,
+                 fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::Alloc {
+            count: function.get_leaf(mir, count, block, fn_types),
+            value: function.get_leaf(mir, value, block, fn_types),
+            message: mir.panic_message(function, line, "out of memory"),
+        })
+    }
+    pub fn offset(ptr: Self, count: Self, mir: &Mir<'t>,
+                  function: &mut Function<'t>, block: &mut Block,
+                  fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::Offset(
+                function.get_leaf(mir, ptr, block, fn_types),
+                function.get_leaf(mir, count, block, fn_types)))
+    }
+    pub fn tuple(elements: Vec<Self>, mir: &Mir<'t>,
+                 function: &mut Function<'t>, block: &mut Block,
+                 fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let elements = elements.into_iter().map(|v|
+                                                function.get_leaf(mir, v, block, fn_types)).collect();
+        Value(ValueKind::Tuple(elements))
+    }
+    pub fn field(tuple: Self, index: u32, mir: &Mir<'t>,
+                 function: &mut Function<'t>, block: &mut Block,
+                 fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::Field(function.get_leaf(mir, tuple, block, fn_types), index))
+    }
+    // `ptr` must have come from `alloc`
+    pub fn free(ptr: Self, mir: &Mir<'t>, function: &mut Function<'t>,
+                block: &mut Block, fn_types: &HashMap<String, ty::Function<'t>>)
+        -> Self {
+            Value(ValueKind::Free(function.get_leaf(mir, ptr, block, fn_types)))
+        }
+
 
     #[inline(always)]
     fn leaf(leaf: ValueLeaf<'t>) -> Self {
@@ -341,8 +522,26 @@ impl<'t> Value<'t> {
         -> Self {
             Value(ValueKind::Pos(function.get_leaf(mir, inner, block, fn_types)))
         }
-    pub fn neg(inner: Self, mir: &Mir<'t>, function: &mut Function<'t>,
+    // traps on negating the most negative int if overflow checks are on, and
+    // wraps otherwise
+    pub fn neg(inner: Self, line: u32, mir: &Mir<'t>, function: &mut Function<'t>,
                block: &mut Block, fn_types: &HashMap<String, ty::Function<'t>>)
+        -> Self {
+            let ty = inner.ty(mir, function, fn_types);
+            let inner = function.get_leaf(mir, inner, block, fn_types);
+            if let (true, &TypeVariant::SInt(_)) = (mir.overflow_checks, &*ty.0) {
+                let zero = ValueLeaf::Const(Const::Int {
+                    value: 0,
+                    ty: ty,
+                });
+                block.panic_if(Value(ValueKind::SubOverflows(zero, inner)),
+                               mir.panic_message(function, line, "attempt to negate with overflow"),
+                               mir, function, fn_types);
+            }
+            Value(ValueKind::Neg(inner))
+        }
+    pub fn wrapping_neg(inner: Self, mir: &Mir<'t>, function: &mut Function<'t>,
+                        block: &mut Block, fn_types: &HashMap<String, ty::Function<'t>>)
         -> Self {
             Value(ValueKind::Neg(function.get_leaf(mir, inner, block, fn_types)))
         }
@@ -351,13 +550,14 @@ impl<'t> Value<'t> {
         -> Self {
             Value(ValueKind::Not(function.get_leaf(mir, inner, block, fn_types)))
         }
-    pub fn ref_(inner: Self, mir: &Mir<'t>, function: &mut Function<'t>, block: &mut Block,
+    pub fn ref_(inner: Self, mutability: Mutability, mir: &Mir<'t>,
+                function: &mut Function<'t>, block: &mut Block,
                 fn_types: &HashMap<String, ty::Function<'t>>)
         -> Self {
             let inner_ty = inner.ty(mir, function, fn_types);
-            let ptr = function.new_tmp(Type::ref_(inner_ty, mir.ctxt));
+            let ptr = function.new_tmp(Type::reference(inner_ty, mutability, mir.ctxt));
             block.write_ref(Lvalue::Temporary(ptr),
-            inner, mir, function, fn_types);
+            inner, mutability, mir, function, fn_types);
             Value::leaf(ValueLeaf::Temporary(ptr))
         }
 
@@ -368,41 +568,200 @@ impl<'t> Value<'t> {
         }
 
     // -- binops --
-    pub fn add(lhs: Self, rhs: Self, mir: &Mir<'t>,
+    // add, sub and mul trap on integer overflow if overflow checks are on,
+    // and wrap otherwise
+    pub fn add(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
                function: &mut Function<'t>, block: &mut Block,
                fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        if mir.overflow_checks && !lhs.is_float(mir, function, fn_types) {
+            Value::trapping_add(lhs, rhs, line, mir, function, block, fn_types)
+        } else {
+            Value::wrapping_add(lhs, rhs, mir, function, block, fn_types)
+        }
+    }
+    pub fn sub(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+               function: &mut Function<'t>, block: &mut Block,
+               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        if mir.overflow_checks && !lhs.is_float(mir, function, fn_types) {
+            Value::trapping_sub(lhs, rhs, line, mir, function, block, fn_types)
+        } else {
+            Value::wrapping_sub(lhs, rhs, mir, function, block, fn_types)
+        }
+    }
+    pub fn mul(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+               function: &mut Function<'t>, block: &mut Block,
+               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        if mir.overflow_checks && !lhs.is_float(mir, function, fn_types) {
+            Value::trapping_mul(lhs, rhs, line, mir, function, block, fn_types)
+        } else {
+            Value::wrapping_mul(lhs, rhs, mir, function, block, fn_types)
+        }
+    }
+
+    pub fn wrapping_add(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
         Value(ValueKind::Add(
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
-    pub fn sub(lhs: Self, rhs: Self, mir: &Mir<'t>,
-               function: &mut Function<'t>, block: &mut Block,
-               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+    pub fn wrapping_sub(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
         Value(ValueKind::Sub(
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
-    pub fn mul(lhs: Self, rhs: Self, mir: &Mir<'t>,
-               function: &mut Function<'t>, block: &mut Block,
-               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+    pub fn wrapping_mul(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
         Value(ValueKind::Mul(
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
-    pub fn div(lhs: Self, rhs: Self, mir: &Mir<'t>,
-               function: &mut Function<'t>, block: &mut Block,
-               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
-        Value(ValueKind::Div(
+
+    pub fn trapping_add(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        block.panic_if(Value(ValueKind::AddOverflows(lhs, rhs)),
+                       mir.panic_message(function, line, "attempt to add with overflow"),
+                       mir, function, fn_types);
+        Value(ValueKind::Add(lhs, rhs))
+    }
+    pub fn trapping_sub(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        block.panic_if(Value(ValueKind::SubOverflows(lhs, rhs)),
+                       mir.panic_message(function, line, "attempt to subtract with overflow"),
+                       mir, function, fn_types);
+        Value(ValueKind::Sub(lhs, rhs))
+    }
+    pub fn trapping_mul(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+                        function: &mut Function<'t>, block: &mut Block,
+                        fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        block.panic_if(Value(ValueKind::MulOverflows(lhs, rhs)),
+                       mir.panic_message(function, line, "attempt to multiply with overflow"),
+                       mir, function, fn_types);
+        Value(ValueKind::Mul(lhs, rhs))
+    }
+
+    // `(lhs op rhs, whether it overflowed)`, with the result wrapped; for the
+    // checked_* builtins
+    pub fn overflowing_add(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                           function: &mut Function<'t>, block: &mut Block,
+                           fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        let elements = vec![Value(ValueKind::Add(lhs, rhs)),
+                            Value(ValueKind::AddOverflows(lhs, rhs))];
+        Value::tuple(elements, mir, function, block, fn_types)
+    }
+    pub fn overflowing_sub(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                           function: &mut Function<'t>, block: &mut Block,
+                           fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        let elements = vec![Value(ValueKind::Sub(lhs, rhs)),
+                            Value(ValueKind::SubOverflows(lhs, rhs))];
+        Value::tuple(elements, mir, function, block, fn_types)
+    }
+    pub fn overflowing_mul(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                           function: &mut Function<'t>, block: &mut Block,
+                           fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        let elements = vec![Value(ValueKind::Mul(lhs, rhs)),
+                            Value(ValueKind::MulOverflows(lhs, rhs))];
+        Value::tuple(elements, mir, function, block, fn_types)
+    }
+
+    pub fn saturating_add(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                          function: &mut Function<'t>, block: &mut Block,
+                          fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::SaturatingAdd(
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
-    pub fn rem(lhs: Self, rhs: Self, mir: &Mir<'t>,
-               function: &mut Function<'t>, block: &mut Block,
-               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
-        Value(ValueKind::Rem(
+    pub fn saturating_sub(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                          function: &mut Function<'t>, block: &mut Block,
+                          fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::SaturatingSub(
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
+    pub fn saturating_mul(lhs: Self, rhs: Self, mir: &Mir<'t>,
+                          function: &mut Function<'t>, block: &mut Block,
+                          fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        Value(ValueKind::SaturatingMul(
+                function.get_leaf(mir, lhs, block, fn_types),
+                function.get_leaf(mir, rhs, block, fn_types)))
+    }
+    pub fn div(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+               function: &mut Function<'t>, block: &mut Block,
+               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let (lhs, rhs) = Value::guard_division(lhs, rhs, line,
+            "attempt to divide by zero",
+            "attempt to divide with overflow",
+            mir, function, block, fn_types);
+        Value(ValueKind::Div(lhs, rhs))
+    }
+    pub fn rem(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
+               function: &mut Function<'t>, block: &mut Block,
+               fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let (lhs, rhs) = Value::guard_division(lhs, rhs, line,
+            "attempt to calculate the remainder with a divisor of zero",
+            "attempt to calculate the remainder with overflow",
+            mir, function, block, fn_types);
+        Value(ValueKind::Rem(lhs, rhs))
+    }
+
+    // division by zero, and `MIN / -1` for signed integers, are undefined
+    // in LLVM, so they always panic; regardless of `overflow_checks`
+    fn guard_division(lhs: Self, rhs: Self, line: u32,
+                      zero_message: &str, overflow_message: &str,
+                      mir: &Mir<'t>, function: &mut Function<'t>, block: &mut Block,
+                      fn_types: &HashMap<String, ty::Function<'t>>)
+            -> (ValueLeaf<'t>, ValueLeaf<'t>) {
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = function.get_leaf(mir, rhs, block, fn_types);
+        let ty = rhs.ty(mir, function);
+        if let TypeVariant::Float(_) = *ty.0 {
+            // floats just give an infinity or a NaN
+            return (lhs, rhs);
+        }
+        let zero = ValueLeaf::Const(Const::Int {
+            value: 0,
+            ty: ty,
+        });
+        block.panic_if(Value(ValueKind::Eq(rhs, zero)),
+                       mir.panic_message(function, line, zero_message),
+                       mir, function, fn_types);
+        if let TypeVariant::SInt(_) = *ty.0 {
+            let one = ValueLeaf::Const(Const::Int {
+                value: 1,
+                ty: ty,
+            });
+            let neg_one = ValueLeaf::Const(Const::Int {
+                value: -1i64 as u64,
+                ty: ty,
+            });
+            // only `MIN - 1` overflows, whatever the width
+            let lhs_min = function.get_leaf(mir,
+                Value(ValueKind::SubOverflows(lhs, one)), block, fn_types);
+            let rhs_neg_one = function.get_leaf(mir,
+                Value(ValueKind::Eq(rhs, neg_one)), block, fn_types);
+            block.panic_if(Value(ValueKind::And(lhs_min, rhs_neg_one)),
+                           mir.panic_message(function, line, overflow_message),
+                           mir, function, fn_types);
+        }
+        (lhs, rhs)
+    }
     pub fn and(lhs: Self, rhs: Self, mir: &Mir<'t>,
                function: &mut Function<'t>, block: &mut Block,
                fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
@@ -424,19 +783,61 @@ impl<'t> Value<'t> {
                 function.get_leaf(mir, lhs, block, fn_types),
                 function.get_leaf(mir, rhs, block, fn_types)))
     }
-    pub fn shl(lhs: Self, rhs: Self, mir: &Mir<'t>,
+    // shifting by the width or more traps if overflow checks are on, and
+    // shifts by the amount modulo the width otherwise
+    pub fn shl(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
                function: &mut Function<'t>, block: &mut Block,
                fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
-        Value(ValueKind::Shl(
-                function.get_leaf(mir, lhs, block, fn_types),
-                function.get_leaf(mir, rhs, block, fn_types)))
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = Value::shift_amount(rhs, line, "attempt to shift left with overflow",
+                                      mir, function, block, fn_types);
+        Value(ValueKind::Shl(lhs, rhs))
     }
-    pub fn shr(lhs: Self, rhs: Self, mir: &Mir<'t>,
+    pub fn shr(lhs: Self, rhs: Self, line: u32, mir: &Mir<'t>,
                function: &mut Function<'t>, block: &mut Block,
                fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
-        Value(ValueKind::Shr(
-                function.get_leaf(mir, lhs, block, fn_types),
-                function.get_leaf(mir, rhs, block, fn_types)))
+        let lhs = function.get_leaf(mir, lhs, block, fn_types);
+        let rhs = Value::shift_amount(rhs, line, "attempt to shift right with overflow",
+                                      mir, function, block, fn_types);
+        Value(ValueKind::Shr(lhs, rhs))
+    }
+    // the amount as an unsigned int, which LLVM is given only if it's less
+    // than the width
+    fn shift_amount(amount: Self, line: u32, message: &str, mir: &Mir<'t>,
+                    function: &mut Function<'t>, block: &mut Block,
+                    fn_types: &HashMap<String, ty::Function<'t>>) -> ValueLeaf<'t> {
+        let ty = amount.ty(mir, function, fn_types);
+        let (int, signed) = match *ty.0 {
+            TypeVariant::SInt(int) => (int, true),
+            TypeVariant::UInt(int) => (int, false),
+            _ => panic!("ICE: {} can't be used as a shift amount", ty),
+        };
+        let amount_ty = Type::uint(int, mir.ctxt);
+        let pointer_size = mir.target_data.pointer_size();
+        let mut amount = function.get_leaf(mir, amount, block, fn_types);
+        if signed {
+            // a negative amount is as oversized as a huge unsigned one
+            amount = function.get_leaf(mir, Value(ValueKind::Cast(amount, amount_ty)),
+                                       block, fn_types);
+        }
+        let width = ValueLeaf::Const(Const::Int {
+            value: int.size(pointer_size) as u64,
+            ty: amount_ty,
+        });
+        if mir.overflow_checks {
+            block.panic_if(Value(ValueKind::Gte(amount, width)),
+                           mir.panic_message(function, line, message),
+                           mir, function, fn_types);
+            amount
+        } else if let Some(mask) = int.shift_mask(pointer_size) {
+            let mask = ValueLeaf::Const(Const::Int {
+                value: mask,
+                ty: amount_ty,
+            });
+            function.get_leaf(mir, Value(ValueKind::And(amount, mask)), block, fn_types)
+        } else {
+            function.get_leaf(mir, Value(ValueKind::Rem(amount, width)), block, fn_types)
+        }
     }
 
     // -- comparisons --
@@ -485,6 +886,35 @@ impl<'t> Value<'t> {
     }
 
     // -- misc --
+    pub fn cast(inner: Self, ty: Type<'t>, line: u32, mir: &Mir<'t>,
+                function: &mut Function<'t>, block: &mut Block,
+                fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
+        let inner = function.get_leaf(mir, inner, block, fn_types);
+        let from = inner.ty(mir, function);
+        match (*from.0, *ty.0) {
+            (TypeVariant::UInt(ty::Int::I8), TypeVariant::Char) => {}
+            (_, TypeVariant::Char) => {
+                // anything wider than a u8 may not be a unicode scalar value
+                let message = mir.panic_message(function, line,
+                    "invalid char: not a unicode scalar value");
+                let int = |value| {
+                    ValueLeaf::Const(Const::Int {
+                        value: value,
+                        ty: from,
+                    })
+                };
+                block.panic_if(Value(ValueKind::Gt(inner, int(0x10FFFF))),
+                               message.clone(), mir, function, fn_types);
+                // surrogates are 0xD800...0xDFFF
+                let offset = function.get_leaf(mir,
+                    Value(ValueKind::Sub(inner, int(0xD800))), block, fn_types);
+                block.panic_if(Value(ValueKind::Lt(offset, int(0x800))),
+                               message, mir, function, fn_types);
+            }
+            _ => {}
+        }
+        Value(ValueKind::Cast(inner, ty))
+    }
     pub fn call(callee: String, args: Vec<Self>, mir: &Mir<'t>,
                 function: &mut Function<'t>, block: &mut Block,
                 fn_types: &HashMap<String, ty::Function<'t>>) -> Self {
@@ -498,6 +928,70 @@ impl<'t> Value<'t> {
 }
 
 impl<'t> Value<'t> {
+    fn is_float(&self, mir: &Mir<'t>, function: &Function<'t>,
+                fn_types: &HashMap<String, ty::Function<'t>>) -> bool {
+        match *self.ty(mir, function, fn_types).0 {
+            TypeVariant::Float(_) => true,
+            _ => false,
+        }
+    }
+
+    fn map_leaves<F>(&self, mut f: F) -> Self
+        where F: FnMut(ValueLeaf<'t>) -> ValueLeaf<'t>
+    {
+        Value(match self.0 {
+            ValueKind::Leaf(v) => ValueKind::Leaf(f(v)),
+            ValueKind::Pos(v) => ValueKind::Pos(f(v)),
+            ValueKind::Neg(v) => ValueKind::Neg(f(v)),
+            ValueKind::Not(v) => ValueKind::Not(f(v)),
+            ValueKind::Ref(v, mutability) => ValueKind::Ref(f(v), mutability),
+            ValueKind::Deref(v) => ValueKind::Deref(f(v)),
+            ValueKind::Add(l, r) => ValueKind::Add(f(l), f(r)),
+            ValueKind::Sub(l, r) => ValueKind::Sub(f(l), f(r)),
+            ValueKind::Mul(l, r) => ValueKind::Mul(f(l), f(r)),
+            ValueKind::Div(l, r) => ValueKind::Div(f(l), f(r)),
+            ValueKind::Rem(l, r) => ValueKind::Rem(f(l), f(r)),
+            ValueKind::And(l, r) => ValueKind::And(f(l), f(r)),
+            ValueKind::Xor(l, r) => ValueKind::Xor(f(l), f(r)),
+            ValueKind::Or(l, r) => ValueKind::Or(f(l), f(r)),
+            ValueKind::Shl(l, r) => ValueKind::Shl(f(l), f(r)),
+            ValueKind::Shr(l, r) => ValueKind::Shr(f(l), f(r)),
+            ValueKind::AddOverflows(l, r) => ValueKind::AddOverflows(f(l), f(r)),
+            ValueKind::SubOverflows(l, r) => ValueKind::SubOverflows(f(l), f(r)),
+            ValueKind::MulOverflows(l, r) => ValueKind::MulOverflows(f(l), f(r)),
+            ValueKind::SaturatingAdd(l, r) => ValueKind::SaturatingAdd(f(l), f(r)),
+            ValueKind::SaturatingSub(l, r) => ValueKind::SaturatingSub(f(l), f(r)),
+            ValueKind::SaturatingMul(l, r) => ValueKind::SaturatingMul(f(l), f(r)),
+            ValueKind::Eq(l, r) => ValueKind::Eq(f(l), f(r)),
+            ValueKind::Neq(l, r) => ValueKind::Neq(f(l), f(r)),
+            ValueKind::Lt(l, r) => ValueKind::Lt(f(l), f(r)),
+            ValueKind::Lte(l, r) => ValueKind::Lte(f(l), f(r)),
+            ValueKind::Gt(l, r) => ValueKind::Gt(f(l), f(r)),
+            ValueKind::Gte(l, r) => ValueKind::Gte(f(l), f(r)),
+            ValueKind::Cast(v, ty) => ValueKind::Cast(f(v), ty),
+            ValueKind::Static(ref name, mutability) => ValueKind::Static(name.clone(), mutability),
+            ValueKind::Alloc { count, value, ref message } => {
+                ValueKind::Alloc {
+                    count: f(count),
+                    value: f(value),
+                    message: message.clone(),
+                }
+            }
+            ValueKind::Free(ptr) => ValueKind::Free(f(ptr)),
+            ValueKind::Offset(ptr, count) => ValueKind::Offset(f(ptr), f(count)),
+            ValueKind::Tuple(ref elements) => {
+                ValueKind::Tuple(elements.iter().map(|&e| f(e)).collect())
+            }
+            ValueKind::Field(tuple, index) => ValueKind::Field(f(tuple), index),
+            ValueKind::Call { ref callee, ref args } => {
+                ValueKind::Call {
+                    callee: callee.clone(),
+                    args: args.iter().map(|&a| f(a)).collect(),
+                }
+            }
+        })
+    }
+
     fn ty(&self, mir: &Mir<'t>, function: &Function<'t>,
           fn_types: &HashMap<String, ty::Function<'t>>) -> Type<'t> {
         match self.0 {
@@ -506,11 +1000,11 @@ impl<'t> Value<'t> {
             ValueKind::Pos(ref inner) | ValueKind::Neg(ref inner)
                 | ValueKind::Not(ref inner) => inner.ty(mir, function),
 
-                ValueKind::Ref(ref inner) =>
-                    Type::ref_(inner.ty(mir, function), mir.ctxt),
+                ValueKind::Ref(ref inner, mutability) =>
+                    Type::reference(inner.ty(mir, function), mutability, mir.ctxt),
                     ValueKind::Deref(ref inner) => {
-                        if let TypeVariant::Reference(inner) =
-                            *inner.ty(mir, function).0 {
+                        if let TypeVariant::Reference(inner, _) |
+                               TypeVariant::RawPointer(inner, _) = *inner.ty(mir, function).0 {
                                 inner
                             } else {
                                 panic!("Deref of a non-ref type: {:?}", inner)
@@ -525,6 +1019,9 @@ impl<'t> Value<'t> {
                 | ValueKind::And(ref lhs, ref rhs)
                 | ValueKind::Xor(ref lhs, ref rhs)
                 | ValueKind::Or(ref lhs, ref rhs)
+                | ValueKind::SaturatingAdd(ref lhs, ref rhs)
+                | ValueKind::SaturatingSub(ref lhs, ref rhs)
+                | ValueKind::SaturatingMul(ref lhs, ref rhs)
                 => {
                     let lhs_ty = lhs.ty(mir, function);
                     assert_eq!(lhs_ty, rhs.ty(mir, function));
@@ -539,8 +1036,32 @@ impl<'t> Value<'t> {
 
             ValueKind::Eq(_, _) | ValueKind::Neq(_, _) | ValueKind::Lt(_, _)
                 | ValueKind::Lte(_, _) | ValueKind::Gt(_, _) | ValueKind::Gte(_, _)
+                | ValueKind::AddOverflows(_, _) | ValueKind::SubOverflows(_, _)
+                | ValueKind::MulOverflows(_, _)
                 => Type::bool(mir.ctxt),
 
+            ValueKind::Cast(_, ty) => ty,
+
+            ValueKind::Static(ref name, mutability) => {
+                let global = mir.statics.get(name).expect("ICE: no such static");
+                Type::reference(global.ty(mir), mutability, mir.ctxt)
+            }
+
+            ValueKind::Alloc { ref value, .. } => {
+                Type::reference(value.ty(mir, function), Mutability::Mutable, mir.ctxt)
+            }
+            ValueKind::Free(_) => Type::unit(mir.ctxt),
+            ValueKind::Offset(ref ptr, _) => ptr.ty(mir, function),
+            ValueKind::Tuple(ref elements) => {
+                Type::tuple(elements.iter().map(|e| e.ty(mir, function)).collect(), mir.ctxt)
+            }
+            ValueKind::Field(ref tuple, index) => {
+                match *tuple.ty(mir, function).0 {
+                    TypeVariant::Tuple(elements) => elements[index as usize],
+                    _ => panic!("ICE: field of a non-tuple: {:?}", tuple),
+                }
+            }
+
                 ValueKind::Call {
                     ref callee,
                     ..
@@ -555,6 +1076,7 @@ impl<'t> Value<'t> {
                       funcs: &HashMap<String, (llvm::Value, Type<'t>)>)
         -> llvm::Value {
             use llvm_sys::LLVMIntPredicate::*;
+            use llvm_sys::LLVMRealPredicate::*;
             match self.0 {
                 ValueKind::Leaf(v) => {
                     v.to_llvm(mir, function)
@@ -563,7 +1085,8 @@ impl<'t> Value<'t> {
                     let ty = inner.ty(mir, &function.mir);
                     let llinner = inner.to_llvm(mir, function);
                     match *ty.0 {
-                        TypeVariant::SInt(_) | TypeVariant::UInt(_) => llinner,
+                        TypeVariant::SInt(_) | TypeVariant::UInt(_)
+                            | TypeVariant::Float(_) => llinner,
                         _ => panic!("ICE: {} can't be used in unary +", ty),
                     }
                 }
@@ -574,6 +1097,8 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) =>
                             function.builder.build_neg(llinner),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fneg(llinner),
                             _ => panic!("ICE: {} can't be used in unary -", ty),
                     }
                 }
@@ -587,7 +1112,7 @@ impl<'t> Value<'t> {
                             _ => panic!("ICE: {} can't be used in unary !", ty),
                     }
                 }
-                ValueKind::Ref(inner) => {
+                ValueKind::Ref(inner, _) => {
                     match inner {
                         ValueLeaf::Variable(v) => function.get_local_ptr(&v),
                         ValueLeaf::Temporary(t) => function.get_tmp_ptr(&t),
@@ -607,6 +1132,8 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) | TypeVariant::UInt(_) =>
                             function.builder.build_add(lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fadd(lhs, rhs),
                             _ => panic!("ICE: {} can't be used in binary +", ty),
                     }
                 }
@@ -617,6 +1144,8 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) | TypeVariant::UInt(_) =>
                             function.builder.build_sub(lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fsub(lhs, rhs),
                             _ => panic!("ICE: {} can't be used in binary -", ty),
                     }
                 }
@@ -627,6 +1156,8 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) | TypeVariant::UInt(_) =>
                             function.builder.build_mul(lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fmul(lhs, rhs),
                             _ => panic!("ICE: {} can't be used in binary *", ty),
                     }
                 }
@@ -639,6 +1170,8 @@ impl<'t> Value<'t> {
                             function.builder.build_sdiv(lhs, rhs),
                             TypeVariant::UInt(_) =>
                                 function.builder.build_udiv(lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fdiv(lhs, rhs),
                                 _ => panic!("ICE: {} can't be used in binary /", ty),
                     }
                 }
@@ -651,6 +1184,8 @@ impl<'t> Value<'t> {
                             function.builder.build_srem(lhs, rhs),
                             TypeVariant::UInt(_) =>
                                 function.builder.build_urem(lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_frem(lhs, rhs),
                                 _ => panic!("ICE: {} can't be used in binary %", ty),
                     }
                 }
@@ -709,14 +1244,70 @@ impl<'t> Value<'t> {
                                 _ => panic!("ICE: {} can't be used in binary %", ty),
                     }
                 }
+                ValueKind::AddOverflows(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    match *ty.0 {
+                        TypeVariant::SInt(_) =>
+                            function.builder.build_overflow_op("sadd", lhs, rhs).1,
+                        TypeVariant::UInt(_) =>
+                            function.builder.build_overflow_op("uadd", lhs, rhs).1,
+                        _ => panic!("ICE: {} can't be used in checked +", ty),
+                    }
+                }
+                ValueKind::SubOverflows(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    match *ty.0 {
+                        TypeVariant::SInt(_) =>
+                            function.builder.build_overflow_op("ssub", lhs, rhs).1,
+                        TypeVariant::UInt(_) =>
+                            function.builder.build_overflow_op("usub", lhs, rhs).1,
+                        _ => panic!("ICE: {} can't be used in checked -", ty),
+                    }
+                }
+                ValueKind::MulOverflows(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    match *ty.0 {
+                        TypeVariant::SInt(_) =>
+                            function.builder.build_overflow_op("smul", lhs, rhs).1,
+                        TypeVariant::UInt(_) =>
+                            function.builder.build_overflow_op("umul", lhs, rhs).1,
+                        _ => panic!("ICE: {} can't be used in checked *", ty),
+                    }
+                }
+                ValueKind::SaturatingAdd(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    function.build_saturating(mir, "add", ty, lhs, rhs)
+                }
+                ValueKind::SaturatingSub(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    function.build_saturating(mir, "sub", ty, lhs, rhs)
+                }
+                ValueKind::SaturatingMul(lhs, rhs) => {
+                    let ty = lhs.ty(mir, &function.mir);
+                    let lhs = lhs.to_llvm(mir, function);
+                    let rhs = rhs.to_llvm(mir, function);
+                    function.build_saturating(mir, "mul", ty, lhs, rhs)
+                }
                 ValueKind::Eq(lhs, rhs) => {
                     let ty = lhs.ty(mir, &function.mir);
                     let lhs = lhs.to_llvm(mir, function);
                     let rhs = rhs.to_llvm(mir, function);
                     match *ty.0 {
                         TypeVariant::SInt(_) | TypeVariant::UInt(_)
-                            | TypeVariant::Bool =>
+                            | TypeVariant::Char | TypeVariant::Bool =>
                             function.builder.build_icmp(llvm::IntEQ, lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealOEQ, lhs, rhs),
                             _ =>  panic!("ICE: {} can't be used in ==", ty),
                     }
                 }
@@ -726,8 +1317,10 @@ impl<'t> Value<'t> {
                     let rhs = rhs.to_llvm(mir, function);
                     match *ty.0 {
                         TypeVariant::SInt(_) | TypeVariant::UInt(_)
-                            | TypeVariant::Bool =>
+                            | TypeVariant::Char | TypeVariant::Bool =>
                             function.builder.build_icmp(llvm::IntNE, lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealUNE, lhs, rhs),
                             _ =>  panic!("ICE: {} can't be used in !=", ty),
                     }
                 }
@@ -738,8 +1331,10 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) =>
                             function.builder.build_icmp(LLVMIntSLT, lhs, rhs),
-                            TypeVariant::UInt(_) | TypeVariant::Bool =>
+                            TypeVariant::UInt(_) | TypeVariant::Char | TypeVariant::Bool =>
                                 function.builder.build_icmp(LLVMIntULT, lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealOLT, lhs, rhs),
                                 _ =>  panic!("ICE: {} can't be used in <", ty),
                     }
                 }
@@ -750,8 +1345,10 @@ impl<'t> Value<'t> {
                     match *ty.0 {
                         TypeVariant::SInt(_) =>
                             function.builder.build_icmp(LLVMIntSLE, lhs, rhs),
-                            TypeVariant::UInt(_) | TypeVariant::Bool =>
+                            TypeVariant::UInt(_) | TypeVariant::Char | TypeVariant::Bool =>
                                 function.builder.build_icmp(LLVMIntULE, lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealOLE, lhs, rhs),
                                 _ =>  panic!("ICE: {} can't be used in <=", ty),
                     }
                 }
@@ -763,9 +1360,12 @@ impl<'t> Value<'t> {
                         TypeVariant::SInt(_) =>
                             function.builder.build_icmp(LLVMIntSGT,
                                                         lhs, rhs),
-                                                        TypeVariant::UInt(_) | TypeVariant::Bool =>
+                                                        TypeVariant::UInt(_) | TypeVariant::Char
+                                                            | TypeVariant::Bool =>
                                                             function.builder.build_icmp(LLVMIntUGT,
                                                                                         lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealOGT, lhs, rhs),
                                                                                         _ =>  panic!("ICE: {} can't be used in >", ty),
                     }
                 }
@@ -777,12 +1377,133 @@ impl<'t> Value<'t> {
                         TypeVariant::SInt(_) =>
                             function.builder.build_icmp(LLVMIntSGE,
                                                         lhs, rhs),
-                                                        TypeVariant::UInt(_) | TypeVariant::Bool =>
+                                                        TypeVariant::UInt(_) | TypeVariant::Char
+                                                            | TypeVariant::Bool =>
                                                             function.builder.build_icmp(LLVMIntUGE,
                                                                                         lhs, rhs),
+                        TypeVariant::Float(_) =>
+                            function.builder.build_fcmp(LLVMRealOGE, lhs, rhs),
                                                                                         _ =>  panic!("ICE: {} can't be used in >=", ty),
                     }
                 }
+                ValueKind::Cast(inner, ty) => {
+                    let from = inner.ty(mir, &function.mir);
+                    let llty = llvm::get_type(&mir.target_data, ty);
+                    let inner = inner.to_llvm(mir, function);
+                    let (from_size, to_size) = (llvm::size_of_type(&mir.target_data, from),
+                                                llvm::size_of_type(&mir.target_data, ty));
+                    match (*from.0, *ty.0) {
+                        (TypeVariant::Reference(..), TypeVariant::Reference(..))
+                            | (TypeVariant::Reference(..), TypeVariant::RawPointer(..))
+                            | (TypeVariant::RawPointer(..), TypeVariant::Reference(..))
+                            | (TypeVariant::RawPointer(..), TypeVariant::RawPointer(..)) =>
+                            function.builder.build_pointer_cast(inner, llty),
+                        (TypeVariant::Reference(..), _) | (TypeVariant::RawPointer(..), _) =>
+                            function.builder.build_ptr_to_int(inner, llty),
+                        (_, TypeVariant::RawPointer(..)) =>
+                            function.builder.build_int_to_ptr(inner, llty),
+                        // out of range float to int casts are undefined,
+                        // like they are in LLVM
+                        (TypeVariant::Float(_), TypeVariant::SInt(_)) =>
+                            function.builder.build_fptosi(inner, llty),
+                        (TypeVariant::Float(_), TypeVariant::UInt(_)) =>
+                            function.builder.build_fptoui(inner, llty),
+                        (TypeVariant::SInt(_), TypeVariant::Float(_)) =>
+                            function.builder.build_sitofp(inner, llty),
+                        (TypeVariant::UInt(_), TypeVariant::Float(_)) =>
+                            function.builder.build_uitofp(inner, llty),
+                        (TypeVariant::Float(_), TypeVariant::Float(_)) => {
+                            if from_size > to_size {
+                                function.builder.build_fptrunc(inner, llty)
+                            } else if from_size < to_size {
+                                function.builder.build_fpext(inner, llty)
+                            } else {
+                                inner
+                            }
+                        }
+                        _ if from_size > to_size => function.builder.build_trunc(inner, llty),
+                        (TypeVariant::SInt(_), _) if from_size < to_size =>
+                            function.builder.build_sext(inner, llty),
+                        (TypeVariant::UInt(_), _) | (TypeVariant::Char, _)
+                            | (TypeVariant::Bool, _) if from_size < to_size =>
+                            function.builder.build_zext(inner, llty),
+                        _ if from_size == to_size => function.builder.build_bitcast(inner, llty),
+                        _ => panic!("ICE: {} can't be cast to {}", from, ty),
+                    }
+                }
+                ValueKind::Static(name, _) => {
+                    function.builder.get_global(&symbol(&name))
+                }
+                ValueKind::Alloc { count, value, message } => {
+                    let elt_ty = value.ty(mir, &function.mir);
+                    let ptr_ty = llvm::get_type(&mir.target_data,
+                        Type::reference(elt_ty, Mutability::Mutable, mir.ctxt));
+                    let size_ty = llvm::get_type(&mir.target_data, Type::uint(ty::Int::Pointer, mir.ctxt));
+                    // malloc(0) may return null, which we'd take for failure
+                    let elt_size = std::cmp::max(
+                        llvm::alloc_size_of_type(&mir.target_data, elt_ty), 1);
+                    let count = count.to_llvm(mir, function);
+                    let value = value.to_llvm(mir, function);
+
+                    let (bytes, overflowed) = function.builder.build_overflow_op("umul",
+                        count, llvm::Value::const_int(size_ty, elt_size));
+                    let raw_ptr = function.builder.build_malloc(&mir.target_data, bytes);
+                    let failed = function.builder.build_or(overflowed,
+                        function.builder.build_is_null(raw_ptr));
+                    let ptr = function.builder.build_pointer_cast(raw_ptr, ptr_ty);
+
+                    let oom = llvm::BasicBlock::append_named(function.raw, "alloc_failed");
+                    let init = llvm::BasicBlock::append_named(function.raw, "alloc_init");
+                    let store = llvm::BasicBlock::append_named(function.raw, "alloc_store");
+                    let done = llvm::BasicBlock::append_named(function.raw, "alloc_done");
+                    let entry = function.builder.insert_block();
+                    function.builder.build_cond_br(failed, oom, init);
+
+                    function.builder.position_at_end(oom);
+                    function.builder.build_panic(&mir.target_data, &message);
+
+                    // for (i = 0; i < count; ++i) { ptr[i] = value; }
+                    function.builder.position_at_end(init);
+                    let i = function.builder.build_phi(size_ty);
+                    i.add_incoming(llvm::Value::const_int(size_ty, 0), entry);
+                    let finished = function.builder.build_icmp(LLVMIntUGE, i, count);
+                    function.builder.build_cond_br(finished, done, store);
+
+                    function.builder.position_at_end(store);
+                    function.builder.build_store(function.builder.build_gep(ptr, i), value);
+                    let next = function.builder.build_add(i,
+                        llvm::Value::const_int(size_ty, 1));
+                    i.add_incoming(next, store);
+                    function.builder.build_br(init);
+
+                    function.builder.position_at_end(done);
+                    ptr
+                }
+                ValueKind::Free(ptr) => {
+                    let ptr = ptr.to_llvm(mir, function);
+                    function.builder.build_free(ptr);
+                    llvm::Value::const_struct(&[])
+                }
+                ValueKind::Offset(ptr, count) => {
+                    let ptr = ptr.to_llvm(mir, function);
+                    let count = count.to_llvm(mir, function);
+                    function.builder.build_gep(ptr, count)
+                }
+                ValueKind::Tuple(elements) => {
+                    let ty = Type::tuple(elements.iter().map(|e| e.ty(mir, &function.mir))
+                                                 .collect(),
+                                         mir.ctxt);
+                    let mut tuple = llvm::Value::undef(llvm::get_type(&mir.target_data, ty));
+                    for (i, elt) in elements.into_iter().enumerate() {
+                        let elt = elt.to_llvm(mir, function);
+                        tuple = function.builder.build_insert_value(tuple, elt, i as u32);
+                    }
+                    tuple
+                }
+                ValueKind::Field(tuple, index) => {
+                    let tuple = tuple.to_llvm(mir, function);
+                    function.builder.build_extract_value(tuple, index)
+                }
                 ValueKind::Call {
                     callee,
                     args,
@@ -837,6 +1558,10 @@ enum Terminator<'t> {
     },
     // Normal return; should only happen in the end block
     Return,
+    // Prints the message to stderr, and aborts
+    Panic(String),
+    // after a call of a function which never returns
+    Unreachable,
 }
 
 impl<'t> Terminator<'t> {
@@ -864,6 +1589,10 @@ impl<'t> Terminator<'t> {
                     function.builder.build_ret(value);
                 }
             }
+            Terminator::Panic(message) => {
+                function.builder.build_panic(&mir.target_data, &message);
+            }
+            Terminator::Unreachable => function.builder.build_unreachable(),
         }
     }
 }
@@ -890,14 +1619,15 @@ impl Block {
                             mir: &Mir<'t>, function: &mut Function<'t>,
                             fn_types: &HashMap<String, ty::Function<'t>>) {
         let leaf = function.get_leaf(mir, ptr, self, fn_types);
-        if let TypeVariant::Reference(_) = *leaf.ty(mir, function).0 {
+        if let TypeVariant::Reference(..) | TypeVariant::RawPointer(..) =
+               *leaf.ty(mir, function).0 {
         } else {
             panic!("writing to a not-pointer: {}", leaf.ty(mir, function))
         }
         self.add_stmt(Lvalue::Deref(leaf), val, function)
     }
 
-    fn write_ref<'t>(&mut self, ptr: Lvalue<'t>, val: Value<'t>,
+    fn write_ref<'t>(&mut self, ptr: Lvalue<'t>, val: Value<'t>, mutability: Mutability,
                      mir: &Mir<'t>, function: &mut Function<'t>,
                      fn_types: &HashMap<String, ty::Function<'t>>) {
         let leaf = function.get_leaf(mir, val, self, fn_types);
@@ -914,7 +1644,7 @@ impl Block {
             }
             l @ ValueLeaf::Temporary(_) | l @ ValueLeaf::Variable(_) => l,
         };
-        self.add_stmt(ptr, Value(ValueKind::Ref(leaf)), function);
+        self.add_stmt(ptr, Value(ValueKind::Ref(leaf, mutability)), function);
     }
 
     fn add_stmt<'t>(&mut self, lvalue: Lvalue<'t>, value: Value<'t>,
@@ -962,6 +1692,36 @@ impl Block {
             (then, else_, join, Value(ValueKind::Leaf(ValueLeaf::Temporary(tmp))))
         }
 
+    // `self` becomes the block that's run when `cond` is false
+    fn panic_if<'t>(&mut self, cond: Value<'t>, message: String,
+                    mir: &Mir<'t>, function: &mut Function<'t>,
+                    fn_types: &HashMap<String, ty::Function<'t>>) {
+        let cond = function.get_leaf(mir, cond, self, fn_types);
+        let panic = function.new_block(Lvalue::Return, Terminator::Panic(message));
+        let (expr, term) = {
+            let blk = function.get_block(self);
+            let term = std::mem::replace(&mut blk.terminator,
+                                         Terminator::Goto(Block(0)));
+            (blk.expr, term)
+        };
+        let cont = function.new_block(expr, term);
+        self.terminate(function, Terminator::If {
+            cond: cond,
+            then_blk: panic,
+            else_blk: Block(cont.0),
+        });
+        *self = cont;
+    }
+
+    // `message` is printed with the file and `line` unless `cond` is true
+    pub fn assert<'t>(&mut self, cond: Value<'t>, line: u32, message: &str,
+                      mir: &Mir<'t>, function: &mut Function<'t>,
+                      fn_types: &HashMap<String, ty::Function<'t>>) {
+        let failed = Value::not(cond, mir, function, self, fn_types);
+        let message = mir.panic_message(function, line, message);
+        self.panic_if(failed, message, mir, function, fn_types);
+    }
+
     pub fn early_ret<'t>(mut self, function: &mut Function<'t>,
                          value: Value<'t>) {
         let blk = function.get_block(&mut self);
@@ -969,6 +1729,19 @@ impl Block {
         blk.terminator = Terminator::Goto(END_BLOCK);
     }
 
+    // `message` is printed to stderr with the file and `line`. LLVM already
+    // treats the block as cold, since it ends in `unreachable`.
+    pub fn panic<'t>(mut self, line: u32, message: &str, mir: &Mir<'t>,
+                     function: &mut Function<'t>) {
+        let message = mir.panic_message(function, line, message);
+        self.terminate(function, Terminator::Panic(message));
+    }
+
+    // the block can't be left, since the call it ends with never returns
+    pub fn unreachable<'t>(mut self, function: &mut Function<'t>) {
+        self.terminate(function, Terminator::Unreachable);
+    }
+
     pub fn finish<'t>(mut self, function: &mut Function<'t>,
                       value: Value<'t>) {
         let blk = function.get_block(&mut self);
@@ -999,18 +1772,40 @@ impl<'t> BlockData<'t> {
     }
 }
 
+// the name of a function or static in the object file. Only `main` and the
+// externs keep their own; the rest get a prefix no C name can have, so that
+// a user's `malloc` or `write` isn't mistaken for the one the runtime calls
+fn symbol(name: &str) -> String {
+    if name == "main" {
+        name.to_owned()
+    } else {
+        format!("sva.{}", name)
+    }
+}
+
 pub struct Mir<'t> {
     functions: HashMap<String, Function<'t>>,
+    // declared, but defined elsewhere
+    externs: HashMap<String, ty::Function<'t>>,
+    // the C name of each extern; a prelude extern which a program's item
+    // shadows has been renamed, but keeps its C name
+    extern_symbols: HashMap<String, String>,
+    // the values of the consts, already evaluated
+    consts: HashMap<String, Const<'t>>,
+    statics: HashMap<String, Static<'t>>,
     ctxt: &'t TypeContext<'t>,
+    file: String,
 
     optimize: bool,
+    overflow_checks: bool,
 
     target_machine: llvm::TargetMachine,
     target_data: llvm::TargetData,
 }
 
 impl<'t> Mir<'t> {
-    pub fn new(ctxt: &'t TypeContext<'t>, opt: bool) -> Mir<'t> {
+    pub fn new(ctxt: &'t TypeContext<'t>, file: &str, opt: bool,
+               overflow_checks: bool) -> Mir<'t> {
         let opt_level = if opt {
             llvm::NoOptimization
         } else {
@@ -1022,8 +1817,14 @@ impl<'t> Mir<'t> {
 
         Mir {
             functions: HashMap::new(),
+            externs: HashMap::new(),
+            extern_symbols: HashMap::new(),
+            consts: HashMap::new(),
+            statics: HashMap::new(),
             ctxt: ctxt,
+            file: file.to_owned(),
             optimize: opt,
+            overflow_checks: overflow_checks,
             target_machine: target_machine,
             target_data: target_data,
         }
@@ -1033,14 +1834,67 @@ impl<'t> Mir<'t> {
         self.functions.insert(name, func);
     }
 
+    pub fn add_extern(&mut self, name: String, symbol: String, ty: ty::Function<'t>) {
+        self.extern_symbols.insert(name.clone(), symbol);
+        self.externs.insert(name, ty);
+    }
+
+    // `value` must be a constant
+    pub fn add_const(&mut self, name: String, value: Value<'t>) {
+        match value.0 {
+            ValueKind::Leaf(ValueLeaf::Const(value)) => {
+                self.consts.insert(name, value);
+            }
+            _ => panic!("ICE: const {} isn't a constant: {}", name, value),
+        }
+    }
+
+    pub fn get_const(&self, name: &str) -> Option<Value<'t>> {
+        self.consts.get(name).map(|&value| Value::leaf(ValueLeaf::Const(value)))
+    }
+
+    // `init` must be a constant
+    pub fn add_static(&mut self, name: String, mutability: Mutability, init: Value<'t>) {
+        match init.0 {
+            ValueKind::Leaf(ValueLeaf::Const(init)) => {
+                self.statics.insert(name, Static {
+                    mutability: mutability,
+                    init: init,
+                });
+            }
+            _ => panic!("ICE: the initializer of static {} isn't a constant: {}", name, init),
+        }
+    }
+
+    pub fn is_static(&self, name: &str) -> bool {
+        self.statics.contains_key(name)
+    }
+
+    // in bits
+    pub fn pointer_size(&self) -> u32 {
+        self.target_data.pointer_size()
+    }
+
     pub fn build_and_write(mut self, output: &str, print_llir: bool) {
         let mut llvm_functions = HashMap::new();
         let module = llvm::Module::new();
 
         let optimizer = llvm::FnOptimizer::for_module(&module);
 
+        for (name, global) in &self.statics {
+            let init = unsafe { global.init.to_llvm(&self) };
+            module.add_global(&symbol(name), init, global.mutability == Mutability::Immutable);
+        }
+
+        for (name, ty) in &self.externs {
+            // both a program and the prelude may declare the same C function
+            let llfunc = module.get_or_add_function(&self.extern_symbols[name],
+                                                    llvm::get_function_type(&self.target_data,
+                                                                            ty));
+            llvm_functions.insert(name.clone(), (llfunc, ty.output()));
+        }
         for (name, function) in &self.functions {
-            let llfunc = module.add_function(&name,
+            let llfunc = module.add_function(&symbol(name),
                                              llvm::get_function_type(&self.target_data, &function.ty));
             llvm_functions.insert(name.clone(),
             (llfunc, function.ty.output()));
@@ -1072,6 +1926,10 @@ impl<'t> Mir<'t> {
     pub fn ty_ctxt(&self) -> &'t TypeContext<'t> {
         self.ctxt
     }
+
+    fn panic_message(&self, function: &Function<'t>, line: u32, message: &str) -> String {
+        format!("{}:{}: {}", function.file.unwrap_or(&self.file), line, message)
+    }
 }
 
 impl<'t> std::fmt::Display for Function<'t> {
@@ -1099,6 +1957,8 @@ impl<'t> std::fmt::Display for Terminator<'t> {
         match *self {
             Terminator::Goto(ref b) => write!(f, "goto -> bb{}", b.0),
             Terminator::Return => write!(f, "return"),
+            Terminator::Panic(ref message) => write!(f, "panic({:?})", message),
+            Terminator::Unreachable => write!(f, "unreachable"),
             Terminator::If {
                 ref cond,
                 ref then_blk,
@@ -1136,9 +1996,19 @@ impl<'t> std::fmt::Display for Const<'t> {
                 match *ty.0 {
                     TypeVariant::SInt(_) => write!(f, "{}", *value as i64),
                     TypeVariant::UInt(_) => write!(f, "{}", *value as u64),
+                    TypeVariant::Char => {
+                        match std::char::from_u32(*value as u32) {
+                            Some(c) => write!(f, "{:?}", c),
+                            None => write!(f, "'\\u{{{:x}}}'", value),
+                        }
+                    }
                     _ => panic!("Non-integer int"),
                 }
             }
+            Const::Float {
+                ref value,
+                ..
+            } => write!(f, "{:?}", value),
             Const::Bool(ref value) => write!(f, "{}", value),
             Const::Unit => write!(f, "()"),
         }
@@ -1163,7 +2033,8 @@ impl<'t> std::fmt::Display for Value<'t> {
             ValueKind::Pos(ref inner) => write!(f, "Pos({})", inner),
             ValueKind::Neg(ref inner) => write!(f, "Neg({})", inner),
             ValueKind::Not(ref inner) => write!(f, "Not({})", inner),
-            ValueKind::Ref(ref inner) => write!(f, "&{}", inner),
+            ValueKind::Ref(ref inner, Mutability::Immutable) => write!(f, "&{}", inner),
+            ValueKind::Ref(ref inner, Mutability::Mutable) => write!(f, "&mut {}", inner),
             ValueKind::Deref(ref inner) => write!(f, "*{}", inner),
             ValueKind::Add(ref lhs, ref rhs)
                 => write!(f, "Add({}, {})", lhs, rhs),
@@ -1185,6 +2056,18 @@ impl<'t> std::fmt::Display for Value<'t> {
                                                 => write!(f, "Shl({}, {})", lhs, rhs),
                                                 ValueKind::Shr(ref lhs, ref rhs)
                                                     => write!(f, "Shr({}, {})", lhs, rhs),
+            ValueKind::AddOverflows(ref lhs, ref rhs)
+                => write!(f, "AddOverflows({}, {})", lhs, rhs),
+            ValueKind::SubOverflows(ref lhs, ref rhs)
+                => write!(f, "SubOverflows({}, {})", lhs, rhs),
+            ValueKind::MulOverflows(ref lhs, ref rhs)
+                => write!(f, "MulOverflows({}, {})", lhs, rhs),
+            ValueKind::SaturatingAdd(ref lhs, ref rhs)
+                => write!(f, "SaturatingAdd({}, {})", lhs, rhs),
+            ValueKind::SaturatingSub(ref lhs, ref rhs)
+                => write!(f, "SaturatingSub({}, {})", lhs, rhs),
+            ValueKind::SaturatingMul(ref lhs, ref rhs)
+                => write!(f, "SaturatingMul({}, {})", lhs, rhs),
 
                                                     ValueKind::Eq(ref lhs, ref rhs)
                                                         => write!(f, "Eq({}, {})", lhs, rhs),
@@ -1199,6 +2082,26 @@ impl<'t> std::fmt::Display for Value<'t> {
                                                                         ValueKind::Gte(ref lhs, ref rhs)
                                                                             => write!(f, "Gte({}, {})", lhs, rhs),
 
+            ValueKind::Cast(ref inner, ref ty) => write!(f, "{} as {}", inner, ty),
+            ValueKind::Static(ref name, Mutability::Immutable) => write!(f, "&{}", name),
+            ValueKind::Static(ref name, Mutability::Mutable) => write!(f, "&mut {}", name),
+            ValueKind::Alloc { ref count, ref value, .. } => {
+                write!(f, "Alloc({}, {})", count, value)
+            }
+            ValueKind::Free(ref ptr) => write!(f, "Free({})", ptr),
+            ValueKind::Offset(ref ptr, ref count) => write!(f, "Offset({}, {})", ptr, count),
+            ValueKind::Tuple(ref elements) => {
+                try!(write!(f, "("));
+                for (i, elt) in elements.iter().enumerate() {
+                    if i != 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", elt));
+                }
+                write!(f, ")")
+            }
+            ValueKind::Field(ref tuple, index) => write!(f, "{}.{}", tuple, index),
+
                                                                             ValueKind::Call {
                                                                                 ref callee,
                                                                                 ref args,
@@ -1218,6 +2121,18 @@ impl<'t> std::fmt::Display for Value<'t> {
 
 impl<'t> std::fmt::Display for Mir<'t> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        for (name, global) in &self.statics {
+            let mutability = match global.mutability {
+                Mutability::Immutable => "",
+                Mutability::Mutable => "mut ",
+            };
+            try!(writeln!(f, "static {}{}: {} = {};\n",
+                          mutability, name, global.ty(self), global.init));
+        }
+        for (name, ty) in &self.externs {
+            let inputs = ty.input().iter().map(|input| input.to_string()).collect::<Vec<_>>();
+            try!(writeln!(f, "extern fn {}({}) -> {};\n", name, inputs.join(", "), ty.output()));
+        }
         for (name, function) in &self.functions {
             try!(write!(f, "fn {}(", name));
             let inputs = function.ty.input();
@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+use ty::{self, Type, TypeVariant};
+use super::{Mir, Function, Block, Statement, Terminator, Lvalue, Value, ValueKind, ValueLeaf,
+            START_BLOCK, END_BLOCK};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Location {
+    Statement {
+        block: usize,
+        index: usize,
+    },
+    Terminator {
+        block: usize,
+    },
+}
+
+#[derive(Debug)]
+pub enum ValidationError<'t> {
+    TypeMismatch {
+        lvalue: Type<'t>,
+        value: Type<'t>,
+        statement: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    OperandMismatch {
+        lhs: Type<'t>,
+        rhs: Type<'t>,
+        value: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    DerefOfNonReference {
+        ty: Type<'t>,
+        leaf: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    RefOfNonLvalue {
+        leaf: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    LeafOutOfRange {
+        leaf: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    FieldOutOfRange {
+        ty: Type<'t>,
+        index: u32,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    AllocCountNotUsize {
+        ty: Type<'t>,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    StaticDoesntExist {
+        name: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    FunctionDoesntExist {
+        callee: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    IncorrectNumberOfArguments {
+        passed: usize,
+        expected: usize,
+        callee: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    ArgumentMismatch {
+        expected: Type<'t>,
+        found: Type<'t>,
+        callee: String,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    NonBoolCondition {
+        ty: Type<'t>,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    BlockOutOfRange {
+        target: usize,
+        blocks: usize,
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    // the builder uses `goto -> bb0` as a placeholder terminator, and
+    // nothing may legitimately jump back to the start block
+    Unterminated {
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+    ReturnOutsideEndBlock {
+        function: String,
+        location: Location,
+        compiler: (&'static str, u32),
+    },
+}
+
+pub fn validate<'t>(mir: &Mir<'t>) -> Result<(), ValidationError<'t>> {
+    let fn_types = mir.functions
+                      .iter()
+                      .map(|(name, func)| (name.clone(), func.ty.clone()))
+                      .chain(mir.externs.iter().map(|(name, ty)| (name.clone(), ty.clone())))
+                      .collect::<HashMap<_, _>>();
+    for (name, function) in &mir.functions {
+        let validator = Validator {
+            mir: mir,
+            name: name,
+            function: function,
+            fn_types: &fn_types,
+        };
+        try!(validator.validate());
+    }
+    Ok(())
+}
+
+struct Validator<'a, 't: 'a> {
+    mir: &'a Mir<'t>,
+    name: &'a str,
+    function: &'a Function<'t>,
+    fn_types: &'a HashMap<String, ty::Function<'t>>,
+}
+
+impl<'a, 't> Validator<'a, 't> {
+    fn validate(&self) -> Result<(), ValidationError<'t>> {
+        for (i, block) in self.function.blocks.iter().enumerate() {
+            for (j, stmt) in block.statements.iter().enumerate() {
+                try!(self.statement(stmt,
+                                    Location::Statement {
+                                        block: i,
+                                        index: j,
+                                    }));
+            }
+            try!(self.terminator(&block.terminator, Location::Terminator { block: i }));
+        }
+        match self.function.blocks[END_BLOCK.0].terminator {
+            Terminator::Return => Ok(()),
+            _ => {
+                Err(ValidationError::Unterminated {
+                    function: self.name.to_owned(),
+                    location: Location::Terminator { block: END_BLOCK.0 },
+                    compiler: fl!(),
+                })
+            }
+        }
+    }
+
+    fn statement(&self,
+                 stmt: &Statement<'t>,
+                 location: Location)
+                 -> Result<(), ValidationError<'t>> {
+        let lvalue = try!(self.lvalue_ty(&stmt.0, location));
+        let value = try!(self.value_ty(&stmt.1, location));
+        if lvalue == value {
+            Ok(())
+        } else {
+            Err(ValidationError::TypeMismatch {
+                lvalue: lvalue,
+                value: value,
+                statement: format!("{}", stmt),
+                function: self.name.to_owned(),
+                location: location,
+                compiler: fl!(),
+            })
+        }
+    }
+
+    fn terminator(&self,
+                  term: &Terminator<'t>,
+                  location: Location)
+                  -> Result<(), ValidationError<'t>> {
+        match *term {
+            Terminator::Goto(ref blk) => self.target(blk, location),
+            Terminator::If { ref cond, ref then_blk, ref else_blk } => {
+                let ty = try!(self.leaf_ty(cond, location));
+                if *ty.0 != TypeVariant::Bool {
+                    return Err(ValidationError::NonBoolCondition {
+                        ty: ty,
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+                try!(self.target(then_blk, location));
+                self.target(else_blk, location)
+            }
+            Terminator::Return => {
+                match location {
+                    Location::Terminator { block } if block == END_BLOCK.0 => Ok(()),
+                    _ => {
+                        Err(ValidationError::ReturnOutsideEndBlock {
+                            function: self.name.to_owned(),
+                            location: location,
+                            compiler: fl!(),
+                        })
+                    }
+                }
+            }
+            Terminator::Panic(_) | Terminator::Unreachable => Ok(()),
+        }
+    }
+
+    fn target(&self, blk: &Block, location: Location) -> Result<(), ValidationError<'t>> {
+        if blk.0 >= self.function.blocks.len() {
+            Err(ValidationError::BlockOutOfRange {
+                target: blk.0,
+                blocks: self.function.blocks.len(),
+                function: self.name.to_owned(),
+                location: location,
+                compiler: fl!(),
+            })
+        } else if *blk == START_BLOCK {
+            Err(ValidationError::Unterminated {
+                function: self.name.to_owned(),
+                location: location,
+                compiler: fl!(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    fn lvalue_ty(&self,
+                 lvalue: &Lvalue<'t>,
+                 location: Location)
+                 -> Result<Type<'t>, ValidationError<'t>> {
+        match *lvalue {
+            Lvalue::Variable(var) => self.leaf_ty(&ValueLeaf::Variable(var), location),
+            Lvalue::Temporary(tmp) => self.leaf_ty(&ValueLeaf::Temporary(tmp), location),
+            Lvalue::Deref(ref ptr) => self.pointee_ty(ptr, location),
+            Lvalue::Return => Ok(self.function.ty.output()),
+        }
+    }
+
+    fn value_ty(&self,
+                value: &Value<'t>,
+                location: Location)
+                -> Result<Type<'t>, ValidationError<'t>> {
+        match value.0 {
+            ValueKind::Leaf(ref inner) |
+            ValueKind::Pos(ref inner) |
+            ValueKind::Neg(ref inner) |
+            ValueKind::Not(ref inner) |
+            ValueKind::Cast(ref inner, _) => {
+                try!(self.leaf_ty(inner, location));
+            }
+            ValueKind::Ref(ref inner, _) => {
+                match *inner {
+                    ValueLeaf::Variable(_) | ValueLeaf::Temporary(_) => {
+                        try!(self.leaf_ty(inner, location));
+                    }
+                    ValueLeaf::Const(_) | ValueLeaf::Parameter(_) => {
+                        return Err(ValidationError::RefOfNonLvalue {
+                            leaf: format!("{}", inner),
+                            function: self.name.to_owned(),
+                            location: location,
+                            compiler: fl!(),
+                        });
+                    }
+                }
+            }
+            ValueKind::Deref(ref inner) => {
+                try!(self.pointee_ty(inner, location));
+            }
+            ValueKind::Add(ref lhs, ref rhs) |
+            ValueKind::Sub(ref lhs, ref rhs) |
+            ValueKind::Mul(ref lhs, ref rhs) |
+            ValueKind::Div(ref lhs, ref rhs) |
+            ValueKind::Rem(ref lhs, ref rhs) |
+            ValueKind::And(ref lhs, ref rhs) |
+            ValueKind::Xor(ref lhs, ref rhs) |
+            ValueKind::Or(ref lhs, ref rhs) |
+            ValueKind::AddOverflows(ref lhs, ref rhs) |
+            ValueKind::SubOverflows(ref lhs, ref rhs) |
+            ValueKind::MulOverflows(ref lhs, ref rhs) |
+            ValueKind::SaturatingAdd(ref lhs, ref rhs) |
+            ValueKind::SaturatingSub(ref lhs, ref rhs) |
+            ValueKind::SaturatingMul(ref lhs, ref rhs) |
+            ValueKind::Eq(ref lhs, ref rhs) |
+            ValueKind::Neq(ref lhs, ref rhs) |
+            ValueKind::Lt(ref lhs, ref rhs) |
+            ValueKind::Lte(ref lhs, ref rhs) |
+            ValueKind::Gt(ref lhs, ref rhs) |
+            ValueKind::Gte(ref lhs, ref rhs) => {
+                let lhs_ty = try!(self.leaf_ty(lhs, location));
+                let rhs_ty = try!(self.leaf_ty(rhs, location));
+                if lhs_ty != rhs_ty {
+                    return Err(ValidationError::OperandMismatch {
+                        lhs: lhs_ty,
+                        rhs: rhs_ty,
+                        value: format!("{}", value),
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+            }
+            ValueKind::Shl(ref lhs, ref rhs) | ValueKind::Shr(ref lhs, ref rhs) => {
+                try!(self.leaf_ty(lhs, location));
+                try!(self.leaf_ty(rhs, location));
+            }
+            ValueKind::Static(ref name, _) => {
+                if !self.mir.statics.contains_key(name) {
+                    return Err(ValidationError::StaticDoesntExist {
+                        name: name.clone(),
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+            }
+            ValueKind::Alloc { ref count, ref value, .. } => {
+                let count_ty = try!(self.leaf_ty(count, location));
+                if count_ty != Type::uint(ty::Int::Pointer, self.mir.ctxt) {
+                    return Err(ValidationError::AllocCountNotUsize {
+                        ty: count_ty,
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+                try!(self.leaf_ty(value, location));
+            }
+            ValueKind::Free(ref ptr) => {
+                try!(self.pointee_ty(ptr, location));
+            }
+            ValueKind::Tuple(ref elements) => {
+                for elt in elements {
+                    try!(self.leaf_ty(elt, location));
+                }
+            }
+            ValueKind::Field(ref tuple, index) => {
+                let ty = try!(self.leaf_ty(tuple, location));
+                let in_range = match *ty.0 {
+                    TypeVariant::Tuple(elements) => (index as usize) < elements.len(),
+                    _ => false,
+                };
+                if !in_range {
+                    return Err(ValidationError::FieldOutOfRange {
+                        ty: ty,
+                        index: index,
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+            }
+            ValueKind::Offset(ref ptr, ref count) => {
+                try!(self.pointee_ty(ptr, location));
+                try!(self.leaf_ty(count, location));
+            }
+            ValueKind::Call { ref callee, ref args } => {
+                let callee_ty = match self.fn_types.get(callee) {
+                    Some(f) => f,
+                    None => {
+                        return Err(ValidationError::FunctionDoesntExist {
+                            callee: callee.clone(),
+                            function: self.name.to_owned(),
+                            location: location,
+                            compiler: fl!(),
+                        })
+                    }
+                };
+                if callee_ty.input().len() != args.len() {
+                    return Err(ValidationError::IncorrectNumberOfArguments {
+                        passed: args.len(),
+                        expected: callee_ty.input().len(),
+                        callee: callee.clone(),
+                        function: self.name.to_owned(),
+                        location: location,
+                        compiler: fl!(),
+                    });
+                }
+                for (&expected, arg) in callee_ty.input().iter().zip(args) {
+                    let found = try!(self.leaf_ty(arg, location));
+                    if expected != found {
+                        return Err(ValidationError::ArgumentMismatch {
+                            expected: expected,
+                            found: found,
+                            callee: callee.clone(),
+                            function: self.name.to_owned(),
+                            location: location,
+                            compiler: fl!(),
+                        });
+                    }
+                }
+            }
+        }
+        // every leaf has been checked, so `ty` can't panic any more
+        Ok(value.ty(self.mir, self.function, self.fn_types))
+    }
+
+    fn pointee_ty(&self,
+                  ptr: &ValueLeaf<'t>,
+                  location: Location)
+                  -> Result<Type<'t>, ValidationError<'t>> {
+        let ty = try!(self.leaf_ty(ptr, location));
+        match *ty.0 {
+            TypeVariant::Reference(inner, _) | TypeVariant::RawPointer(inner, _) => Ok(inner),
+            _ => {
+                Err(ValidationError::DerefOfNonReference {
+                    ty: ty,
+                    leaf: format!("{}", ptr),
+                    function: self.name.to_owned(),
+                    location: location,
+                    compiler: fl!(),
+                })
+            }
+        }
+    }
+
+    fn leaf_ty(&self,
+               leaf: &ValueLeaf<'t>,
+               location: Location)
+               -> Result<Type<'t>, ValidationError<'t>> {
+        let in_range = match *leaf {
+            ValueLeaf::Const(_) => true,
+            ValueLeaf::Parameter(ref par) => (par.0 as usize) < self.function.ty.input().len(),
+            ValueLeaf::Variable(ref var) => (var.0 as usize) < self.function.locals.len(),
+            ValueLeaf::Temporary(ref tmp) => (tmp.0 as usize) < self.function.temporaries.len(),
+        };
+        if in_range {
+            Ok(leaf.ty(self.mir, self.function))
+        } else {
+            Err(ValidationError::LeafOutOfRange {
+                leaf: format!("{}", leaf),
+                function: self.name.to_owned(),
+                location: location,
+                compiler: fl!(),
+            })
+        }
+    }
+}
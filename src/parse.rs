@@ -1,13 +1,20 @@
+use std;
 use std::str;
+use std::collections::HashMap;
 use ast;
-use ast::expr::{Stmt, Expr, ExprKind};
-use ty::{self, Type, TypeContext};
+use ast::expr::{Stmt, Expr, ExprKind, Pattern, Message};
+use ty::{self, Type, TypeContext, Mutability};
 use Either::{self, Left, Right};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     // Item
     KeywordFn,
+    KeywordConst,
+    KeywordStatic,
+    KeywordType,
+    KeywordExtern,
+    Hash,
 
     // Statement
     KeywordLet,
@@ -19,11 +26,20 @@ pub enum Token {
     KeywordFalse,
     KeywordIf,
     KeywordElse,
+    KeywordAs,
+    KeywordMut,
+    KeywordUnsafe,
     Ident(String),
     Integer {
         value: u64,
         suffix: String,
     },
+    Float {
+        value: f64,
+        suffix: String,
+    },
+    Char(char),
+    Str(String),
 
     Operand(Operand),
 
@@ -31,18 +47,30 @@ pub enum Token {
     OpenParen,
     CloseParen,
     OpenBrace,
+    OpenBracket,
+    CloseBracket,
     Semicolon,
     Colon,
+    // `::`, before the type arguments of a call
+    ColonColon,
     Comma,
+    Dot,
     SkinnyArrow,
     Equals,
+    // `+=`, `<<=`, etc.
+    CompoundAssign(Operand),
     Eof,
 }
 
 impl Token {
     pub fn ty(&self) -> TokenType {
         match *self {
-            Token::KeywordFn => TokenType::Item,
+            Token::KeywordFn |
+            Token::KeywordConst |
+            Token::KeywordStatic |
+            Token::KeywordType |
+            Token::KeywordExtern |
+            Token::Hash => TokenType::Item,
 
             Token::KeywordLet | Token::CloseBrace => TokenType::Statement,
 
@@ -50,20 +78,31 @@ impl Token {
             Token::KeywordTrue |
             Token::KeywordFalse |
             Token::KeywordIf |
+            Token::KeywordUnsafe |
             Token::Ident(_) |
-            Token::Integer { .. } => TokenType::Expression,
+            Token::Integer { .. } |
+            Token::Float { .. } |
+            Token::Char(_) |
+            Token::Str(_) => TokenType::Expression,
 
             Token::Operand(_) => TokenType::Operand,
 
             Token::KeywordElse |
+            Token::KeywordAs |
+            Token::KeywordMut |
             Token::OpenParen |
             Token::CloseParen |
             Token::OpenBrace |
+            Token::OpenBracket |
+            Token::CloseBracket |
             Token::Semicolon |
             Token::Colon |
+            Token::ColonColon |
+            Token::Dot |
             Token::SkinnyArrow |
             Token::Comma |
             Token::Equals |
+            Token::CompoundAssign(_) |
             Token::Eof => TokenType::Misc,
         }
     }
@@ -130,11 +169,13 @@ impl Operand {
                 rhs: Box::new(rhs),
             },
             ty: Type::infer(ctxt),
+            line: 0,
+            offset: 0,
         }
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub enum TokenType {
     Item,
     Statement,
@@ -146,21 +187,51 @@ pub enum TokenType {
     AnyOf(Vec<Token>),
 }
 
+// comments are skipped by the parser, but kept for the formatter
+#[derive(Clone, Debug)]
+pub struct Comment {
+    pub line: u32,
+    // including the `//`, or the `/*` and `*/`
+    pub text: String,
+    // whether there's a token before it on the same line
+    pub trailing: bool,
+}
+
+#[derive(Clone)]
 pub struct Lexer<'src> {
+    source: &'src str,
     src: str::Chars<'src>,
     readahead: Vec<char>,
     line: u32,
+    // what the formatter needs to know about the source, besides the tokens
+    comments: Vec<Comment>,
+    blank_lines: Vec<u32>,
+    line_is_blank: bool,
+    last_token_line: u32,
+    // the offset of the last token lexed
+    token_start: usize,
 }
 
 impl<'src> Lexer<'src> {
     pub fn new(src: &str) -> Lexer {
         Lexer {
+            source: src,
             src: src.chars(),
             readahead: Vec::with_capacity(1),
             line: 1,
+            comments: Vec::new(),
+            blank_lines: Vec::new(),
+            line_is_blank: true,
+            last_token_line: 0,
+            token_start: 0,
         }
     }
 
+    // the line the lexer is on; after `next_token`, the line of that token
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
     fn ident(&mut self, first: char) -> String {
         let mut ret = String::new();
         ret.push(first);
@@ -193,46 +264,211 @@ impl<'src> Lexer<'src> {
         c >= '0' && c <= '9'
     }
 
-    fn block_comment(&mut self) -> Result<(), ParserError> {
+    // returns whether any digits were pushed. `_` may be used as a separator,
+    // and is dropped
+    fn digits(&mut self, string: &mut String, radix: u32) -> bool {
+        let len = string.len();
         loop {
-            let c = self.getc();
-            if c == Some('*') {
-                let c = self.getc();
-                if c == Some('/') {
-                    return Ok(());
-                } else if c == Some('\n') {
-                    self.line += 1;
-                } else if c == None {
-                    return Err(ParserError::UnclosedComment);
-                }
-            } else if c == Some('/') {
-                let c = self.getc();
-                if c == Some('*') {
-                    try!(self.block_comment())
-                } else if c == Some('\n') {
-                    self.line += 1;
-                } else if c == None {
-                    return Err(ParserError::UnclosedComment);
+            match self.getc() {
+                Some(c) if c.is_digit(radix) => string.push(c),
+                Some('_') => {}
+                Some(c) => {
+                    self.ungetc(c);
+                    break;
                 }
-            } else if c == Some('\n') {
+                None => break,
+            }
+        }
+        string.len() != len
+    }
+
+    fn suffix(&mut self, suffix: &mut String) {
+        loop {
+            match self.getc() {
+                Some(c) if Self::is_ident(c) => suffix.push(c),
+                Some(c) => {
+                    self.ungetc(c);
+                    break;
+                }
+                None => break,
+            }
+        }
+    }
+
+    // `digits` has already been checked to only contain digits of `radix`, so
+    // the only way this can fail is if the literal doesn't fit into a u64
+    fn integer(&self, digits: String, radix: u32, suffix: String) -> Result<Token, ParserError> {
+        match u64::from_str_radix(&digits, radix) {
+            Ok(value) => {
+                Ok(Token::Integer {
+                    value: value,
+                    suffix: suffix,
+                })
+            }
+            Err(_) => {
+                Err(ParserError::IntegerLiteralTooLarge {
+                    literal: digits,
+                    line: self.line,
+                    compiler: fl!(),
+                })
+            }
+        }
+    }
+
+    // the opening `'` has already been eaten
+    fn char_literal(&mut self) -> Result<char, ParserError> {
+        let c = match self.getc() {
+            Some('\\') => try!(self.escape()),
+            Some(c) if c != '\'' && c != '\n' => c,
+            _ => return Err(self.invalid_char_literal()),
+        };
+        match self.getc() {
+            Some('\'') => Ok(c),
+            _ => Err(self.invalid_char_literal()),
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParserError> {
+        match self.getc() {
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some('0') => Ok('\0'),
+            Some('\\') => Ok('\\'),
+            Some('\'') => Ok('\''),
+            Some('"') => Ok('"'),
+            // `\x7F`; only ascii
+            Some('x') => {
+                let mut value = 0;
+                for _ in 0..2 {
+                    match self.getc().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => value = value * 16 + digit,
+                        None => return Err(self.invalid_char_literal()),
+                    }
+                }
+                if value > 0x7F {
+                    return Err(self.invalid_char_literal());
+                }
+                Ok(value as u8 as char)
+            }
+            // `\u{1F600}`; up to six hex digits, and it must be a unicode
+            // scalar value
+            Some('u') => {
+                if self.getc() != Some('{') {
+                    return Err(self.invalid_char_literal());
+                }
+                let mut value = 0u32;
+                let mut digits = 0;
+                loop {
+                    match self.getc() {
+                        Some('}') if digits != 0 => break,
+                        Some(c) if digits < 6 && c.is_digit(16) => {
+                            value = value * 16 + c.to_digit(16).unwrap();
+                            digits += 1;
+                        }
+                        _ => return Err(self.invalid_char_literal()),
+                    }
+                }
+                match std::char::from_u32(value) {
+                    Some(c) => Ok(c),
+                    None => Err(self.invalid_char_literal()),
+                }
+            }
+            _ => Err(self.invalid_char_literal()),
+        }
+    }
+
+    // after the opening `"`; the same escapes as in char literals
+    fn string_literal(&mut self) -> Result<String, ParserError> {
+        let mut ret = String::new();
+        loop {
+            match self.getc() {
+                Some('"') => return Ok(ret),
+                Some('\\') => ret.push(try!(self.escape())),
+                Some('\n') | None => {
+                    return Err(ParserError::InvalidStringLiteral {
+                        line: self.line,
+                        compiler: fl!(),
+                    })
+                }
+                Some(c) => ret.push(c),
+            }
+        }
+    }
+
+    fn invalid_char_literal(&self) -> ParserError {
+        ParserError::InvalidCharLiteral {
+            line: self.line,
+            compiler: fl!(),
+        }
+    }
+
+    // after the `/*`; comments nest
+    fn block_comment(&mut self, text: &mut String) -> Result<(), ParserError> {
+        loop {
+            let c = match self.getc() {
+                Some(c) => c,
+                None => return Err(ParserError::UnclosedComment),
+            };
+            text.push(c);
+            if c == '*' {
+                match self.getc() {
+                    Some('/') => {
+                        text.push('/');
+                        return Ok(());
+                    }
+                    Some(c) => self.ungetc(c),
+                    None => {}
+                }
+            } else if c == '/' {
+                match self.getc() {
+                    Some('*') => {
+                        text.push('*');
+                        try!(self.block_comment(text));
+                    }
+                    Some(c) => self.ungetc(c),
+                    None => {}
+                }
+            } else if c == '\n' {
                 self.line += 1;
-            } else if c == None {
-                return Err(ParserError::UnclosedComment);
             }
         }
     }
 
-    fn line_comment(&mut self) {
+    // after the `//`
+    fn line_comment(&mut self, text: &mut String) {
         loop {
             match self.getc() {
                 Some('\n') => {
                     self.line += 1;
+                    self.line_is_blank = true;
                     break;
                 }
                 None => break,
-                Some(_) => {}
+                Some(c) => text.push(c),
             }
         }
+        let len = text.trim_right().len();
+        text.truncate(len);
+    }
+
+    fn comment(&mut self, line: u32, text: String, trailing: bool) {
+        self.comments.push(Comment {
+            line: line,
+            text: text,
+            trailing: trailing,
+        });
+    }
+
+    // the offset into the source of the next character
+    fn position(&self) -> usize {
+        let ungot: usize = self.readahead.iter().map(|c| c.len_utf8()).sum();
+        self.source.len() - self.src.as_str().len() - ungot
+    }
+
+    // the last token lexed, as it was written
+    fn spelling(&self) -> &'src str {
+        &self.source[self.token_start..self.position()]
     }
 
     fn getc(&mut self) -> Option<char> {
@@ -260,13 +496,27 @@ impl<'src> Lexer<'src> {
                 self.ungetc(c);
                 break;
             } else if c == '\n' {
+                if self.line_is_blank {
+                    self.blank_lines.push(self.line);
+                }
                 self.line += 1;
+                self.line_is_blank = true;
             }
         }
 
         Some(())
     }
 
+    // `op` has already been eaten; a following `=` turns it into `op=`
+    fn maybe_compound(&mut self, op: Operand) -> Token {
+        match self.getc() {
+            Some('=') => return Token::CompoundAssign(op),
+            Some(c) => self.ungetc(c),
+            None => {}
+        }
+        Token::Operand(op)
+    }
+
     fn is_whitespace(c: char) -> bool {
         c == '\t' || c == '\n' || c == '\r' || c == ' '
     }
@@ -277,22 +527,41 @@ impl<'src> Lexer<'src> {
             Some(c) => c,
             None => return Ok(Token::Eof),
         };
+        self.token_start = self.position() - first.len_utf8();
+        let line = self.line;
+        let trailing = self.last_token_line == line;
+        self.last_token_line = line;
+        self.line_is_blank = false;
         match first {
             '(' => Ok(Token::OpenParen),
             ')' => Ok(Token::CloseParen),
             '{' => Ok(Token::OpenBrace),
             '}' => Ok(Token::CloseBrace),
+            '[' => Ok(Token::OpenBracket),
+            ']' => Ok(Token::CloseBracket),
+            '#' => Ok(Token::Hash),
             ';' => Ok(Token::Semicolon),
-            ':' => Ok(Token::Colon),
+            ':' => {
+                match self.getc() {
+                    Some(':') => return Ok(Token::ColonColon),
+                    Some(c) => self.ungetc(c),
+                    None => {}
+                }
+                Ok(Token::Colon)
+            }
             ',' => Ok(Token::Comma),
-            '*' => Ok(Token::Operand(Operand::Mul)),
-            '%' => Ok(Token::Operand(Operand::Rem)),
-            '+' => Ok(Token::Operand(Operand::Plus)),
+            '.' => Ok(Token::Dot),
+            '*' => Ok(self.maybe_compound(Operand::Mul)),
+            '%' => Ok(self.maybe_compound(Operand::Rem)),
+            '+' => Ok(self.maybe_compound(Operand::Plus)),
             '-' => {
                 match self.getc() {
                     Some('>') => {
                         return Ok(Token::SkinnyArrow);
                     }
+                    Some('=') => {
+                        return Ok(Token::CompoundAssign(Operand::Minus));
+                    }
                     Some(c) => self.ungetc(c),
                     None => {}
                 }
@@ -301,13 +570,20 @@ impl<'src> Lexer<'src> {
             '/' => {
                 match self.getc() {
                     Some('*') => {
-                        try!(self.block_comment());
+                        let mut text = "/*".to_owned();
+                        try!(self.block_comment(&mut text));
+                        self.comment(line, text, trailing);
                         return self.next_token();
                     }
                     Some('/') => {
-                        self.line_comment();
+                        let mut text = "//".to_owned();
+                        self.line_comment(&mut text);
+                        self.comment(line, text, trailing);
                         return self.next_token();
                     }
+                    Some('=') => {
+                        return Ok(Token::CompoundAssign(Operand::Div));
+                    }
                     Some(c) => {
                         self.ungetc(c);
                     }
@@ -329,7 +605,7 @@ impl<'src> Lexer<'src> {
             '<' => {
                 match self.getc() {
                     Some('<') => {
-                        return Ok(Token::Operand(Operand::Shl));
+                        return Ok(self.maybe_compound(Operand::Shl));
                     }
                     Some('=') => {
                         return Ok(Token::Operand(Operand::LessThanEquals));
@@ -342,7 +618,7 @@ impl<'src> Lexer<'src> {
             '>' => {
                 match self.getc() {
                     Some('>') => {
-                        return Ok(Token::Operand(Operand::Shr));
+                        return Ok(self.maybe_compound(Operand::Shr));
                     }
                     Some('=') => {
                         return Ok(Token::Operand(Operand::GreaterThanEquals));
@@ -367,6 +643,9 @@ impl<'src> Lexer<'src> {
                     Some('&') => {
                         return Ok(Token::Operand(Operand::AndAnd));
                     }
+                    Some('=') => {
+                        return Ok(Token::CompoundAssign(Operand::And));
+                    }
                     Some(c) => self.ungetc(c),
                     None => {}
                 }
@@ -377,21 +656,33 @@ impl<'src> Lexer<'src> {
                     Some('|') => {
                         return Ok(Token::Operand(Operand::OrOr));
                     }
+                    Some('=') => {
+                        return Ok(Token::CompoundAssign(Operand::Or));
+                    }
                     Some(c) => self.ungetc(c),
                     None => {}
                 }
                 Ok(Token::Operand(Operand::Or))
             }
-            '^' => Ok(Token::Operand(Operand::Xor)),
+            '^' => Ok(self.maybe_compound(Operand::Xor)),
+            '\'' => self.char_literal().map(Token::Char),
+            '"' => self.string_literal().map(Token::Str),
 
             c if Self::is_start_of_ident(c) => {
                 let ident = self.ident(c);
                 match &ident[..] {
                     "fn" => return Ok(Token::KeywordFn),
+                    "const" => return Ok(Token::KeywordConst),
+                    "static" => return Ok(Token::KeywordStatic),
+                    "type" => return Ok(Token::KeywordType),
+                    "extern" => return Ok(Token::KeywordExtern),
                     "return" => return Ok(Token::KeywordReturn),
                     "let" => return Ok(Token::KeywordLet),
                     "if" => return Ok(Token::KeywordIf),
                     "else" => return Ok(Token::KeywordElse),
+                    "as" => return Ok(Token::KeywordAs),
+                    "mut" => return Ok(Token::KeywordMut),
+                    "unsafe" => return Ok(Token::KeywordUnsafe),
                     "true" => return Ok(Token::KeywordTrue),
                     "false" => return Ok(Token::KeywordFalse),
                     _ => {}
@@ -401,36 +692,81 @@ impl<'src> Lexer<'src> {
             }
             c if Self::is_integer(c) => {
                 let mut string = String::new();
-                string.push(c);
                 let mut suffix = String::new();
-                loop {
+                // `0x`, `0o` and `0b` literals are always integers
+                let prefix = if c == '0' {
                     match self.getc() {
-                        Some(c @ '0'...'9') => string.push(c),
+                        Some(c @ 'x') | Some(c @ 'o') | Some(c @ 'b') => Some(c),
                         Some(c) => {
                             self.ungetc(c);
-                            break;
+                            None
                         }
-                        None => break,
+                        None => None,
+                    }
+                } else {
+                    None
+                };
+                if let Some(prefix) = prefix {
+                    let radix = match prefix {
+                        'x' => 16,
+                        'o' => 8,
+                        _ => 2,
+                    };
+                    if !self.digits(&mut string, radix) {
+                        return Err(ParserError::InvalidIntegerLiteral {
+                            literal: format!("0{}", prefix),
+                            line: self.line,
+                            compiler: fl!(),
+                        });
                     }
+                    self.suffix(&mut suffix);
+                    return self.integer(string, radix, suffix);
                 }
-                loop {
-                    match self.getc() {
-                        Some(c) if Self::is_ident(c) => suffix.push(c),
-                        Some(c) => {
-                            self.ungetc(c);
-                            break;
+                string.push(c);
+                self.digits(&mut string, 10);
+                let mut is_float = false;
+                match self.getc() {
+                    Some('.') => {
+                        is_float = true;
+                        string.push('.');
+                        self.digits(&mut string, 10);
+                    }
+                    Some(c) => self.ungetc(c),
+                    None => {}
+                }
+                match self.getc() {
+                    Some('e') | Some('E') => {
+                        is_float = true;
+                        string.push('e');
+                        match self.getc() {
+                            Some(c @ '+') | Some(c @ '-') => string.push(c),
+                            Some(c) => self.ungetc(c),
+                            None => {}
+                        }
+                        if !self.digits(&mut string, 10) {
+                            return Err(ParserError::InvalidFloatLiteral {
+                                literal: string,
+                                line: self.line,
+                                compiler: fl!(),
+                            });
                         }
-                        None => break,
                     }
+                    Some(c) => self.ungetc(c),
+                    None => {}
+                }
+                self.suffix(&mut suffix);
+
+                if is_float {
+                    let value = string.parse::<f64>()
+                                      .expect("we pushed something which wasn't a float onto a \
+                                               string");
+                    return Ok(Token::Float {
+                        value: value,
+                        suffix: suffix,
+                    });
                 }
 
-                let value = string.parse::<u64>()
-                                  .expect("we pushed something which wasn't 0...9 onto a string");
-
-                Ok(Token::Integer {
-                    value: value,
-                    suffix: suffix,
-                })
+                self.integer(string, 10, suffix)
             }
 
             i => {
@@ -449,8 +785,15 @@ pub enum ParserError {
     ExpectedEof,
 
     UnclosedComment,
-    UnknownType {
-        found: String,
+    // a type name that is neither a builtin type nor a type alias
+    UndefinedType {
+        name: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    // `type A = B; type B = A;`
+    RecursiveTypeAlias {
+        name: String,
         line: u32,
         compiler: (&'static str, u32),
     },
@@ -468,6 +811,23 @@ pub enum ParserError {
         function: String,
         compiler: (&'static str, u32),
     },
+    // the same name twice in one `let` pattern, as in `let (a, a) = ...`
+    DuplicatedBinding {
+        name: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    // a const or static with the same name as another item, or two type
+    // aliases with the same name
+    DuplicatedItem {
+        name: String,
+        compiler: (&'static str, u32),
+    },
+    InvalidAttribute {
+        attribute: ast::Attribute,
+        function: String,
+        compiler: (&'static str, u32),
+    },
     UnexpectedToken {
         found: Token,
         expected: TokenType,
@@ -483,18 +843,103 @@ pub enum ParserError {
         line: u32,
         compiler: (&'static str, u32),
     },
+    InvalidIntegerLiteral {
+        literal: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    IntegerLiteralTooLarge {
+        literal: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    InvalidFloatLiteral {
+        literal: String,
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    InvalidCharLiteral {
+        line: u32,
+        compiler: (&'static str, u32),
+    },
+    // unterminated, or with an invalid escape
+    InvalidStringLiteral {
+        line: u32,
+        compiler: (&'static str, u32),
+    },
 }
 
 pub struct Parser<'src> {
     lexer: Lexer<'src>,
     peekahead: Option<Token>,
+    // the tokens of the right hand side of every `type Name = ...;`; aliases
+    // may be used before they are defined, so they're collected up front
+    aliases: HashMap<String, Vec<Token>>,
+    // the aliases currently being expanded, for cycle detection
+    expanding: Vec<String>,
+    // while expanding an alias, tokens come from here (in reverse order)
+    // instead of from the lexer
+    replay: Option<Vec<Token>>,
+    // for the formatter, type names aren't resolved; each is parsed as an
+    // unresolved type whose id is its index in here
+    unresolved: Option<Vec<String>>,
+    // the spelling of every literal, by where its token starts, for the
+    // formatter
+    literals: HashMap<usize, String>,
 }
 
 impl<'src> Parser<'src> {
     pub fn new(lexer: Lexer<'src>) -> Self {
+        let aliases = Self::collect_aliases(lexer.clone());
+        Parser {
+            lexer: lexer,
+            peekahead: None,
+            aliases: aliases,
+            expanding: Vec::new(),
+            replay: None,
+            unresolved: None,
+            literals: HashMap::new(),
+        }
+    }
+
+    // a parser which leaves type names as they're written, for the formatter
+    pub fn unresolved(lexer: Lexer<'src>) -> Self {
         Parser {
             lexer: lexer,
             peekahead: None,
+            aliases: HashMap::new(),
+            expanding: Vec::new(),
+            replay: None,
+            unresolved: Some(Vec::new()),
+            literals: HashMap::new(),
+        }
+    }
+
+    // `type` is only valid as an item, so any `type Name =` is an alias;
+    // lexer errors are left for the real parse to report
+    fn collect_aliases(mut lexer: Lexer) -> HashMap<String, Vec<Token>> {
+        let mut aliases = HashMap::new();
+        loop {
+            match lexer.next_token() {
+                Ok(Token::KeywordType) => {}
+                Ok(Token::Eof) | Err(_) => return aliases,
+                Ok(_) => continue,
+            }
+            let name = match lexer.next_token() {
+                Ok(Token::Ident(name)) => name,
+                _ => continue,
+            };
+            if let Ok(Token::Equals) = lexer.next_token() {
+                let mut tokens = Vec::new();
+                loop {
+                    match lexer.next_token() {
+                        Ok(Token::Semicolon) | Ok(Token::Eof) | Err(_) => break,
+                        Ok(tok) => tokens.push(tok),
+                    }
+                }
+                tokens.reverse();
+                aliases.entry(name).or_insert(tokens);
+            }
         }
     }
 
@@ -503,20 +948,58 @@ impl<'src> Parser<'src> {
         self.lexer.line
     }
 
+    pub fn comments(&self) -> &[Comment] {
+        &self.lexer.comments
+    }
+
+    // the lines with nothing but whitespace on them
+    pub fn blank_lines(&self) -> &[u32] {
+        &self.lexer.blank_lines
+    }
+
+    // the name of a type parsed by an `unresolved` parser
+    pub fn type_name(&self, id: u32) -> &str {
+        &self.unresolved.as_ref().expect("ICE: types were resolved")[id as usize]
+    }
+
+    // how the literal starting at `offset` was written, if it was
+    pub fn literal(&self, offset: usize) -> Option<&str> {
+        self.literals.get(&offset).map(|s| &s[..])
+    }
+
+    // records how the literal just lexed was written, and returns where it
+    // starts
+    fn record_literal(&mut self) -> usize {
+        let offset = self.lexer.token_start;
+        self.literals.insert(offset, self.lexer.spelling().to_owned());
+        offset
+    }
+
+    fn next_token(&mut self) -> Result<Token, ParserError> {
+        match self.replay {
+            Some(ref mut tokens) => Ok(tokens.pop().unwrap_or(Token::Eof)),
+            None => self.lexer.next_token(),
+        }
+    }
     fn get_token(&mut self) -> Result<Token, ParserError> {
         match self.peekahead.take() {
             Some(tok) => Ok(tok),
-            None => self.lexer.next_token(),
+            None => self.next_token(),
         }
     }
     fn peek_token(&mut self) -> Result<Token, ParserError> {
         let tok = match self.peekahead {
             Some(ref tok) => return Ok(tok.clone()),
-            None => try!(self.lexer.next_token()),
+            None => try!(self.next_token()),
         };
         self.peekahead = Some(tok.clone());
         Ok(tok)
     }
+    // the line of the next token
+    pub fn peek_line(&mut self) -> Result<u32, ParserError> {
+        try!(self.peek_token());
+        Ok(self.line())
+    }
     fn unget_token(&mut self, token: Token) {
         assert!(self.peekahead.is_none(),
                 "current: {:?}, attempted to unget: {:?}, line: {}",
@@ -527,8 +1010,19 @@ impl<'src> Parser<'src> {
     }
 
     pub fn item<'t>(&mut self, ctxt: &'t TypeContext<'t>) -> Result<ast::Item<'t>, ParserError> {
+        let attributes = try!(self.parse_attributes());
         match try!(self.get_token()) {
-            Token::KeywordFn => self.function(ctxt),
+            Token::KeywordFn => self.function(attributes, ctxt),
+            Token::KeywordConst => self.global(None, attributes, ctxt),
+            Token::KeywordStatic => {
+                let mutability = try!(self.parse_mutability());
+                self.global(Some(mutability), attributes, ctxt)
+            }
+            Token::KeywordType => self.type_alias(attributes, ctxt),
+            Token::KeywordExtern => {
+                try!(self.eat(Token::KeywordFn, line!()));
+                self.extern_function(attributes, ctxt)
+            }
             Token::Eof => Err(ParserError::ExpectedEof),
             tok => {
                 Err(ParserError::UnexpectedToken {
@@ -630,42 +1124,90 @@ impl<'src> Parser<'src> {
         }
     }
 
+    // #[name] or #[name(arg, ...)]
+    fn parse_attributes(&mut self) -> Result<Vec<ast::Attribute>, ParserError> {
+        let mut attributes = Vec::new();
+        while let Some(_) = try!(self.maybe_eat(Token::Hash)) {
+            try!(self.eat(Token::OpenBracket, line!()));
+            let name = try!(self.parse_ident(line!()));
+            let mut args = Vec::new();
+            if let Some(_) = try!(self.maybe_eat(Token::OpenParen)) {
+                if let Token::Ident(_) = try!(self.peek_token()) {
+                    args.push(try!(self.parse_ident(line!())));
+                    while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                        args.push(try!(self.parse_ident(line!())));
+                    }
+                }
+                try!(self.eat(Token::CloseParen, line!()));
+            }
+            try!(self.eat(Token::CloseBracket, line!()));
+            attributes.push(ast::Attribute::new(name, args));
+        }
+        Ok(attributes)
+    }
+
     fn parse_ty<'t>(&mut self,
                     ctxt: &'t TypeContext<'t>,
                     line: u32)
                     -> Result<Type<'t>, ParserError> {
         match try!(self.get_token()) {
             Token::Ident(s) => {
+                if let Some(ty) = int_ty(&s, ctxt).or_else(|| float_ty(&s, ctxt)) {
+                    return Ok(ty);
+                }
                 match &*s {
-                    "s8" => Ok(Type::sint(ty::Int::I8, ctxt)),
-                    "s16" => Ok(Type::sint(ty::Int::I16, ctxt)),
-                    "s32" => Ok(Type::sint(ty::Int::I32, ctxt)),
-                    "s64" => Ok(Type::sint(ty::Int::I64, ctxt)),
-                    "u8" => Ok(Type::uint(ty::Int::I8, ctxt)),
-                    "u16" => Ok(Type::uint(ty::Int::I16, ctxt)),
-                    "u32" => Ok(Type::uint(ty::Int::I32, ctxt)),
-                    "u64" => Ok(Type::uint(ty::Int::I64, ctxt)),
-                    "bool" => Ok(Type::bool(ctxt)),
-                    s => {
-                        Err(ParserError::UnknownType {
-                            found: s.to_owned(),
-                            line: line,
-                            compiler: fl!(),
-                        })
-                    }
+                    "char" => return Ok(Type::char(ctxt)),
+                    "bool" => return Ok(Type::bool(ctxt)),
+                    _ => {}
+                }
+                if let Some(ref mut names) = self.unresolved {
+                    names.push(s);
+                    return Ok(Type::unresolved(names.len() as u32 - 1, ctxt));
                 }
+                self.expand_alias(s, ctxt, line)
             }
             Token::OpenParen => {
+                if let Some(_) = try!(self.maybe_eat(Token::CloseParen)) {
+                    return Ok(Type::unit(ctxt));
+                }
+                // `(ty)` is just `ty`
+                let mut elements = vec![try!(self.parse_ty(ctxt, line))];
+                while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                    elements.push(try!(self.parse_ty(ctxt, line)));
+                }
                 try!(self.eat(Token::CloseParen, line!()));
-                Ok(Type::unit(ctxt))
+                if elements.len() == 1 {
+                    Ok(elements.pop().unwrap())
+                } else {
+                    Ok(Type::tuple(elements, ctxt))
+                }
             }
             Token::Operand(Operand::And) => {
+                let mutability = try!(self.parse_mutability());
                 let inner = try!(self.parse_ty(ctxt, line));
-                Ok(Type::ref_(inner, ctxt))
+                Ok(Type::reference(inner, mutability, ctxt))
             }
             Token::Operand(Operand::AndAnd) => {
+                let mutability = try!(self.parse_mutability());
                 let inner = try!(self.parse_ty(ctxt, line));
-                Ok(Type::ref_(Type::ref_(inner, ctxt), ctxt))
+                Ok(Type::ref_(Type::reference(inner, mutability, ctxt), ctxt))
+            }
+            Token::Operand(Operand::Mul) => {
+                let mutability = match try!(self.get_token()) {
+                    Token::KeywordConst => Mutability::Immutable,
+                    Token::KeywordMut => Mutability::Mutable,
+                    tok => {
+                        return Err(ParserError::UnexpectedToken {
+                            found: tok,
+                            expected: TokenType::AnyOf(vec![Token::KeywordConst,
+                                                            Token::KeywordMut]),
+                            line: self.line(),
+                            compiler: (file!(), line),
+                        })
+                    }
+                };
+                let inner = try!(self.parse_ty(ctxt, line));
+                Ok(Type::raw_pointer(inner, mutability, ctxt))
             }
             tok => {
                 Err(ParserError::UnexpectedToken {
@@ -678,23 +1220,192 @@ impl<'src> Parser<'src> {
         }
     }
 
+    // aliases are expanded by parsing their tokens in place of the name, so
+    // they're interned as, and therefore equal to, the aliased type
+    fn expand_alias<'t>(&mut self,
+                        name: String,
+                        ctxt: &'t TypeContext<'t>,
+                        line: u32)
+                        -> Result<Type<'t>, ParserError> {
+        let tokens = match self.aliases.get(&name) {
+            Some(tokens) => tokens.clone(),
+            None => {
+                return Err(ParserError::UndefinedType {
+                    name: name,
+                    line: self.line(),
+                    compiler: fl!(),
+                })
+            }
+        };
+        if self.expanding.contains(&name) {
+            return Err(ParserError::RecursiveTypeAlias {
+                name: name,
+                line: self.line(),
+                compiler: fl!(),
+            });
+        }
+        self.expanding.push(name);
+        let peekahead = self.peekahead.take();
+        let outer = std::mem::replace(&mut self.replay, Some(tokens));
+        let ty = self.parse_ty(ctxt, line);
+        let ty = match ty {
+            Ok(ty) => self.eat(Token::Eof, line!()).map(|_| ty),
+            Err(e) => Err(e),
+        };
+        self.replay = outer;
+        self.peekahead = peekahead;
+        self.expanding.pop();
+        ty
+    }
+
+    // a single expression, followed by any number of `as` casts
     fn maybe_parse_single_expr<'t>(&mut self,
                                    ctxt: &'t TypeContext<'t>)
                                    -> Result<Option<Expr<'t>>, ParserError> {
+        let mut expr = match try!(self.maybe_parse_unary_expr(ctxt)) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        while let Some(_) = try!(self.maybe_eat(Token::KeywordAs)) {
+            let line = self.line();
+            let ty = try!(self.parse_ty(ctxt, line!()));
+            expr = Expr::cast(expr, ty).at(line);
+        }
+        Ok(Some(expr))
+    }
+
+    // unary operators bind more tightly than `as`, so `-x as u8` is
+    // `(-x) as u8`, and method calls more tightly still, so `-p.offset(1)` is
+    // `-(p.offset(1))`
+    fn maybe_parse_unary_expr<'t>(&mut self,
+                                  ctxt: &'t TypeContext<'t>)
+                                  -> Result<Option<Expr<'t>>, ParserError> {
+        let mut expr = match try!(self.maybe_parse_prefix_expr(ctxt)) {
+            Some(e) => e,
+            None => return Ok(None),
+        };
+        // `recv.name::<T>(args)` is `name::<T>(recv, args)`, and `tuple.0`
+        // is an element of a tuple
+        while let Some(_) = try!(self.maybe_eat(Token::Dot)) {
+            let line = self.line();
+            let mut tok = try!(self.get_token());
+            // `t.0.1` is lexed as `t`, `.`, `0.1`; the second field is taken
+            // as if it were an integer after another `.`
+            let fields = match tok {
+                Token::Float { ref suffix, .. } if suffix.is_empty() => {
+                    Self::field_pair(self.lexer.spelling())
+                }
+                _ => None,
+            };
+            if let Some((first, second)) = fields {
+                expr = Expr::field(expr, first, ctxt).at(line);
+                tok = Token::Integer {
+                    value: second as u64,
+                    suffix: String::new(),
+                };
+            }
+            match tok {
+                Token::Ident(name) => {
+                    let ty_args = try!(self.parse_ty_args(ctxt));
+                    try!(self.eat(Token::OpenParen, line!()));
+                    let (mut args, message) = try!(self.parse_call_args(ctxt));
+                    args.insert(0, expr);
+                    expr = Expr::method_call(name, ty_args, args, message, ctxt).at(line);
+                }
+                Token::Integer { value, ref suffix } if suffix.is_empty() && value <= 0xFFFF => {
+                    expr = Expr::field(expr, value as u32, ctxt).at(line);
+                }
+                tok => {
+                    return Err(ParserError::UnexpectedToken {
+                        found: tok,
+                        expected: TokenType::AnyOf(vec![Token::Ident(String::new()),
+                                                        Token::Integer {
+                                                            value: 0,
+                                                            suffix: String::new(),
+                                                        }]),
+                        line: self.line(),
+                        compiler: fl!(),
+                    })
+                }
+            }
+        }
+        Ok(Some(expr))
+    }
+
+    // the two field indices in a float literal like `0.1`, if it's only that
+    fn field_pair(spelling: &str) -> Option<(u32, u32)> {
+        let index = |digits: &str| {
+            if !digits.is_empty() && digits.chars().all(|c| c.is_digit(10)) {
+                digits.parse::<u32>().ok().and_then(|i| if i <= 0xFFFF { Some(i) } else { None })
+            } else {
+                None
+            }
+        };
+        let mut parts = spelling.splitn(2, '.');
+        match (parts.next().and_then(&index), parts.next().and_then(&index)) {
+            (Some(first), Some(second)) => Some((first, second)),
+            _ => None,
+        }
+    }
+
+    // the arguments of a call, after the `(`, and the string literal which may
+    // come last; only the panicking builtins take one. Eats the `)`
+    fn parse_call_args<'t>(&mut self,
+                           ctxt: &'t TypeContext<'t>)
+                           -> Result<(Vec<Expr<'t>>, Option<Message>), ParserError> {
+        let mut args = Vec::new();
+        let mut message = try!(self.maybe_parse_message());
+        if message.is_none() {
+            if let Some(e) = try!(self.maybe_parse_expr(ctxt)) {
+                args.push(e);
+                while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                    message = try!(self.maybe_parse_message());
+                    if message.is_some() {
+                        break;
+                    }
+                    args.push(try!(self.parse_expr(ctxt, line!())));
+                }
+            }
+        }
+        try!(self.eat(Token::CloseParen, line!()));
+        Ok((args, message))
+    }
+
+    fn maybe_parse_message(&mut self) -> Result<Option<Message>, ParserError> {
         match try!(self.get_token()) {
+            Token::Str(s) => {
+                Ok(Some(Message {
+                    text: s,
+                    offset: self.record_literal(),
+                }))
+            }
+            tok => {
+                self.unget_token(tok);
+                Ok(None)
+            }
+        }
+    }
+
+    fn maybe_parse_prefix_expr<'t>(&mut self,
+                                   ctxt: &'t TypeContext<'t>)
+                                   -> Result<Option<Expr<'t>>, ParserError> {
+        let tok = try!(self.get_token());
+        let line = self.line();
+        let expr = match tok {
             Token::Ident(name) => {
+                let ty_args = try!(self.parse_ty_args(ctxt));
                 if let Some(_) = try!(self.maybe_eat(Token::OpenParen)) {
-                    let mut args = Vec::new();
-                    if let Some(e) = try!(self.maybe_parse_expr(ctxt)) {
-                        args.push(e);
-                        while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
-                            args.push(try!(self.parse_expr(ctxt, line!())));
-                        }
-                    }
-                    try!(self.eat(Token::CloseParen, line!()));
-                    Ok(Some(Expr::call(name, args, ctxt)))
+                    let (args, message) = try!(self.parse_call_args(ctxt));
+                    Expr::call(name, ty_args, args, message, ctxt)
+                } else if ty_args.is_empty() {
+                    Expr::var(name, ctxt)
                 } else {
-                    Ok(Some(Expr::var(name, ctxt)))
+                    return Err(ParserError::UnexpectedToken {
+                        found: try!(self.get_token()),
+                        expected: TokenType::Specific(Token::OpenParen),
+                        line: self.line(),
+                        compiler: fl!(),
+                    });
                 }
             }
             Token::KeywordIf => {
@@ -705,34 +1416,37 @@ impl<'src> Parser<'src> {
                                                                   Token::KeywordIf]),
                                             line!())) {
                         Token::OpenBrace => try!(self.parse_block(ctxt)),
-                        Token::KeywordIf => try!(self.parse_block(ctxt)),
+                        // `else if` is `else { if ... }`
+                        Token::KeywordIf => {
+                            let else_if = try!(self.maybe_parse_prefix_expr(ctxt));
+                            ast::Block::expr(else_if.expect("ICE: `if` is an expression"))
+                        }
                         tok => unreachable!("{:?}", tok),
                     }
                 } else {
                     ast::Block::expr(Expr::unit_lit(ctxt))
                 };
-                Ok(Some(Expr::if_else(condition, if_value, else_value, ctxt)))
+                Expr::if_else(condition, if_value, else_value, ctxt)
             }
             Token::OpenBrace => {
                 self.unget_token(Token::OpenBrace);
-                Ok(Some(Expr::block(try!(self.parse_block(ctxt)), ctxt)))
+                Expr::block(try!(self.parse_block(ctxt)), ctxt)
             }
+            Token::KeywordUnsafe => Expr::unsafe_block(try!(self.parse_block(ctxt)), ctxt),
 
             Token::Integer { value, suffix } => {
-                if suffix == "" {
-                    return Ok(Some(Expr::int_lit(value, ctxt)));
-                }
-                let ty = match &*suffix {
-                    "" => return Ok(Some(Expr::int_lit(value, ctxt))),
-                    "s8" => Type::sint(ty::Int::I8, ctxt),
-                    "s16" => Type::sint(ty::Int::I16, ctxt),
-                    "s32" => Type::sint(ty::Int::I32, ctxt),
-                    "s64" => Type::sint(ty::Int::I64, ctxt),
-                    "u8" => Type::uint(ty::Int::I8, ctxt),
-                    "u16" => Type::uint(ty::Int::I16, ctxt),
-                    "u32" => Type::uint(ty::Int::I32, ctxt),
-                    "u64" => Type::uint(ty::Int::I64, ctxt),
-                    _ => {
+                let offset = self.record_literal();
+                if suffix.is_empty() {
+                    return Ok(Some(Expr::int_lit(value, ctxt).at(line).spelled_at(offset)));
+                }
+                // `1f32` is a float
+                if let Some(ty) = float_ty(&suffix, ctxt) {
+                    let expr = Expr::float_lit_with_ty(value as f64, ty);
+                    return Ok(Some(expr.at(line).spelled_at(offset)));
+                }
+                let ty = match int_ty(&suffix, ctxt) {
+                    Some(ty) => ty,
+                    None => {
                         return Err(ParserError::InvalidSuffix {
                             suffix: suffix.clone(),
                             line: self.line(),
@@ -740,56 +1454,88 @@ impl<'src> Parser<'src> {
                         })
                     }
                 };
-                Ok(Some(Expr::int_lit_with_ty(value, ty)))
+                Expr::int_lit_with_ty(value, ty).spelled_at(offset)
+            }
+            Token::Float { value, suffix } => {
+                let offset = self.record_literal();
+                if suffix.is_empty() {
+                    return Ok(Some(Expr::float_lit(value, ctxt).at(line).spelled_at(offset)));
+                }
+                match float_ty(&suffix, ctxt) {
+                    Some(ty) => Expr::float_lit_with_ty(value, ty).spelled_at(offset),
+                    None => {
+                        return Err(ParserError::InvalidSuffix {
+                            suffix: suffix.clone(),
+                            line: self.line(),
+                            compiler: fl!(),
+                        })
+                    }
+                }
             }
             Token::OpenParen => {
                 if let Some(_) = try!(self.maybe_eat(Token::CloseParen)) {
-                    Ok(Some(Expr::unit_lit(ctxt)))
+                    Expr::unit_lit(ctxt)
                 } else {
                     let expr = try!(self.parse_expr(ctxt, line!()));
-                    try!(self.eat(Token::CloseParen, line!()));
-                    Ok(Some(expr))
+                    if let Some(_) = try!(self.maybe_peek(Token::Comma)) {
+                        let mut elements = vec![expr];
+                        while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                            elements.push(try!(self.parse_expr(ctxt, line!())));
+                        }
+                        try!(self.eat(Token::CloseParen, line!()));
+                        Expr::tuple(elements, ctxt)
+                    } else {
+                        try!(self.eat(Token::CloseParen, line!()));
+                        expr
+                    }
                 }
             }
             Token::Operand(Operand::Minus) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::neg(inner, ctxt)))
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::neg(inner, ctxt)
             }
             Token::Operand(Operand::Plus) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::pos(inner, ctxt)))
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::pos(inner, ctxt)
             }
             Token::Operand(Operand::Not) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::not(inner, ctxt)))
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::not(inner, ctxt)
             }
             Token::Operand(Operand::And) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::ref_(inner, ctxt)))
+                let mutability = try!(self.parse_mutability());
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::ref_(inner, mutability, ctxt)
             }
             Token::Operand(Operand::AndAnd) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::ref_(Expr::ref_(inner, ctxt), ctxt)))
+                let mutability = try!(self.parse_mutability());
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::ref_(Expr::ref_(inner, mutability, ctxt), Mutability::Immutable, ctxt)
             }
             Token::Operand(Operand::Mul) => {
-                let inner = try!(self.parse_single_expr(ctxt, line!()));
-                Ok(Some(Expr::deref(inner, ctxt)))
+                let inner = try!(self.parse_unary_expr(ctxt, line!()));
+                Expr::deref(inner, ctxt)
+            }
+            Token::Char(c) => {
+                let offset = self.record_literal();
+                Expr::char_lit(c, ctxt).spelled_at(offset)
             }
-            Token::KeywordTrue => Ok(Some(Expr::bool_lit(true, ctxt))),
-            Token::KeywordFalse => Ok(Some(Expr::bool_lit(false, ctxt))),
+            Token::KeywordTrue => Expr::bool_lit(true, ctxt),
+            Token::KeywordFalse => Expr::bool_lit(false, ctxt),
             Token::KeywordReturn => {
-                Ok(Some(Expr::ret(if let Some(e) = try!(self.maybe_parse_expr(ctxt)) {
-                                      e
-                                  } else {
-                                      Expr::unit_lit(ctxt)
-                                  },
-                                  ctxt)))
+                let ret = if let Some(e) = try!(self.maybe_parse_expr(ctxt)) {
+                    e
+                } else {
+                    Expr::unit_lit(ctxt)
+                };
+                Expr::ret(ret, ctxt)
             }
             tok => {
                 self.unget_token(tok);
-                Ok(None)
+                return Ok(None);
             }
-        }
+        };
+        Ok(Some(expr.at(line)))
     }
 
     fn parse_single_expr<'t>(&mut self,
@@ -810,6 +1556,24 @@ impl<'src> Parser<'src> {
         }
     }
 
+    fn parse_unary_expr<'t>(&mut self,
+                            ctxt: &'t TypeContext<'t>,
+                            line: u32)
+                            -> Result<Expr<'t>, ParserError> {
+        match self.maybe_parse_unary_expr(ctxt) {
+            Ok(Some(e)) => Ok(e),
+            Ok(None) => {
+                Err(ParserError::UnexpectedToken {
+                    found: try!(self.get_token()),
+                    expected: TokenType::Expression,
+                    line: self.line(),
+                    compiler: (file!(), line),
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     fn maybe_parse_expr<'t>(&mut self,
                             ctxt: &'t TypeContext<'t>)
                             -> Result<Option<Expr<'t>>, ParserError> {
@@ -817,13 +1581,18 @@ impl<'src> Parser<'src> {
             Some(l) => l,
             None => return Ok(None),
         };
-        match try!(self.maybe_eat_ty(&TokenType::Operand)) {
-            Some(Token::Operand(ref op)) => self.parse_binop(lhs, op, ctxt).map(|e| Some(e)),
-            Some(tok) => unreachable!("{:?}", tok),
+        match try!(self.maybe_eat_binop(0)) {
+            Some(op) => self.parse_binop(lhs, &op, 0, ctxt).map(|e| Some(e)),
             None => {
                 if let Some(_) = try!(self.maybe_eat(Token::Equals)) {
+                    let line = lhs.line;
                     let assign = Expr::assign(lhs, try!(self.parse_expr(ctxt, line!())), ctxt);
-                    Ok(Some(assign))
+                    Ok(Some(assign.at(line)))
+                } else if let Token::CompoundAssign(op) = try!(self.peek_token()) {
+                    try!(self.get_token());
+                    let line = lhs.line;
+                    let src = try!(self.parse_expr(ctxt, line!()));
+                    Ok(Some(Expr::compound_assign(op, lhs, src, ctxt).at(line)))
                 } else {
                     Ok(Some(lhs))
                 }
@@ -836,9 +1605,8 @@ impl<'src> Parser<'src> {
                       line: u32)
                       -> Result<Expr<'t>, ParserError> {
         let lhs = try!(self.parse_single_expr(ctxt, line));
-        match try!(self.maybe_eat_ty(&TokenType::Operand)) {
-            Some(Token::Operand(ref op)) => self.parse_binop(lhs, op, ctxt),
-            Some(tok) => unreachable!("{:?}", tok),
+        match try!(self.maybe_eat_binop(0)) {
+            Some(op) => self.parse_binop(lhs, &op, 0, ctxt),
             None => Ok(lhs),
         }
     }
@@ -864,7 +1632,7 @@ impl<'src> Parser<'src> {
             None => {
                 match try!(self.eat_ty(TokenType::Statement, line!())) {
                     Token::KeywordLet => {
-                        let name = try!(self.parse_ident(line!()));
+                        let pattern = try!(self.parse_pattern(&mut Vec::new()));
                         let ty = if let Some(_) = try!(self.maybe_eat(Token::Colon)) {
                             try!(self.parse_ty(ctxt, line!()))
                         } else {
@@ -877,7 +1645,7 @@ impl<'src> Parser<'src> {
                         };
                         try!(self.eat(Token::Semicolon, line!()));
                         Ok(Some(Left(Stmt::Let {
-                            name: name,
+                            pattern: pattern,
                             ty: ty,
                             value: expr,
                         })))
@@ -892,24 +1660,34 @@ impl<'src> Parser<'src> {
         }
     }
 
+    // after `lhs left_op`; only operators which bind more tightly than `min`
+    // are parsed, and those of equal precedence are left associative
     fn parse_binop<'t>(&mut self,
                        lhs: Expr<'t>,
                        left_op: &Operand,
+                       min: u8,
                        ctxt: &'t TypeContext<'t>)
                        -> Result<Expr<'t>, ParserError> {
-        let rhs = try!(self.parse_single_expr(ctxt, line!()));
-        match try!(self.maybe_eat_ty(&TokenType::Operand)) {
-            Some(Token::Operand(ref right_op)) => {
-                if left_op.precedence() >= right_op.precedence() {
-                    let new_lhs = left_op.expr(lhs, rhs, ctxt);
-                    self.parse_binop(new_lhs, right_op, ctxt)
-                } else {
-                    let new_rhs = try!(self.parse_binop(rhs, right_op, ctxt));
-                    Ok(left_op.expr(lhs, new_rhs, ctxt))
-                }
+        let line = self.line();
+        let mut rhs = try!(self.parse_single_expr(ctxt, line!()));
+        while let Some(right_op) = try!(self.maybe_eat_binop(left_op.precedence())) {
+            rhs = try!(self.parse_binop(rhs, &right_op, left_op.precedence(), ctxt));
+        }
+        let expr = left_op.expr(lhs, rhs, ctxt).at(line);
+        match try!(self.maybe_eat_binop(min)) {
+            Some(right_op) => self.parse_binop(expr, &right_op, min, ctxt),
+            None => Ok(expr),
+        }
+    }
+
+    // the next token, if it's a binop which binds more tightly than `min`
+    fn maybe_eat_binop(&mut self, min: u8) -> Result<Option<Operand>, ParserError> {
+        match try!(self.peek_token()) {
+            Token::Operand(op) if op != Operand::Not && op.precedence() > min => {
+                try!(self.get_token());
+                Ok(Some(op))
             }
-            Some(tok) => unreachable!("{:?}", tok),
-            None => Ok(left_op.expr(lhs, rhs, ctxt)),
+            _ => Ok(None),
         }
     }
 
@@ -919,9 +1697,13 @@ impl<'src> Parser<'src> {
         try!(self.eat(Token::OpenBrace, line!()));
         let mut body = Vec::new();
         let mut expr = None;
+        let mut lines = vec![try!(self.peek_line())];
         while let Some(st) = try!(self.parse_stmt(ctxt)) {
             match st {
-                Left(st) => body.push(st),
+                Left(st) => {
+                    body.push(st);
+                    lines.push(try!(self.peek_line()));
+                }
                 Right(e) => {
                     expr = Some(e);
                     if let Some(_) = try!(self.parse_stmt(ctxt)) {
@@ -931,31 +1713,135 @@ impl<'src> Parser<'src> {
                             compiler: fl!(),
                         });
                     } else {
+                        lines.push(try!(self.peek_line()));
                         break;
                     }
                 }
             }
         }
+        // the last line is that of the `}`
+        let end_line = lines.pop().unwrap();
         try!(self.eat(Token::CloseBrace, line!()));
-        Ok(ast::Block::new(body, expr))
+        Ok(ast::Block::new(body, expr, lines, end_line))
+    }
+
+    // `name`, `mut name`, or `(pattern, ...)`
+    // `bound` holds the names bound so far in the whole pattern, since each
+    // may only be bound once
+    fn parse_pattern(&mut self, bound: &mut Vec<String>) -> Result<Pattern, ParserError> {
+        if let Some(_) = try!(self.maybe_eat(Token::OpenParen)) {
+            let mut patterns = vec![try!(self.parse_pattern(bound))];
+            while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                patterns.push(try!(self.parse_pattern(bound)));
+            }
+            try!(self.eat(Token::CloseParen, line!()));
+            if patterns.len() == 1 {
+                Ok(patterns.pop().unwrap())
+            } else {
+                Ok(Pattern::Tuple(patterns))
+            }
+        } else {
+            let mutability = try!(self.parse_mutability());
+            let name = try!(self.parse_ident(line!()));
+            if bound.contains(&name) {
+                return Err(ParserError::DuplicatedBinding {
+                    name: name,
+                    line: self.line(),
+                    compiler: fl!(),
+                });
+            }
+            bound.push(name.clone());
+            Ok(Pattern::Binding {
+                name: name,
+                mutability: mutability,
+            })
+        }
+    }
+
+    // `mut name: ty` or `name: ty`
+    fn parse_argument<'t>(&mut self,
+                          ctxt: &'t TypeContext<'t>)
+                          -> Result<(String, Type<'t>, Mutability), ParserError> {
+        let mutability = try!(self.parse_mutability());
+        let name = try!(self.parse_ident(line!()));
+        try!(self.eat(Token::Colon, line!()));
+        Ok((name, try!(self.parse_ty(ctxt, line!())), mutability))
+    }
+
+    // `::<ty, ...>` after the name of a function, if it's there
+    fn parse_ty_args<'t>(&mut self,
+                         ctxt: &'t TypeContext<'t>)
+                         -> Result<Vec<Type<'t>>, ParserError> {
+        let mut ty_args = Vec::new();
+        if let Some(_) = try!(self.maybe_eat(Token::ColonColon)) {
+            try!(self.eat(Token::Operand(Operand::LessThan), line!()));
+            ty_args.push(try!(self.parse_ty(ctxt, line!())));
+            while let Some(_) = try!(self.maybe_eat(Token::Comma)) {
+                ty_args.push(try!(self.parse_ty(ctxt, line!())));
+            }
+            try!(self.eat(Token::Operand(Operand::GreaterThan), line!()));
+        }
+        Ok(ty_args)
     }
 
-    fn function<'t>(&mut self, ctxt: &'t TypeContext<'t>) -> Result<ast::Item<'t>, ParserError> {
+    fn parse_mutability(&mut self) -> Result<Mutability, ParserError> {
+        match try!(self.maybe_eat(Token::KeywordMut)) {
+            Some(_) => Ok(Mutability::Mutable),
+            None => Ok(Mutability::Immutable),
+        }
+    }
+
+    fn function<'t>(&mut self,
+                    attributes: Vec<ast::Attribute>,
+                    ctxt: &'t TypeContext<'t>)
+                    -> Result<ast::Item<'t>, ParserError> {
+        let (name, args, ret_ty) = try!(self.parse_signature(ctxt));
+        Ok(ast::Item::Function {
+            name: name,
+            ret: ret_ty,
+            args: args,
+            attributes: attributes,
+            body: try!(self.parse_block(ctxt)),
+        })
+    }
+
+    // `name(args) -> ty;`, after `extern fn`
+    fn extern_function<'t>(&mut self,
+                           attributes: Vec<ast::Attribute>,
+                           ctxt: &'t TypeContext<'t>)
+                           -> Result<ast::Item<'t>, ParserError> {
+        let (name, args, ret_ty) = try!(self.parse_signature(ctxt));
+        if let Some(attr) = attributes.into_iter().next() {
+            return Err(ParserError::InvalidAttribute {
+                attribute: attr,
+                function: name,
+                compiler: fl!(),
+            });
+        }
+        try!(self.eat(Token::Semicolon, line!()));
+        Ok(ast::Item::ExternFunction {
+            name: name,
+            ret: ret_ty,
+            args: args,
+        })
+    }
+
+    // `name(args) -> ty`, without the body
+    fn parse_signature<'t>(&mut self, ctxt: &'t TypeContext<'t>)
+        -> Result<(String, Vec<(String, Type<'t>, Mutability)>, Type<'t>), ParserError> {
         let name = try!(self.parse_ident(line!()));
 
         try!(self.eat(Token::OpenParen, line!()));
 
         let mut args = Vec::new();
         match try!(self.get_token()) {
-            Token::Ident(arg) => {
-                try!(self.eat(Token::Colon, line!()));
-                args.push((arg, try!(self.parse_ty(ctxt, line!()))));
+            tok @ Token::Ident(_) | tok @ Token::KeywordMut => {
+                self.unget_token(tok);
+                args.push(try!(self.parse_argument(ctxt)));
                 loop {
                     let comma_or_close_paren = try!(self.get_token());
                     if let Token::Comma = comma_or_close_paren {
-                        let name = try!(self.parse_ident(line!()));
-                        try!(self.eat(Token::Colon, line!()));
-                        args.push((name, try!(self.parse_ty(ctxt, line!()))));
+                        args.push(try!(self.parse_argument(ctxt)));
                     } else if let Token::CloseParen = comma_or_close_paren {
                         break;
                     } else {
@@ -980,17 +1866,112 @@ impl<'src> Parser<'src> {
             }
         }
 
+        // `-> !` is only allowed here, for functions which never return
         let ret_ty = match try!(self.maybe_eat(Token::SkinnyArrow)) {
-            Some(_) => try!(self.parse_ty(ctxt, line!())),
+            Some(_) => {
+                match try!(self.maybe_eat(Token::Operand(Operand::Not))) {
+                    Some(_) => Type::diverging(ctxt),
+                    None => try!(self.parse_ty(ctxt, line!())),
+                }
+            }
             None => Type::unit(ctxt),
         };
+        Ok((name, args, ret_ty))
+    }
 
+    // `NAME: ty = expr;`, after `const` (mutability is `None`) or
+    // `static`/`static mut`
+    fn global<'t>(&mut self,
+                  mutability: Option<Mutability>,
+                  attributes: Vec<ast::Attribute>,
+                  ctxt: &'t TypeContext<'t>)
+                  -> Result<ast::Item<'t>, ParserError> {
+        let name = try!(self.parse_ident(line!()));
+        if let Some(attr) = attributes.into_iter().next() {
+            return Err(ParserError::InvalidAttribute {
+                attribute: attr,
+                function: name,
+                compiler: fl!(),
+            });
+        }
+        try!(self.eat(Token::Colon, line!()));
+        let ty = try!(self.parse_ty(ctxt, line!()));
+        try!(self.eat(Token::Equals, line!()));
+        let value = try!(self.parse_expr(ctxt, line!()));
+        try!(self.eat(Token::Semicolon, line!()));
+        Ok(match mutability {
+            Some(mutability) => {
+                ast::Item::Static {
+                    name: name,
+                    ty: ty,
+                    mutability: mutability,
+                    value: value,
+                }
+            }
+            None => {
+                ast::Item::Const {
+                    name: name,
+                    ty: ty,
+                    value: value,
+                }
+            }
+        })
+    }
 
-        Ok(ast::Item::Function {
+    fn type_alias<'t>(&mut self,
+                      attributes: Vec<ast::Attribute>,
+                      ctxt: &'t TypeContext<'t>)
+                      -> Result<ast::Item<'t>, ParserError> {
+        let name = try!(self.parse_ident(line!()));
+        if let Some(attr) = attributes.into_iter().next() {
+            return Err(ParserError::InvalidAttribute {
+                attribute: attr,
+                function: name,
+                compiler: fl!(),
+            });
+        }
+        try!(self.eat(Token::Equals, line!()));
+        // the alias was already collected; this reports any errors in it,
+        // even if it's never used
+        let ty = try!(self.parse_ty(ctxt, line!()));
+        try!(self.eat(Token::Semicolon, line!()));
+        Ok(ast::Item::TypeAlias {
             name: name,
-            ret: ret_ty,
-            args: args,
-            body: try!(self.parse_block(ctxt)),
+            ty: ty,
         })
     }
 }
+
+// `s<bits>`/`u<bits>` for any width from 1 to 128, and `isize`/`usize`
+fn int_ty<'t>(name: &str, ctxt: &'t TypeContext<'t>) -> Option<Type<'t>> {
+    match name {
+        "isize" => return Some(Type::sint(ty::Int::Pointer, ctxt)),
+        "usize" => return Some(Type::uint(ty::Int::Pointer, ctxt)),
+        _ => {}
+    }
+    let signed = if name.starts_with('s') {
+        true
+    } else if name.starts_with('u') {
+        false
+    } else {
+        return None;
+    };
+    let bits = &name[1..];
+    // `s08` is not `s8`
+    if bits.starts_with('0') {
+        return None;
+    }
+    match bits.parse().ok().and_then(ty::Int::new) {
+        Some(int) if signed => Some(Type::sint(int, ctxt)),
+        Some(int) => Some(Type::uint(int, ctxt)),
+        None => None,
+    }
+}
+
+fn float_ty<'t>(name: &str, ctxt: &'t TypeContext<'t>) -> Option<Type<'t>> {
+    match name {
+        "f32" => Some(Type::float(ty::Float::F32, ctxt)),
+        "f64" => Some(Type::float(ty::Float::F64, ctxt)),
+        _ => None,
+    }
+}
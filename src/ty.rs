@@ -6,6 +6,9 @@ use typed_arena::Arena;
 pub struct TypeContext<'t> {
     backing_store: Arena<TypeVariant<'t>>,
     type_references: RefCell<HashMap<TypeVariant<'t>, &'t TypeVariant<'t>>>,
+    // the element lists of tuple types
+    tuple_store: Arena<Vec<Type<'t>>>,
+    tuple_references: RefCell<HashMap<Vec<Type<'t>>, &'t [Type<'t>]>>,
 }
 
 impl<'t> TypeContext<'t> {
@@ -13,9 +16,21 @@ impl<'t> TypeContext<'t> {
         TypeContext {
             backing_store: Arena::new(),
             type_references: RefCell::new(HashMap::new()),
+            tuple_store: Arena::new(),
+            tuple_references: RefCell::new(HashMap::new()),
         }
     }
 
+    fn get_elements(&'t self, elements: Vec<Type<'t>>) -> &'t [Type<'t>] {
+        if let Some(elts) = self.tuple_references.borrow().get(&elements) {
+            return elts;
+        }
+
+        let elts = &self.tuple_store.alloc(elements.clone())[..];
+        self.tuple_references.borrow_mut().insert(elements, elts);
+        elts
+    }
+
     fn get(&'t self, variant: TypeVariant<'t>) -> &'t TypeVariant<'t> {
         if let Some(var) = self.type_references.borrow().get(&variant) {
             return var;
@@ -51,12 +66,27 @@ impl<'t> std::fmt::Debug for Type<'t> {
         match *self.0 {
             TypeVariant::SInt(size) => write!(f, "SInt({:?})", size),
             TypeVariant::UInt(size) => write!(f, "SInt({:?})", size),
+            TypeVariant::Float(size) => write!(f, "Float({:?})", size),
+            TypeVariant::Char => write!(f, "Char"),
             TypeVariant::Bool => write!(f, "Bool"),
             TypeVariant::Unit => write!(f, "Unit"),
             TypeVariant::Diverging => write!(f, "Diverging"),
-            TypeVariant::Reference(inner) => write!(f, "Ref({:?})", inner),
+            TypeVariant::Reference(inner, Mutability::Immutable) => {
+                write!(f, "Ref({:?})", inner)
+            }
+            TypeVariant::Reference(inner, Mutability::Mutable) => {
+                write!(f, "RefMut({:?})", inner)
+            }
+            TypeVariant::RawPointer(inner, Mutability::Immutable) => {
+                write!(f, "PtrConst({:?})", inner)
+            }
+            TypeVariant::RawPointer(inner, Mutability::Mutable) => {
+                write!(f, "PtrMut({:?})", inner)
+            }
+            TypeVariant::Tuple(elements) => write!(f, "Tuple({:?})", elements),
             TypeVariant::Infer(i) => write!(f, "Infer({:?})", i),
             TypeVariant::InferInt(i) => write!(f, "InferInt({:?})", i),
+            TypeVariant::InferFloat(i) => write!(f, "InferFloat({:?})", i),
         }
     }
 }
@@ -71,12 +101,28 @@ impl<'t> Type<'t> {
         Type(ctxt.get(TypeVariant::InferInt(None)))
     }
 
+    pub fn infer_float(ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::InferFloat(None)))
+    }
+
+    // a type name the parser left as it was written, for the formatter; `id`
+    // is the name's index in the parser
+    pub fn unresolved(id: u32, ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::Infer(Some(id))))
+    }
+
     pub fn sint(int: Int, ctxt: &'t TypeContext<'t>) -> Self {
         Type(ctxt.get(TypeVariant::SInt(int)))
     }
     pub fn uint(int: Int, ctxt: &'t TypeContext<'t>) -> Self {
         Type(ctxt.get(TypeVariant::UInt(int)))
     }
+    pub fn float(float: Float, ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::Float(float)))
+    }
+    pub fn char(ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::Char))
+    }
     pub fn bool(ctxt: &'t TypeContext<'t>) -> Self {
         Type(ctxt.get(TypeVariant::Bool))
     }
@@ -88,7 +134,18 @@ impl<'t> Type<'t> {
     }
 
     pub fn ref_(ty: Type<'t>, ctxt: &'t TypeContext<'t>) -> Self {
-        Type(ctxt.get(TypeVariant::Reference(ty)))
+        Type::reference(ty, Mutability::Immutable, ctxt)
+    }
+    pub fn reference(ty: Type<'t>, mutability: Mutability, ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::Reference(ty, mutability)))
+    }
+    pub fn raw_pointer(ty: Type<'t>, mutability: Mutability, ctxt: &'t TypeContext<'t>) -> Self {
+        Type(ctxt.get(TypeVariant::RawPointer(ty, mutability)))
+    }
+    // there are no tuples of fewer than two elements; `()` is `unit`
+    pub fn tuple(elements: Vec<Type<'t>>, ctxt: &'t TypeContext<'t>) -> Self {
+        assert!(elements.len() >= 2, "ICE: tuple of {} elements", elements.len());
+        Type(ctxt.get(TypeVariant::Tuple(ctxt.get_elements(elements))))
     }
 }
 
@@ -96,15 +153,30 @@ impl<'t> Type<'t> {
 pub enum TypeVariant<'t> {
     SInt(Int),
     UInt(Int),
+    Float(Float),
+    // a unicode scalar value
+    Char,
     Bool,
     Unit,
 
     Diverging,
 
-    Reference(Type<'t>),
+    Reference(Type<'t>, Mutability),
+    // `*const T` or `*mut T`; may be null, and is only dereferenced in
+    // `unsafe` blocks
+    RawPointer(Type<'t>, Mutability),
+    // `(A, B, ...)`, with at least two elements
+    Tuple(&'t [Type<'t>]),
 
     Infer(Option<u32>),
     InferInt(Option<u32>),
+    InferFloat(Option<u32>),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Mutability {
+    Immutable,
+    Mutable,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -113,9 +185,21 @@ pub enum Int {
     I16,
     I32,
     I64,
+    I128,
+    // isize/usize; as wide as a pointer on the target
+    Pointer,
+    // any other width, from 1 to 128 bits. Use `Int::new`, so that the
+    // common widths always get their own variant
+    Other(u32),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Float {
+    F32,
+    F64,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Function<'t> {
     input: Vec<Type<'t>>,
     output: Type<'t>,
@@ -143,11 +227,15 @@ impl<'t> Type<'t> {
         match *self.0 {
             TypeVariant::SInt(_) |
             TypeVariant::UInt(_) |
+            TypeVariant::Float(_) |
+            TypeVariant::Char |
             TypeVariant::Bool |
             TypeVariant::Unit |
             TypeVariant::Diverging => true,
-            TypeVariant::Reference(inner) => inner.is_final_type(),
-            TypeVariant::Infer(_) | TypeVariant::InferInt(_) => false,
+            TypeVariant::Reference(inner, _) |
+            TypeVariant::RawPointer(inner, _) => inner.is_final_type(),
+            TypeVariant::Tuple(elements) => elements.iter().all(|elt| elt.is_final_type()),
+            TypeVariant::Infer(_) | TypeVariant::InferInt(_) | TypeVariant::InferFloat(_) => false,
         }
     }
 
@@ -160,18 +248,39 @@ impl<'t> Type<'t> {
                           ctxt: &'t TypeContext<'t>)
                           -> &'t TypeVariant<'t> {
         match *self.0 {
-            TypeVariant::Infer(None) => ctxt.get(TypeVariant::Infer(Some(uf.next_id()))),
-            TypeVariant::InferInt(None) => ctxt.get(TypeVariant::InferInt(Some(uf.next_id()))),
-            TypeVariant::Reference(inner) => {
-                ctxt.get(TypeVariant::Reference(Type(inner.get_inference_type(uf, ctxt))))
+            TypeVariant::Infer(None) => {
+                ctxt.get(TypeVariant::Infer(Some(uf.next_id(Constraint::Any))))
+            }
+            TypeVariant::InferInt(None) => {
+                ctxt.get(TypeVariant::InferInt(Some(uf.next_id(Constraint::Int))))
+            }
+            TypeVariant::InferFloat(None) => {
+                ctxt.get(TypeVariant::InferFloat(Some(uf.next_id(Constraint::Float))))
+            }
+            TypeVariant::Reference(inner, mutability) => {
+                let inner = Type(inner.get_inference_type(uf, ctxt));
+                ctxt.get(TypeVariant::Reference(inner, mutability))
+            }
+            TypeVariant::RawPointer(inner, mutability) => {
+                let inner = Type(inner.get_inference_type(uf, ctxt));
+                ctxt.get(TypeVariant::RawPointer(inner, mutability))
+            }
+            TypeVariant::Tuple(elements) => {
+                let elements = elements.iter()
+                                       .map(|elt| Type(elt.get_inference_type(uf, ctxt)))
+                                       .collect();
+                Type::tuple(elements, ctxt).0
             }
             ref t @ TypeVariant::SInt(_) |
             ref t @ TypeVariant::UInt(_) |
+            ref t @ TypeVariant::Float(_) |
+            ref t @ TypeVariant::Char |
             ref t @ TypeVariant::Bool |
             ref t @ TypeVariant::Diverging |
             ref t @ TypeVariant::Unit |
             ref t @ TypeVariant::Infer(Some(_)) |
-            ref t @ TypeVariant::InferInt(Some(_)) => t,
+            ref t @ TypeVariant::InferInt(Some(_)) |
+            ref t @ TypeVariant::InferFloat(Some(_)) => t,
         }
     }
 
@@ -190,16 +299,34 @@ impl<'t> Type<'t> {
         match *self.0 {
             TypeVariant::SInt(_) |
             TypeVariant::UInt(_) |
+            TypeVariant::Float(_) |
+            TypeVariant::Char |
             TypeVariant::Bool |
             TypeVariant::Unit |
             TypeVariant::Diverging => Some(*self),
-            TypeVariant::Reference(inner) => {
+            TypeVariant::Reference(inner, mutability) => {
+                match inner.get_final_ty(uf, ctxt) {
+                    Some(inner) => Some(Type::reference(inner, mutability, ctxt)),
+                    None => None,
+                }
+            }
+            TypeVariant::RawPointer(inner, mutability) => {
                 match inner.get_final_ty(uf, ctxt) {
-                    Some(inner) => Some(Type::ref_(inner, ctxt)),
+                    Some(inner) => Some(Type::raw_pointer(inner, mutability, ctxt)),
                     None => None,
                 }
             }
-            TypeVariant::Infer(_) | TypeVariant::InferInt(_) => {
+            TypeVariant::Tuple(elements) => {
+                let mut finals = Vec::with_capacity(elements.len());
+                for elt in elements {
+                    match elt.get_final_ty(uf, ctxt) {
+                        Some(elt) => finals.push(elt),
+                        None => return None,
+                    }
+                }
+                Some(Type::tuple(finals, ctxt))
+            }
+            TypeVariant::Infer(_) | TypeVariant::InferInt(_) | TypeVariant::InferFloat(_) => {
                 match uf.resolve(*self) {
                     Some(t) => t.get_final_ty(uf, ctxt),
                     None => return None,
@@ -212,41 +339,111 @@ impl<'t> Type<'t> {
 impl<'t> std::fmt::Display for Type<'t> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         let s = match *self.0 {
-            TypeVariant::SInt(Int::I8) => "s8",
-            TypeVariant::SInt(Int::I16) => "s16",
-            TypeVariant::SInt(Int::I32) => "s32",
-            TypeVariant::SInt(Int::I64) => "s64",
-            TypeVariant::UInt(Int::I8) => "u8",
-            TypeVariant::UInt(Int::I16) => "u16",
-            TypeVariant::UInt(Int::I32) => "u32",
-            TypeVariant::UInt(Int::I64) => "u64",
+            TypeVariant::SInt(Int::Pointer) => "isize",
+            TypeVariant::UInt(Int::Pointer) => "usize",
+            TypeVariant::SInt(int) => return write!(f, "s{}", int.bits().unwrap()),
+            TypeVariant::UInt(int) => return write!(f, "u{}", int.bits().unwrap()),
+            TypeVariant::Float(Float::F32) => "f32",
+            TypeVariant::Float(Float::F64) => "f64",
+            TypeVariant::Char => "char",
             TypeVariant::Bool => "bool",
             TypeVariant::Unit => "()",
             TypeVariant::Diverging => "!",
-            TypeVariant::Reference(inner) => return write!(f, "&{}", inner),
-            TypeVariant::Infer(_) | TypeVariant::InferInt(_) => "_",
+            TypeVariant::Reference(inner, Mutability::Immutable) => return write!(f, "&{}", inner),
+            TypeVariant::Reference(inner, Mutability::Mutable) => {
+                return write!(f, "&mut {}", inner)
+            }
+            TypeVariant::RawPointer(inner, Mutability::Immutable) => {
+                return write!(f, "*const {}", inner)
+            }
+            TypeVariant::RawPointer(inner, Mutability::Mutable) => {
+                return write!(f, "*mut {}", inner)
+            }
+            TypeVariant::Tuple(elements) => {
+                try!(write!(f, "("));
+                for (i, elt) in elements.iter().enumerate() {
+                    if i != 0 {
+                        try!(write!(f, ", "));
+                    }
+                    try!(write!(f, "{}", elt));
+                }
+                return write!(f, ")");
+            }
+            TypeVariant::Infer(_) | TypeVariant::InferInt(_) | TypeVariant::InferFloat(_) => "_",
         };
         write!(f, "{}", s)
     }
 }
 
+// the widest integer type that can be written
+pub const MAX_INT_BITS: u32 = 128;
+
 impl Int {
-    #[allow(dead_code)]
-    pub fn shift_mask(&self) -> u64 {
-        match *self {
-            Int::I8 => (1 << 3) - 1,
-            Int::I16 => (1 << 4) - 1,
-            Int::I32 => (1 << 5) - 1,
-            Int::I64 => (1 << 6) - 1,
+    pub fn new(bits: u32) -> Option<Self> {
+        match bits {
+            8 => Some(Int::I8),
+            16 => Some(Int::I16),
+            32 => Some(Int::I32),
+            64 => Some(Int::I64),
+            128 => Some(Int::I128),
+            1...MAX_INT_BITS => Some(Int::Other(bits)),
+            _ => None,
         }
     }
 
-    pub fn size(&self) -> u32 {
+    // the width, if it doesn't depend on the target
+    pub fn bits(&self) -> Option<u32> {
         match *self {
-            Int::I8 => 8,
-            Int::I16 => 16,
-            Int::I32 => 32,
-            Int::I64 => 64,
+            Int::I8 => Some(8),
+            Int::I16 => Some(16),
+            Int::I32 => Some(32),
+            Int::I64 => Some(64),
+            Int::I128 => Some(128),
+            Int::Pointer => None,
+            Int::Other(bits) => Some(bits),
+        }
+    }
+
+    // the mask which wraps a shift amount to less than the width. Only power
+    // of two widths have one
+    pub fn shift_mask(&self, pointer_size: u32) -> Option<u64> {
+        let size = self.size(pointer_size);
+        if size.is_power_of_two() {
+            Some((size - 1) as u64)
+        } else {
+            None
+        }
+    }
+
+    pub fn size(&self, pointer_size: u32) -> u32 {
+        self.bits().unwrap_or(pointer_size)
+    }
+}
+
+// what an inference group is allowed to resolve to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Constraint {
+    Any,
+    Int,
+    Float,
+}
+
+impl Constraint {
+    fn meet(self, other: Self) -> Result<Self, ()> {
+        match (self, other) {
+            (Constraint::Any, c) | (c, Constraint::Any) => Ok(c),
+            (lhs, rhs) if lhs == rhs => Ok(lhs),
+            _ => Err(()),
+        }
+    }
+
+    fn allows(self, ty: Type) -> bool {
+        match (self, *ty.0) {
+            (Constraint::Any, _) => true,
+            (Constraint::Int, TypeVariant::SInt(_)) |
+            (Constraint::Int, TypeVariant::UInt(_)) => true,
+            (Constraint::Float, TypeVariant::Float(_)) => true,
+            _ => false,
         }
     }
 }
@@ -255,6 +452,8 @@ pub struct UnionFind<'t> {
     current_id: u32,
     group_parents: Vec<u32>,
     parents_ty: Vec<Option<Type<'t>>>,
+    // only meaningful for the parent of a group
+    parents_constraint: Vec<Constraint>,
 }
 
 impl<'t> UnionFind<'t> {
@@ -263,12 +462,21 @@ impl<'t> UnionFind<'t> {
             current_id: 0,
             group_parents: Vec::new(),
             parents_ty: Vec::new(),
+            parents_constraint: Vec::new(),
         }
     }
 
-    fn union(&mut self, a: u32, b: u32) {
-        let b = self.find(b) as usize;
-        self.group_parents[b] = self.find(a);
+    fn union(&mut self, a: u32, b: u32) -> Result<(), ()> {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a == b {
+            return Ok(());
+        }
+        let constraint = try!(self.parents_constraint[a as usize]
+                                  .meet(self.parents_constraint[b as usize]));
+        self.group_parents[b as usize] = a;
+        self.parents_constraint[a as usize] = constraint;
+        Ok(())
     }
 
     fn find(&self, mut n: u32) -> u32 {
@@ -278,6 +486,17 @@ impl<'t> UnionFind<'t> {
         n
     }
 
+    // gives the group of `id` a type, if its constraint allows it
+    fn set_ty(&mut self, id: u32, ty: Type<'t>) -> Result<(), ()> {
+        let id = self.find(id) as usize;
+        if self.parents_constraint[id].allows(ty) {
+            self.parents_ty[id] = Some(ty);
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+
     pub fn unify(&mut self, a: Type<'t>, b: Type<'t>) -> Result<(), ()> {
         let a_actual = self.resolve(a);
         let b_actual = self.resolve(b);
@@ -287,8 +506,16 @@ impl<'t> UnionFind<'t> {
                     Ok(())
                 } else {
                     match (*a.0, *b.0) {
-                        (TypeVariant::Reference(lhs), TypeVariant::Reference(rhs)) => {
-                            self.unify(lhs, rhs)
+                        (TypeVariant::Reference(lhs, lmut), TypeVariant::Reference(rhs, rmut))
+                            if lmut == rmut => self.unify(lhs, rhs),
+                        (TypeVariant::RawPointer(lhs, lmut), TypeVariant::RawPointer(rhs, rmut))
+                            if lmut == rmut => self.unify(lhs, rhs),
+                        (TypeVariant::Tuple(lhs), TypeVariant::Tuple(rhs))
+                            if lhs.len() == rhs.len() => {
+                            for (&l, &r) in lhs.iter().zip(rhs) {
+                                try!(self.unify(l, r));
+                            }
+                            Ok(())
                         }
                         _ => Err(()),
                     }
@@ -302,14 +529,23 @@ impl<'t> UnionFind<'t> {
                     (TypeVariant::InferInt(Some(lid)),
                      TypeVariant::Infer(Some(rid))) |
                     (TypeVariant::InferInt(Some(lid)),
-                     TypeVariant::InferInt(Some(rid))) => {
-                        self.union(lid, rid);
-                        Ok(())
-                    }
+                     TypeVariant::InferInt(Some(rid))) |
+                    (TypeVariant::Infer(Some(lid)),
+                     TypeVariant::InferFloat(Some(rid))) |
+                    (TypeVariant::InferFloat(Some(lid)),
+                     TypeVariant::Infer(Some(rid))) |
+                    (TypeVariant::InferFloat(Some(lid)),
+                     TypeVariant::InferFloat(Some(rid))) |
+                    (TypeVariant::InferInt(Some(lid)),
+                     TypeVariant::InferFloat(Some(rid))) |
+                    (TypeVariant::InferFloat(Some(lid)),
+                     TypeVariant::InferInt(Some(rid))) => self.union(lid, rid),
                     (lhs @ TypeVariant::Infer(None), rhs) |
                     (lhs @ TypeVariant::InferInt(None), rhs) |
+                    (lhs @ TypeVariant::InferFloat(None), rhs) |
                     (rhs, lhs @ TypeVariant::Infer(None)) |
-                    (lhs, rhs @ TypeVariant::InferInt(None)) => {
+                    (lhs, rhs @ TypeVariant::InferInt(None)) |
+                    (lhs, rhs @ TypeVariant::InferFloat(None)) => {
                         panic!("ICE: attempted to unify {:?} with {:?}", lhs, rhs)
                     }
                     (l, r) => panic!("actual ty isn't working: {:?}, {:?}", l, r),
@@ -317,23 +553,12 @@ impl<'t> UnionFind<'t> {
             }
             (Some(ty), None) => {
                 match *b.0 {
-                    TypeVariant::Infer(Some(id)) => {
-                        let id = self.find(id) as usize;
-                        self.parents_ty[id] = Some(ty);
-                        Ok(())
-                    }
-                    TypeVariant::InferInt(Some(id)) => {
-                        match *ty.0 {
-                            TypeVariant::UInt(_) | TypeVariant::SInt(_) => {
-                                let id = self.find(id) as usize;
-                                self.parents_ty[id] = Some(ty);
-                                Ok(())
-                            }
-                            _ => Err(()),
-                        }
-                    }
+                    TypeVariant::Infer(Some(id)) |
+                    TypeVariant::InferInt(Some(id)) |
+                    TypeVariant::InferFloat(Some(id)) => self.set_ty(id, ty),
                     t @ TypeVariant::Infer(None) |
-                    t @ TypeVariant::InferInt(None) => {
+                    t @ TypeVariant::InferInt(None) |
+                    t @ TypeVariant::InferFloat(None) => {
                         panic!("ICE: attempted to unify {:?} with {:?}", ty, t)
                     }
                     t => panic!("ICE: resolve isn't working: {:?}", t),
@@ -341,23 +566,12 @@ impl<'t> UnionFind<'t> {
             }
             (None, Some(ty)) => {
                 match *a.0 {
-                    TypeVariant::Infer(Some(id)) => {
-                        let id = self.find(id) as usize;
-                        self.parents_ty[id] = Some(ty);
-                        Ok(())
-                    }
-                    TypeVariant::InferInt(Some(id)) => {
-                        match *ty.0 {
-                            TypeVariant::UInt(_) | TypeVariant::SInt(_) => {
-                                let id = self.find(id) as usize;
-                                self.parents_ty[id] = Some(ty);
-                                Ok(())
-                            }
-                            _ => Err(()),
-                        }
-                    }
+                    TypeVariant::Infer(Some(id)) |
+                    TypeVariant::InferInt(Some(id)) |
+                    TypeVariant::InferFloat(Some(id)) => self.set_ty(id, ty),
                     t @ TypeVariant::Infer(None) |
-                    t @ TypeVariant::InferInt(None) => {
+                    t @ TypeVariant::InferInt(None) |
+                    t @ TypeVariant::InferFloat(None) => {
                         panic!("ICE: attempted to unify {:?} with {:?}", ty, t)
                     }
                     t => panic!("ICE: resolve isn't working: {:?}", t),
@@ -368,22 +582,45 @@ impl<'t> UnionFind<'t> {
 
     pub fn resolve(&self, ty: Type<'t>) -> Option<Type<'t>> {
         match *ty.0 {
-            TypeVariant::Infer(Some(id)) | TypeVariant::InferInt(Some(id)) => {
-                self.parents_ty[self.find(id) as usize]
-            }
+            TypeVariant::Infer(Some(id)) |
+            TypeVariant::InferInt(Some(id)) |
+            TypeVariant::InferFloat(Some(id)) => self.parents_ty[self.find(id) as usize],
 
-            TypeVariant::Infer(None) | TypeVariant::InferInt(None) => None,
+            TypeVariant::Infer(None) |
+            TypeVariant::InferInt(None) |
+            TypeVariant::InferFloat(None) => None,
 
             _ => Some(ty),
         }
     }
 
-    fn next_id(&mut self) -> u32 {
+    // called once a function has been checked; integer groups which were
+    // never constrained become `s32`, and float groups become `f64`.
+    // Returns the types that were defaulted to, one per group.
+    pub fn apply_defaults(&mut self, ctxt: &'t TypeContext<'t>) -> Vec<Type<'t>> {
+        let mut defaulted = Vec::new();
+        for id in 0..self.current_id as usize {
+            if self.group_parents[id] != id as u32 || self.parents_ty[id].is_some() {
+                continue;
+            }
+            let ty = match self.parents_constraint[id] {
+                Constraint::Int => Type::sint(Int::I32, ctxt),
+                Constraint::Float => Type::float(Float::F64, ctxt),
+                Constraint::Any => continue,
+            };
+            self.parents_ty[id] = Some(ty);
+            defaulted.push(ty);
+        }
+        defaulted
+    }
+
+    fn next_id(&mut self, constraint: Constraint) -> u32 {
         if self.current_id == u32::max_value() {
             panic!()
         } else {
             self.group_parents.push(self.current_id);
             self.parents_ty.push(None);
+            self.parents_constraint.push(constraint);
             self.current_id += 1;
             self.current_id - 1
         }
@@ -11,3 +11,35 @@ for i in *sva; do
     ./test
     echo $?
 done
+
+# every program in fail/ must be rejected, with the error its first line names
+for i in fail/*sva; do
+    expected=$(head -1 $i | sed -n 's|^// fails with \([A-Za-z]*\).*|\1|p')
+    if cargo run -- -o test.o $i > fail.out 2>&1; then
+        echo "$i compiled, but should have failed with $expected"
+    elif ! grep -q "$expected" fail.out; then
+        echo "$i failed, but not with $expected:"
+        cat fail.out
+    fi
+done
+rm -f fail.out test.o
+
+# -O turns overflow checks off; these must abort with them on
+for i in test72.sva test73.sva; do
+    cargo run -- --overflow-checks -o test.o $i && cc test.o -o test && ./test && \
+        echo "$i didn't abort with --overflow-checks"
+done
+rm -f test.o
+
+# test57.sva is formatted already. test69.sva isn't: a copy of it is formatted,
+# and formatting that again must change nothing, nor what the program returns
+cargo run -- fmt --check test57.sva || echo "test57.sva is not formatted"
+cp test69.sva formatted.sva
+cargo run -- fmt formatted.sva
+cmp -s test69.sva formatted.sva && echo "test69.sva was already formatted"
+cp formatted.sva reformatted.sva
+cargo run -- fmt reformatted.sva
+cmp formatted.sva reformatted.sva || echo "formatting test69.sva twice differs"
+cargo run -- -o test.o formatted.sva && cc test.o -o test && ./test
+echo $?
+rm -f formatted.sva reformatted.sva test.o